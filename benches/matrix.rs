@@ -0,0 +1,53 @@
+use concurrency::{
+    multiply, multiply_with_config, Granularity, Matrix, MultiplyConfig, MultiplyContext, Padding,
+};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+fn square(n: usize) -> Matrix<i64> {
+    Matrix::from_fn(n, n, |i, j| ((i * n + j) % 10) as i64)
+}
+
+fn bench_multiply(c: &mut Criterion) {
+    let mut group = c.benchmark_group("multiply");
+    for n in [8, 32, 64] {
+        let a = square(n);
+        let b = square(n);
+        group.bench_with_input(BenchmarkId::new("per_cell", n), &n, |bench, _| {
+            bench.iter(|| multiply(black_box(&a), black_box(&b)).unwrap());
+        });
+        let chunked = MultiplyConfig {
+            granularity: Granularity::Rows(4),
+            ..Default::default()
+        };
+        group.bench_with_input(BenchmarkId::new("chunked", n), &n, |bench, _| {
+            bench.iter(|| multiply_with_config(black_box(&a), black_box(&b), &chunked).unwrap());
+        });
+        let tiled = MultiplyConfig {
+            granularity: Granularity::Tiles(32),
+            ..Default::default()
+        };
+        group.bench_with_input(BenchmarkId::new("tiled", n), &n, |bench, _| {
+            bench.iter(|| multiply_with_config(black_box(&a), black_box(&b), &tiled).unwrap());
+        });
+        group.bench_with_input(BenchmarkId::new("context", n), &n, |bench, _| {
+            let mut ctx = MultiplyContext::new(&MultiplyConfig::default());
+            bench.iter(|| ctx.multiply(black_box(&a), black_box(&b)).unwrap());
+        });
+    }
+    group.finish();
+}
+
+fn bench_convolve(c: &mut Criterion) {
+    let mut group = c.benchmark_group("convolve");
+    let kernel = Matrix::new([1, 2, 1, 2, 4, 2, 1, 2, 1], 3, 3);
+    for n in [64, 256] {
+        let a = square(n);
+        group.bench_with_input(BenchmarkId::new("same_3x3", n), &n, |bench, _| {
+            bench.iter(|| black_box(&a).convolve(&kernel, Padding::Same).unwrap());
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_multiply, bench_convolve);
+criterion_main!(benches);
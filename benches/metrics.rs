@@ -0,0 +1,41 @@
+use std::thread;
+
+use concurrency::{AmapMetrics, CmapMetrics};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+const KEYS: [&str; 4] = ["req.page.1", "req.page.2", "req.page.3", "req.page.4"];
+const INC_PER_THREAD: usize = 10_000;
+
+fn run_threads(threads: usize, f: impl Fn(&'static str) + Sync) {
+    thread::scope(|s| {
+        for t in 0..threads {
+            let f = &f;
+            s.spawn(move || {
+                for i in 0..INC_PER_THREAD {
+                    f(KEYS[(t + i) % KEYS.len()]);
+                }
+            });
+        }
+    });
+}
+
+fn bench_metrics_inc(c: &mut Criterion) {
+    let mut group = c.benchmark_group("metrics_inc");
+    for threads in [1, 2, 4, 8] {
+        group.throughput(Throughput::Elements((threads * INC_PER_THREAD) as u64));
+
+        group.bench_with_input(BenchmarkId::new("amap", threads), &threads, |b, &n| {
+            let metrics = AmapMetrics::new(&KEYS);
+            b.iter(|| run_threads(n, |key| metrics.inc(key).unwrap()));
+        });
+
+        group.bench_with_input(BenchmarkId::new("cmap", threads), &threads, |b, &n| {
+            let metrics = CmapMetrics::new();
+            b.iter(|| run_threads(n, |key| metrics.inc(key).unwrap()));
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_metrics_inc);
+criterion_main!(benches);
@@ -1,50 +1,430 @@
-use std::{io, net::SocketAddr};
+use std::{
+    env, io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
-use anyhow::Result;
-use tokio::{io::AsyncWriteExt, net::TcpListener};
+use anyhow::{Context, Result};
+use bytes::{Buf, BytesMut};
+use concurrency::{split_frame, ParseConfig, ParseMode, ProtocolError};
+use thiserror::Error;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::Semaphore,
+};
 use tracing::{info, warn};
+use tracing_appender::{non_blocking::WorkerGuard, rolling::Rotation};
+use tracing_subscriber::EnvFilter;
 
 const BUF_SIZE: usize = 4096;
+// the latency average halves for every this long without a new sample
+const LATENCY_HALF_LIFE: Duration = Duration::from_secs(1);
+
+/// Everything is read from the environment:
+///
+/// - `DREDIS_ADDR`: listen address, `0.0.0.0:6379` by default
+/// - `DREDIS_HEALTH_ADDR`: serve `/healthz` and `/readyz` over HTTP on this address
+/// - `DREDIS_PARSE_MODE`: `lenient` accepts inline commands and bare `\n` line endings
+/// - `DREDIS_LOG`: per-module levels like `info,dredis=debug`, falls back to `RUST_LOG`
+/// - `DREDIS_LOG_FORMAT`: `text` (default), `pretty` or `json`
+/// - `DREDIS_LOG_FILE`: log to this file instead of stdout
+/// - `DREDIS_LOG_ROTATION`: `never` (default), `minutely`, `hourly` or `daily`, for the file
+/// - `DREDIS_MAX_CONNECTIONS`: connections served at once, 1024 by default
+/// - `DREDIS_MAX_BUFFERED_BYTES`: unparsed input held for all clients, 64 MiB by default
+/// - `DREDIS_MAX_LATENCY_MS`: average time to process a frame, not counting writing
+///   the reply, before new clients are turned away, 100 by default
+#[derive(Debug)]
+struct ServerConfig {
+    addr: String,
+    health_addr: Option<String>,
+    parse: ParseConfig,
+    log: LogConfig,
+    limits: Limits,
+}
+
+#[derive(Debug, Clone)]
+struct Limits {
+    max_connections: usize,
+    max_buffered: usize,
+    max_latency: Duration,
+}
+
+/// Load figures shared by every connection, the accept loop sheds new clients on them.
+#[derive(Debug)]
+struct Load {
+    // bytes read from clients but not parsed yet
+    buffered: AtomicUsize,
+    // moving average of the time to parse a frame and have its reply ready, in
+    // microseconds. Writing the reply is left out, a client that does not read its
+    // replies must not make the server look slow
+    latency_us: AtomicU64,
+    // when `latency_us` took its last sample, in microseconds since `epoch`
+    sampled_us: AtomicU64,
+    epoch: Instant,
+}
+
+// keeps the share of one connection in `Load::buffered` up to date
+struct BufferedBytes<'a> {
+    load: &'a Load,
+    bytes: usize,
+}
+
+#[derive(Debug)]
+struct LogConfig {
+    filter: String,
+    format: LogFormat,
+    file: Option<PathBuf>,
+    rotation: Rotation,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LogFormat {
+    Text,
+    Pretty,
+    Json,
+}
+
+#[derive(Debug, Error)]
+enum DredisError {
+    #[error("Dredis error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Dredis error: {0}")]
+    Protocol(#[from] ProtocolError),
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    tracing_subscriber::fmt::init();
-    let addr = "0.0.0.0:6379";
+    let ServerConfig {
+        addr,
+        health_addr,
+        parse,
+        log,
+        limits,
+    } = ServerConfig::from_env()?;
+    // flushes the buffered log lines when main returns
+    let _guard = init_logging(&log)?;
 
-    let listener = TcpListener::bind(addr).await?;
+    // there is no store to load or log to replay, so ready means accepting connections
+    let ready = Arc::new(AtomicBool::new(false));
+    if let Some(health_addr) = health_addr {
+        let health = TcpListener::bind(&health_addr).await?;
+        info!("Health probes listening on: {}", health_addr);
+        tokio::spawn(serve_health(health, Arc::clone(&ready)));
+    }
+
+    let listener = TcpListener::bind(&addr).await?;
+    ready.store(true, Ordering::Release);
 
     info!("Dummy redis server listening on: {}", addr);
 
+    let connections = Arc::new(Semaphore::new(limits.max_connections));
+    let load = Arc::new(Load::new());
     loop {
         let (stream, raddr) = listener.accept().await?;
+
+        let Ok(permit) = Arc::clone(&connections).try_acquire_owned() else {
+            shed(stream, raddr, "BUSY too many connections");
+            continue;
+        };
+        // with nobody connected the latency average is stale, don't hold it against anyone
+        let idle = connections.available_permits() + 1 == limits.max_connections;
+        if let Some(reason) = load.overloaded(&limits, idle) {
+            shed(stream, raddr, reason);
+            continue;
+        }
+
         info!("Accept connection from {}", raddr);
+        let config = parse.clone();
+        let (load, limits) = (Arc::clone(&load), limits.clone());
         tokio::spawn(async move {
-            if let Err(e) = process_redis_conn(stream, raddr).await {
+            if let Err(e) = process_redis_conn(stream, raddr, &config, &load, &limits).await {
                 warn!("Error processing connection with {}: {:?}", raddr, e);
             };
+            drop(permit);
+        });
+    }
+}
+
+// reply with an error and close, without reading anything from the client
+fn shed(mut stream: TcpStream, raddr: SocketAddr, reason: &'static str) {
+    warn!("Shedding connection from {}: {}", raddr, reason);
+    tokio::spawn(async move {
+        let _ = stream
+            .write_all(format!("-{}\r\n", reason).as_bytes())
+            .await;
+    });
+}
+
+impl Load {
+    fn new() -> Self {
+        Self {
+            buffered: AtomicUsize::new(0),
+            latency_us: AtomicU64::new(0),
+            sampled_us: AtomicU64::new(0),
+            epoch: Instant::now(),
+        }
+    }
+
+    fn overloaded(&self, limits: &Limits, idle: bool) -> Option<&'static str> {
+        if self.buffered.load(Ordering::Relaxed) > limits.max_buffered {
+            return Some("BUSY out of buffer memory");
+        }
+        if !idle && self.latency() > limits.max_latency {
+            return Some("BUSY replies are too slow");
+        }
+        None
+    }
+
+    // the average decayed since its last sample, so a slow burst is forgotten even
+    // when the clients still connected stay quiet
+    fn latency(&self) -> Duration {
+        let since = self
+            .now_us()
+            .saturating_sub(self.sampled_us.load(Ordering::Relaxed));
+        let halvings = since / LATENCY_HALF_LIFE.as_micros() as u64;
+        let average = self.latency_us.load(Ordering::Relaxed);
+        let decayed = u32::try_from(halvings)
+            .ok()
+            .and_then(|h| average.checked_shr(h))
+            .unwrap_or(0);
+        Duration::from_micros(decayed)
+    }
+
+    fn record_latency(&self, latency: Duration) {
+        let sample = u64::try_from(latency.as_micros()).unwrap_or(u64::MAX);
+        // racing updates may drop a sample, fine for an average
+        let old = self.latency().as_micros() as u64;
+        self.latency_us
+            .store(old - old / 8 + sample / 8, Ordering::Relaxed);
+        self.sampled_us.store(self.now_us(), Ordering::Relaxed);
+    }
+
+    fn now_us(&self) -> u64 {
+        u64::try_from(self.epoch.elapsed().as_micros()).unwrap_or(u64::MAX)
+    }
+}
+
+impl BufferedBytes<'_> {
+    fn set(&mut self, bytes: usize) {
+        if bytes > self.bytes {
+            self.load
+                .buffered
+                .fetch_add(bytes - self.bytes, Ordering::Relaxed);
+        } else {
+            self.load
+                .buffered
+                .fetch_sub(self.bytes - bytes, Ordering::Relaxed);
+        }
+        self.bytes = bytes;
+    }
+}
+
+impl Drop for BufferedBytes<'_> {
+    fn drop(&mut self) {
+        self.set(0);
+    }
+}
+
+impl ServerConfig {
+    fn from_env() -> Result<Self> {
+        let var = |name| env::var(name).ok();
+        let number = |name, default: u64| -> Result<u64> {
+            match var(name) {
+                Some(v) => v
+                    .parse()
+                    .with_context(|| format!("invalid {} {:?}", name, v)),
+                None => Ok(default),
+            }
+        };
+
+        let mode = match var("DREDIS_PARSE_MODE").as_deref() {
+            None | Some("strict") => ParseMode::Strict,
+            Some("lenient") => ParseMode::Lenient,
+            Some(other) => anyhow::bail!("unknown DREDIS_PARSE_MODE {:?}", other),
+        };
+        let format = match var("DREDIS_LOG_FORMAT").as_deref() {
+            None | Some("text") => LogFormat::Text,
+            Some("pretty") => LogFormat::Pretty,
+            Some("json") => LogFormat::Json,
+            Some(other) => anyhow::bail!("unknown DREDIS_LOG_FORMAT {:?}", other),
+        };
+        let rotation = match var("DREDIS_LOG_ROTATION").as_deref() {
+            None | Some("never") => Rotation::NEVER,
+            Some("minutely") => Rotation::MINUTELY,
+            Some("hourly") => Rotation::HOURLY,
+            Some("daily") => Rotation::DAILY,
+            Some(other) => anyhow::bail!("unknown DREDIS_LOG_ROTATION {:?}", other),
+        };
+
+        Ok(Self {
+            addr: var("DREDIS_ADDR").unwrap_or_else(|| "0.0.0.0:6379".to_string()),
+            health_addr: var("DREDIS_HEALTH_ADDR"),
+            parse: ParseConfig {
+                mode,
+                ..Default::default()
+            },
+            log: LogConfig {
+                filter: var("DREDIS_LOG")
+                    .or_else(|| var("RUST_LOG"))
+                    .unwrap_or_else(|| "info".to_string()),
+                format,
+                file: var("DREDIS_LOG_FILE").map(PathBuf::from),
+                rotation,
+            },
+            limits: Limits {
+                max_connections: number("DREDIS_MAX_CONNECTIONS", 1024)? as usize,
+                max_buffered: number("DREDIS_MAX_BUFFERED_BYTES", 64 << 20)? as usize,
+                max_latency: Duration::from_millis(number("DREDIS_MAX_LATENCY_MS", 100)?),
+            },
+        })
+    }
+}
+
+fn init_logging(config: &LogConfig) -> Result<WorkerGuard> {
+    let filter = EnvFilter::try_new(&config.filter)
+        .with_context(|| format!("invalid log filter {:?}", config.filter))?;
+
+    let (writer, guard) = match &config.file {
+        Some(path) => {
+            let dir = path.parent().unwrap_or(Path::new("."));
+            let name = path
+                .file_name()
+                .with_context(|| format!("invalid log file {}", path.display()))?;
+            let appender = tracing_appender::rolling::RollingFileAppender::new(
+                config.rotation.clone(),
+                dir,
+                name,
+            );
+            tracing_appender::non_blocking(appender)
+        }
+        None => tracing_appender::non_blocking(io::stdout()),
+    };
+
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(writer)
+        .with_ansi(config.file.is_none());
+    let rst = match config.format {
+        LogFormat::Text => builder.try_init(),
+        LogFormat::Pretty => builder.pretty().try_init(),
+        LogFormat::Json => builder.json().try_init(),
+    };
+    rst.map_err(|e| anyhow::anyhow!("init logging: {}", e))?;
+
+    Ok(guard)
+}
+
+// a minimal HTTP/1.1 responder, one request per connection
+async fn serve_health(listener: TcpListener, ready: Arc<AtomicBool>) {
+    loop {
+        let (stream, raddr) = match listener.accept().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                warn!("Health accept error: {:?}", e);
+                continue;
+            }
+        };
+        let ready = Arc::clone(&ready);
+        tokio::spawn(async move {
+            if let Err(e) = answer_probe(stream, &ready).await {
+                warn!("Health probe from {} failed: {:?}", raddr, e);
+            }
         });
     }
 }
 
-async fn process_redis_conn(mut stream: tokio::net::TcpStream, raddr: SocketAddr) -> Result<()> {
+async fn answer_probe(mut stream: TcpStream, ready: &AtomicBool) -> io::Result<()> {
+    // only the request line matters, e.g. `GET /readyz HTTP/1.1`
+    let mut buf = [0u8; 1024];
+    let mut len = 0;
+    while len < buf.len() && !buf[..len].windows(2).any(|w| w == b"\r\n") {
+        match stream.read(&mut buf[len..]).await? {
+            0 => break,
+            n => len += n,
+        }
+    }
+    let request = String::from_utf8_lossy(&buf[..len]);
+    let mut parts = request.split_whitespace();
+
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/healthz")) => ("200 OK", "ok"),
+        (Some("GET"), Some("/readyz")) if ready.load(Ordering::Acquire) => ("200 OK", "ready"),
+        (Some("GET"), Some("/readyz")) => ("503 Service Unavailable", "not ready"),
+        (Some("GET"), _) => ("404 Not Found", "not found"),
+        _ => ("405 Method Not Allowed", "method not allowed"),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+async fn process_redis_conn(
+    mut stream: TcpStream,
+    raddr: SocketAddr,
+    config: &ParseConfig,
+    load: &Load,
+    limits: &Limits,
+) -> Result<(), DredisError> {
+    // bytes read but not parsed yet, a frame may span several reads. Parsed frames
+    // are split off the front and their bulk strings keep pointing into it
+    let mut buf = BytesMut::with_capacity(BUF_SIZE);
+    let mut buffered = BufferedBytes { load, bytes: 0 };
     loop {
         stream.readable().await?;
 
-        let mut buf = Vec::with_capacity(BUF_SIZE);
-
         match stream.try_read_buf(&mut buf) {
             Ok(0) => break, // EOF
             Ok(n) => {
                 info!("Read {} bytes from client", n);
-                let line = String::from_utf8_lossy(&buf);
-                info!("{:?}", line);
-                stream.write_all(b"+OK\r\n").await?;
+                buffered.set(buf.len());
+                if load.buffered.load(Ordering::Relaxed) > limits.max_buffered {
+                    stream.write_all(b"-BUSY out of buffer memory\r\n").await?;
+                    warn!("Dropping {}, out of buffer memory", raddr);
+                    return Ok(());
+                }
             }
             Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
                 continue;
             }
             Err(e) => return Err(e.into()),
         }
+
+        loop {
+            let started = Instant::now();
+            match split_frame(&mut buf, config) {
+                Ok(Some(frame)) => {
+                    info!("{:?}", frame);
+                    // before the write, a client slow to read its replies only
+                    // holds up itself
+                    load.record_latency(started.elapsed());
+                    stream.write_all(b"+OK\r\n").await?;
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    let reply = format!("-ERR {}\r\n", e);
+                    stream.write_all(reply.as_bytes()).await?;
+                    if config.mode == ParseMode::Strict {
+                        return Err(e.into());
+                    }
+                    // skip the broken line, or everything when it is not complete yet
+                    warn!("Skipping malformed input from {}: {}", raddr, e);
+                    let skip = e.resume_offset(&buf).unwrap_or(buf.len());
+                    buf.advance(skip);
+                }
+            }
+        }
+        buffered.set(buf.len());
     }
     warn!("Connection with {} closed", raddr);
     Ok(())
@@ -49,7 +49,7 @@ fn producer(idx: usize, tx: mpsc::Sender<Msg>) -> Result<()> {
         tx.send(Msg::new(idx, value))?;
         let sleep_time = rand::random::<u8>() as u64 * 10;
         thread::sleep(time::Duration::from_millis(sleep_time));
-        if rand::random::<u8>() % 5 == 0 {
+        if rand::random::<u8>().is_multiple_of(5) {
             println!("producer {} exit", idx);
             break;
         }
@@ -0,0 +1,97 @@
+#ifndef CONCURRENCY_H
+#define CONCURRENCY_H
+
+/* Generated by cbindgen, do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+typedef struct CmapMetrics CmapMetrics;
+
+typedef struct Matrix Matrix;
+
+/*
+ Create a `row` x `col` matrix by copying `row * col` values from `data`.
+
+ # Safety
+
+ `data` must point to at least `row * col` readable `double`s.
+ */
+struct Matrix *matrix_new(const double *data, uintptr_t row, uintptr_t col);
+
+/*
+ Multiply `a` by `b` on the worker threads, null if the shapes don't match.
+
+ # Safety
+
+ `a` and `b` must be live matrices returned by this library.
+ */
+struct Matrix *matrix_multiply(const struct Matrix *a, const struct Matrix *b);
+
+/*
+ # Safety
+
+ `m` must be a live matrix returned by this library.
+ */
+uintptr_t matrix_row(const struct Matrix *m);
+
+/*
+ # Safety
+
+ `m` must be a live matrix returned by this library.
+ */
+uintptr_t matrix_col(const struct Matrix *m);
+
+/*
+ The `row * col` elements in row-major order, valid until the matrix is freed.
+
+ # Safety
+
+ `m` must be a live matrix returned by this library.
+ */
+const double *matrix_data(const struct Matrix *m);
+
+/*
+ # Safety
+
+ `m` must be null or a matrix returned by this library that was not freed yet.
+ */
+void matrix_free(struct Matrix *m);
+
+struct CmapMetrics *metrics_new(void);
+
+/*
+ Increase the counter named by the NUL-terminated UTF-8 string `key`.
+
+ # Safety
+
+ `metrics` must be live metrics returned by this library and `key` a valid C string.
+ */
+int metrics_inc(const struct CmapMetrics *metrics, const char *key);
+
+/*
+ All counters as a JSON object, e.g. `{"req.page.1":3}`. Release it with `string_free`.
+
+ # Safety
+
+ `metrics` must be live metrics returned by this library.
+ */
+char *metrics_snapshot_json(const struct CmapMetrics *metrics);
+
+/*
+ # Safety
+
+ `metrics` must be null or metrics returned by this library that were not freed yet.
+ */
+void metrics_free(struct CmapMetrics *metrics);
+
+/*
+ # Safety
+
+ `s` must be null or a string returned by this library that was not freed yet.
+ */
+void string_free(char *s);
+
+#endif /* CONCURRENCY_H */
@@ -0,0 +1,141 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use concurrency::{multiply_with_progress, Granularity, Matrix, MultiplyConfig};
+
+/// Multiply two matrices read from CSV or JSON files.
+///
+/// A CSV file holds one matrix row per line, a JSON file holds an array of rows,
+/// e.g. `[[1, 2], [3, 4]]`. The format is picked from the file extension.
+#[derive(Debug, Parser)]
+#[command(name = "matrix-cli", version)]
+struct Args {
+    /// left operand
+    a: PathBuf,
+    /// right operand
+    b: PathBuf,
+    /// where to write the product, stdout (as CSV) when omitted
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// number of worker threads
+    #[arg(short, long, default_value_t = MultiplyConfig::default().threads)]
+    threads: usize,
+    /// send each worker blocks of this many output rows instead of single cells
+    #[arg(long, conflicts_with = "tile")]
+    chunk_rows: Option<usize>,
+    /// send each worker square tiles of the output with this side, multiplied in
+    /// cache-sized blocks
+    #[arg(long)]
+    tile: Option<usize>,
+    /// multiply on the calling thread below this many multiply-adds
+    #[arg(long, default_value_t = MultiplyConfig::default().serial_threshold)]
+    serial_threshold: usize,
+    /// show the percentage of output cells done on stderr
+    #[arg(long)]
+    progress: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Csv,
+    Json,
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+
+    let a = read_matrix(&args.a)?;
+    let b = read_matrix(&args.b)?;
+    let config = MultiplyConfig {
+        threads: args.threads,
+        granularity: match (args.chunk_rows, args.tile) {
+            (_, Some(side)) => Granularity::Tiles(side),
+            (Some(rows), None) => Granularity::Rows(rows),
+            (None, None) => Granularity::Cell,
+        },
+        serial_threshold: args.serial_threshold,
+    };
+    let c = multiply_with_progress(&a, &b, &config, |done, total| {
+        if args.progress && total > 0 {
+            eprint!("\r{}%", done * 100 / total);
+            if done == total {
+                eprintln!();
+            }
+        }
+    })?;
+
+    match args.output {
+        Some(path) => {
+            let content = render_matrix(&c, Format::from_path(&path));
+            fs::write(&path, content).with_context(|| format!("write {}", path.display()))?;
+        }
+        None => io::stdout().write_all(render_matrix(&c, Format::Csv).as_bytes())?,
+    }
+
+    Ok(())
+}
+
+impl Format {
+    fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => Format::Json,
+            _ => Format::Csv,
+        }
+    }
+}
+
+fn read_matrix(path: &Path) -> Result<Matrix<f64>> {
+    let content = fs::read_to_string(path).with_context(|| format!("read {}", path.display()))?;
+    let rows = match Format::from_path(path) {
+        Format::Json => serde_json::from_str::<Vec<Vec<f64>>>(&content)
+            .with_context(|| format!("parse {}", path.display()))?,
+        Format::Csv => parse_csv(&content).with_context(|| format!("parse {}", path.display()))?,
+    };
+
+    Matrix::try_from_rows(rows).with_context(|| format!("shape of {}", path.display()))
+}
+
+fn parse_csv(content: &str) -> Result<Vec<Vec<f64>>> {
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            line.split(',')
+                .enumerate()
+                .map(|(j, cell)| {
+                    cell.trim().parse::<f64>().with_context(|| {
+                        format!(
+                            "invalid number {:?} at line {}, column {}",
+                            cell,
+                            i + 1,
+                            j + 1
+                        )
+                    })
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn render_matrix(m: &Matrix<f64>, format: Format) -> String {
+    let rows = m.as_slice().chunks(m.col().max(1));
+    match format {
+        Format::Json => {
+            let rows = rows.map(|r| r.to_vec()).collect::<Vec<_>>();
+            // a Vec<Vec<f64>> always serializes
+            serde_json::to_string(&rows).unwrap_or_default() + "\n"
+        }
+        Format::Csv => rows
+            .map(|r| {
+                let cells = r.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+                cells.join(",") + "\n"
+            })
+            .collect(),
+    }
+}
@@ -0,0 +1,288 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    fmt::Display,
+    io::{self, BufReader, BufWriter, Read, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    ops::{Add, AddAssign, Mul},
+    thread,
+};
+
+use crate::matrix::row_major_operands;
+use crate::{multiply, ChannelError, Error, Matrix, MatrixError};
+
+// frames bigger than this are rejected instead of allocated
+const MAX_FRAME_LEN: usize = 1 << 30;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct WireMatrix<T> {
+    row: usize,
+    col: usize,
+    data: Vec<T>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Request<T> {
+    a: WireMatrix<T>,
+    b: WireMatrix<T>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum Response<T> {
+    Ok(WireMatrix<T>),
+    Err(String),
+}
+
+/// Serve block multiplications for coordinators calling `multiply_distributed`.
+///
+/// Every connection is handled on its own thread and may send any number of
+/// requests. Returns only when accepting fails.
+pub fn serve_worker<T>(listener: TcpListener) -> Result<(), ChannelError>
+where
+    T: Serialize
+        + DeserializeOwned
+        + Display
+        + Mul<Output = T>
+        + Add<Output = T>
+        + AddAssign
+        + Default
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    for stream in listener.incoming() {
+        let stream = stream?;
+        thread::spawn(move || {
+            if let Err(e) = handle_coordinator::<T>(stream) {
+                eprintln!("Worker connection error: {:?}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+/// Multiply `a` by `b` on remote workers started with `serve_worker`.
+///
+/// map/reduce: the rows of `a` are split into one block per worker, each worker
+/// multiplies its block by `b`, and the blocks are stacked back in order.
+pub fn multiply_distributed<T, A>(
+    a: &Matrix<T>,
+    b: &Matrix<T>,
+    workers: &[A],
+) -> Result<Matrix<T>, Error>
+where
+    T: Serialize + DeserializeOwned + Default + Clone + Send + Sync,
+    A: ToSocketAddrs + Sync,
+{
+    let (a, b) = &row_major_operands(a, b)?;
+    if workers.is_empty() {
+        return Err(MatrixError::NoWorkers.into());
+    }
+    if a.row() == 0 {
+        return Ok(Matrix::new(Vec::new(), 0, b.col()));
+    }
+    // every dot product is empty, and `a` has no elements to split into blocks
+    if a.col() == 0 {
+        return Ok(Matrix::new(
+            vec![T::default(); a.row() * b.col()],
+            a.row(),
+            b.col(),
+        ));
+    }
+
+    let blocks = workers.len().min(a.row());
+    let block_rows = a.row().div_ceil(blocks);
+    let wire_b = WireMatrix {
+        row: b.row(),
+        col: b.col(),
+        data: b.as_slice().to_vec(),
+    };
+
+    // map/reduce: map phase
+    let results = thread::scope(|s| {
+        let handles = a
+            .as_slice()
+            .chunks(block_rows * a.col())
+            .zip(workers)
+            .map(|(rows, addr)| {
+                let wire_b = &wire_b;
+                s.spawn(move || {
+                    let block = WireMatrix {
+                        row: rows.len() / a.col(),
+                        col: a.col(),
+                        data: rows.to_vec(),
+                    };
+                    request_block(addr, block, wire_b)
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|h| match h.join() {
+                Ok(block) => Ok(block?),
+                Err(_) => Err(MatrixError::WorkerFailed.into()),
+            })
+            .collect::<Result<Vec<_>, Error>>()
+    })?;
+
+    // map/reduce: reduce phase
+    let mut data = Vec::with_capacity(a.row() * b.col());
+    for block in results {
+        data.extend(block.data);
+    }
+    if data.len() != a.row() * b.col() {
+        return Err(ChannelError::WrongShape.into());
+    }
+
+    Ok(Matrix::new(data, a.row(), b.col()))
+}
+
+fn request_block<T, A>(
+    addr: &A,
+    a: WireMatrix<T>,
+    b: &WireMatrix<T>,
+) -> Result<WireMatrix<T>, ChannelError>
+where
+    T: Serialize + DeserializeOwned + Clone,
+    A: ToSocketAddrs,
+{
+    let stream = TcpStream::connect(addr)?;
+    let mut writer = BufWriter::new(&stream);
+    let mut reader = BufReader::new(&stream);
+
+    // borrow b instead of cloning it for every block
+    #[derive(Serialize)]
+    struct RequestRef<'a, T> {
+        a: &'a WireMatrix<T>,
+        b: &'a WireMatrix<T>,
+    }
+    write_frame(&mut writer, &RequestRef { a: &a, b })?;
+    writer.flush()?;
+
+    match read_frame::<Response<T>>(&mut reader)? {
+        Some(Response::Ok(block)) if block.row == a.row && block.col == b.col => Ok(block),
+        Some(Response::Ok(_)) => Err(ChannelError::WrongShape),
+        Some(Response::Err(e)) => Err(ChannelError::Remote(e)),
+        None => Err(ChannelError::Closed),
+    }
+}
+
+fn handle_coordinator<T>(stream: TcpStream) -> Result<(), ChannelError>
+where
+    T: Serialize
+        + DeserializeOwned
+        + Display
+        + Mul<Output = T>
+        + Add<Output = T>
+        + AddAssign
+        + Default
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    let mut reader = BufReader::new(&stream);
+    let mut writer = BufWriter::new(&stream);
+
+    while let Some(req) = read_frame::<Request<T>>(&mut reader)? {
+        let a = Matrix::new(req.a.data, req.a.row, req.a.col);
+        let b = Matrix::new(req.b.data, req.b.row, req.b.col);
+        let resp =
+            if a.as_slice().len() != a.row() * a.col() || b.as_slice().len() != b.row() * b.col() {
+                Response::Err("matrix data does not match its shape".to_string())
+            } else {
+                match multiply(&a, &b) {
+                    Ok(c) => Response::Ok(WireMatrix {
+                        row: c.row(),
+                        col: c.col(),
+                        data: c.as_slice().to_vec(),
+                    }),
+                    Err(e) => Response::Err(e.to_string()),
+                }
+            };
+        write_frame(&mut writer, &resp)?;
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+// a frame is a big-endian u32 length followed by that many bytes of json. This is
+// not `resp`: that module speaks RESP to redis clients and needs the whole frame
+// buffered to find its end, while a block here is one json body of known length
+fn write_frame<W: Write>(writer: &mut W, value: &impl Serialize) -> Result<(), ChannelError> {
+    let body = serde_json::to_vec(value)?;
+    if body.len() > MAX_FRAME_LEN {
+        return Err(ChannelError::FrameTooLarge(body.len()));
+    }
+    writer.write_all(&(body.len() as u32).to_be_bytes())?;
+    writer.write_all(&body)?;
+    Ok(())
+}
+
+// None on a clean EOF before the next frame
+fn read_frame<T: DeserializeOwned>(reader: &mut impl Read) -> Result<Option<T>, ChannelError> {
+    let mut len = [0u8; 4];
+    match reader.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(ChannelError::FrameTooLarge(len));
+    }
+    let mut body = vec![0u8; len];
+    reader.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddr;
+
+    fn start_worker() -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || serve_worker::<i64>(listener));
+        addr
+    }
+
+    #[test]
+    fn test_multiply_distributed() -> Result<(), Error> {
+        let workers = [start_worker(), start_worker()];
+
+        let a = Matrix::new((1..=15).collect::<Vec<i64>>(), 5, 3);
+        let b = Matrix::new((1..=6).collect::<Vec<i64>>(), 3, 2);
+        let c = multiply_distributed(&a, &b, &workers)?;
+        assert_eq!(c.as_slice(), multiply(&a, &b)?.as_slice());
+
+        // more workers than rows
+        let a = Matrix::new([1, 2, 3], 1, 3);
+        let c = multiply_distributed(&a, &b, &workers)?;
+        assert_eq!(format!("{c}"), "{22 28}");
+
+        // zero inner dimension, nothing to send but the output is not empty
+        let a = Matrix::new(Vec::<i64>::new(), 2, 0);
+        let b = Matrix::new(Vec::<i64>::new(), 0, 3);
+        let c = multiply_distributed(&a, &b, &workers)?;
+        assert_eq!((c.row(), c.col()), (2, 3));
+        assert_eq!(c.as_slice(), [0; 6]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_multiply_distributed_errors() {
+        let workers = [start_worker()];
+        let a = Matrix::new([1i64, 2, 3, 4], 2, 2);
+        let b = Matrix::new([1i64, 2, 3], 3, 1);
+        assert!(matches!(
+            multiply_distributed(&a, &b, &workers),
+            Err(Error::Matrix(MatrixError::DimensionMismatch { .. }))
+        ));
+        assert!(matches!(
+            multiply_distributed::<i64, SocketAddr>(&a, &a, &[]),
+            Err(Error::Matrix(MatrixError::NoWorkers))
+        ));
+    }
+}
@@ -0,0 +1,52 @@
+use std::io;
+use thiserror::Error;
+
+use crate::{MatrixError, MetricsError, ProtocolError, VectorError};
+
+/// Failures moving data between threads, processes or machines: the job queue log,
+/// the shared memory of `multiply_multiprocess` and the `multiply_distributed` wire.
+#[derive(Debug, Error)]
+pub enum ChannelError {
+    #[error("Channel error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Channel error: encoding failed: {0}")]
+    Encode(#[from] serde_json::Error),
+    #[error("Channel error: lock poisoned")]
+    Poisoned,
+    /// a record in the middle of a queue log failed to parse, at this byte offset
+    #[error("Channel error: log corrupted at byte {0}")]
+    Corrupted(u64),
+    #[error("Channel error: job {0} not found")]
+    JobNotFound(u64),
+    #[error("Channel error: frame too large: {0} bytes")]
+    FrameTooLarge(usize),
+    #[error("Channel error: peer closed the connection")]
+    Closed,
+    /// the remote worker answered with an error message
+    #[error("Channel error: remote worker: {0}")]
+    Remote(String),
+    /// the remote worker answered with a block of the wrong shape
+    #[error("Channel error: remote worker returned a wrong shape")]
+    WrongShape,
+}
+
+/// Any error returned by this crate, for callers that don't care which module failed.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    Matrix(#[from] MatrixError),
+    #[error(transparent)]
+    Vector(#[from] VectorError),
+    #[error(transparent)]
+    Metrics(#[from] MetricsError),
+    #[error(transparent)]
+    Channel(#[from] ChannelError),
+    #[error(transparent)]
+    Protocol(#[from] ProtocolError),
+    #[cfg(feature = "csv")]
+    #[error(transparent)]
+    Csv(#[from] crate::CsvError),
+    #[cfg(feature = "npy")]
+    #[error(transparent)]
+    Npy(#[from] crate::NpyError),
+}
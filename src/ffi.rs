@@ -0,0 +1,212 @@
+//! C bindings for the matrix kernel and the metrics counters.
+//!
+//! Every object handed out is an opaque pointer owned by the caller, release it
+//! with the matching `*_free` function. Functions returning a pointer return null
+//! on failure, functions returning `c_int` return 0 on success and -1 on failure.
+//!
+//! The header is `include/concurrency.h`, build a shared library with
+//! `cargo rustc --lib --release --features ffi --crate-type cdylib`.
+
+use std::{
+    collections::BTreeMap,
+    ffi::{c_char, c_int, CStr, CString},
+    ptr, slice,
+};
+
+use crate::{multiply, CmapMetrics, Matrix};
+
+/// Create a `row` x `col` matrix by copying `row * col` values from `data`.
+///
+/// # Safety
+///
+/// `data` must point to at least `row * col` readable `double`s.
+#[no_mangle]
+pub unsafe extern "C" fn matrix_new(data: *const f64, row: usize, col: usize) -> *mut Matrix<f64> {
+    let Some(len) = row.checked_mul(col) else {
+        return ptr::null_mut();
+    };
+    if data.is_null() && len != 0 {
+        return ptr::null_mut();
+    }
+    let data = if len == 0 {
+        Vec::new()
+    } else {
+        slice::from_raw_parts(data, len).to_vec()
+    };
+    Box::into_raw(Box::new(Matrix::new(data, row, col)))
+}
+
+/// Multiply `a` by `b` on the worker threads, null if the shapes don't match.
+///
+/// # Safety
+///
+/// `a` and `b` must be live matrices returned by this library.
+#[no_mangle]
+pub unsafe extern "C" fn matrix_multiply(
+    a: *const Matrix<f64>,
+    b: *const Matrix<f64>,
+) -> *mut Matrix<f64> {
+    let (Some(a), Some(b)) = (a.as_ref(), b.as_ref()) else {
+        return ptr::null_mut();
+    };
+    match multiply(a, b) {
+        Ok(c) => Box::into_raw(Box::new(c)),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// # Safety
+///
+/// `m` must be a live matrix returned by this library.
+#[no_mangle]
+pub unsafe extern "C" fn matrix_row(m: *const Matrix<f64>) -> usize {
+    m.as_ref().map(|m| m.row()).unwrap_or(0)
+}
+
+/// # Safety
+///
+/// `m` must be a live matrix returned by this library.
+#[no_mangle]
+pub unsafe extern "C" fn matrix_col(m: *const Matrix<f64>) -> usize {
+    m.as_ref().map(|m| m.col()).unwrap_or(0)
+}
+
+/// The `row * col` elements in row-major order, valid until the matrix is freed.
+///
+/// # Safety
+///
+/// `m` must be a live matrix returned by this library.
+#[no_mangle]
+pub unsafe extern "C" fn matrix_data(m: *const Matrix<f64>) -> *const f64 {
+    m.as_ref()
+        .map(|m| m.as_slice().as_ptr())
+        .unwrap_or(ptr::null())
+}
+
+/// # Safety
+///
+/// `m` must be null or a matrix returned by this library that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn matrix_free(m: *mut Matrix<f64>) {
+    if !m.is_null() {
+        drop(Box::from_raw(m));
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn metrics_new() -> *mut CmapMetrics {
+    Box::into_raw(Box::new(CmapMetrics::new()))
+}
+
+/// Increase the counter named by the NUL-terminated UTF-8 string `key`.
+///
+/// # Safety
+///
+/// `metrics` must be live metrics returned by this library and `key` a valid C string.
+#[no_mangle]
+pub unsafe extern "C" fn metrics_inc(metrics: *const CmapMetrics, key: *const c_char) -> c_int {
+    let Some(metrics) = metrics.as_ref() else {
+        return -1;
+    };
+    if key.is_null() {
+        return -1;
+    }
+    let Ok(key) = CStr::from_ptr(key).to_str() else {
+        return -1;
+    };
+    match metrics.inc(key) {
+        Ok(()) => 0,
+        Err(_) => -1,
+    }
+}
+
+/// All counters as a JSON object, e.g. `{"req.page.1":3}`. Release it with `string_free`.
+///
+/// # Safety
+///
+/// `metrics` must be live metrics returned by this library.
+#[no_mangle]
+pub unsafe extern "C" fn metrics_snapshot_json(metrics: *const CmapMetrics) -> *mut c_char {
+    let Some(metrics) = metrics.as_ref() else {
+        return ptr::null_mut();
+    };
+    let Ok(snapshot) = metrics.snapshot() else {
+        return ptr::null_mut();
+    };
+    let snapshot = snapshot.into_iter().collect::<BTreeMap<_, _>>();
+    let Ok(json) = serde_json::to_string(&snapshot) else {
+        return ptr::null_mut();
+    };
+    // json escapes control characters so there is no interior NUL
+    CString::new(json)
+        .map(CString::into_raw)
+        .unwrap_or(ptr::null_mut())
+}
+
+/// # Safety
+///
+/// `metrics` must be null or metrics returned by this library that were not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn metrics_free(metrics: *mut CmapMetrics) {
+    if !metrics.is_null() {
+        drop(Box::from_raw(metrics));
+    }
+}
+
+/// # Safety
+///
+/// `s` must be null or a string returned by this library that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ffi_matrix_multiply() {
+        let a = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
+        unsafe {
+            let a = matrix_new(a.as_ptr(), 2, 3);
+            let b = matrix_new([1.0, 2.0, 3.0, 4.0, 5.0, 6.0].as_ptr(), 3, 2);
+            let c = matrix_multiply(a, b);
+            assert!(!c.is_null());
+            assert_eq!((matrix_row(c), matrix_col(c)), (2, 2));
+            assert_eq!(
+                slice::from_raw_parts(matrix_data(c), 4),
+                [22.0, 28.0, 49.0, 64.0]
+            );
+
+            // 2x3 * 2x3 does not work
+            assert!(matrix_multiply(a, a).is_null());
+
+            matrix_free(a);
+            matrix_free(b);
+            matrix_free(c);
+        }
+    }
+
+    #[test]
+    fn test_ffi_metrics_snapshot_json() {
+        unsafe {
+            let metrics = metrics_new();
+            assert_eq!(metrics_inc(metrics, c"req.page.1".as_ptr()), 0);
+            assert_eq!(metrics_inc(metrics, c"req.page.1".as_ptr()), 0);
+            assert_eq!(metrics_inc(metrics, c"req.page.2".as_ptr()), 0);
+            assert_eq!(metrics_inc(metrics, ptr::null()), -1);
+
+            let json = metrics_snapshot_json(metrics);
+            assert_eq!(
+                CStr::from_ptr(json).to_str().unwrap(),
+                r#"{"req.page.1":2,"req.page.2":1}"#
+            );
+
+            string_free(json);
+            metrics_free(metrics);
+        }
+    }
+}
@@ -0,0 +1,230 @@
+use std::sync::{mpsc, OnceLock};
+
+use wgpu::util::DeviceExt;
+
+use crate::matrix::{row_major_operands, MatrixRef};
+use crate::{multiply, Matrix, MatrixError};
+
+const WORKGROUP_SIZE: u32 = 8;
+
+const SHADER: &str = r#"
+struct Dims {
+    m: u32,
+    k: u32,
+    n: u32,
+    _pad: u32,
+}
+
+@group(0) @binding(0) var<storage, read> a: array<f32>;
+@group(0) @binding(1) var<storage, read> b: array<f32>;
+@group(0) @binding(2) var<storage, read_write> c: array<f32>;
+@group(0) @binding(3) var<uniform> dims: Dims;
+
+@compute @workgroup_size(8, 8)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let row = id.x;
+    let col = id.y;
+    if (row >= dims.m || col >= dims.n) {
+        return;
+    }
+    var sum = 0.0;
+    for (var i = 0u; i < dims.k; i = i + 1u) {
+        sum = sum + a[row * dims.k + i] * b[i * dims.n + col];
+    }
+    c[row * dims.n + col] = sum;
+}
+"#;
+
+// device, queue and compiled pipeline, created on first use and shared by every call
+struct Gpu {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::ComputePipeline,
+}
+
+static GPU: OnceLock<Option<Gpu>> = OnceLock::new();
+
+/// Multiply `a` by `b` with a compute shader.
+///
+/// Falls back to `multiply` on the worker threads when there is no usable adapter,
+/// or when the product is too big for the device limits.
+pub fn multiply_gpu(a: &Matrix<f32>, b: &Matrix<f32>) -> Result<Matrix<f32>, MatrixError> {
+    let (a, b) = &row_major_operands(a, b)?;
+
+    let gpu = GPU.get_or_init(|| pollster::block_on(Gpu::new()));
+    match gpu.as_ref().and_then(|gpu| gpu.multiply(a, b)) {
+        Some(data) => Ok(Matrix::new(data, a.row(), b.col())),
+        None => multiply(a, b),
+    }
+}
+
+/// Whether `multiply_gpu` runs on an adapter rather than falling back to the CPU.
+pub fn gpu_available() -> bool {
+    GPU.get_or_init(|| pollster::block_on(Gpu::new())).is_some()
+}
+
+impl Gpu {
+    async fn new() -> Option<Self> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions::default())
+            .await?;
+        let (device, queue) = adapter
+            .request_device(&wgpu::DeviceDescriptor::default(), None)
+            .await
+            .ok()?;
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("multiply"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("multiply"),
+            layout: None,
+            module: &module,
+            entry_point: Some("main"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        Some(Self {
+            device,
+            queue,
+            pipeline,
+        })
+    }
+
+    // None means the caller should fall back to the CPU
+    fn multiply(&self, a: &MatrixRef<'_, f32>, b: &MatrixRef<'_, f32>) -> Option<Vec<f32>> {
+        let (m, k, n) = (a.row(), a.col(), b.col());
+        // wgpu does not allow empty bindings
+        if m == 0 || k == 0 || n == 0 {
+            return None;
+        }
+
+        let limits = self.device.limits();
+        let max_binding = limits.max_storage_buffer_binding_size as usize;
+        let max_groups = limits.max_compute_workgroups_per_dimension;
+        let groups_x = u32::try_from(m).ok()?.div_ceil(WORKGROUP_SIZE);
+        let groups_y = u32::try_from(n).ok()?.div_ceil(WORKGROUP_SIZE);
+        let size = |len: usize| len * size_of::<f32>();
+        if [m * k, k * n, m * n]
+            .into_iter()
+            .any(|len| size(len) > max_binding)
+            || groups_x > max_groups
+            || groups_y > max_groups
+        {
+            return None;
+        }
+
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+        let storage = |label, data: &[f32]| {
+            self.device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(label),
+                    contents: bytemuck::cast_slice(data),
+                    usage: wgpu::BufferUsages::STORAGE,
+                })
+        };
+        let a_buf = storage("a", a.as_slice());
+        let b_buf = storage("b", b.as_slice());
+        let dims = [m as u32, k as u32, n as u32, 0];
+        let dims_buf = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("dims"),
+                contents: bytemuck::cast_slice(&dims),
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+        let out_size = size(m * n) as u64;
+        let c_buf = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("c"),
+            size: out_size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let read_buf = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("read"),
+            size: out_size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("multiply"),
+            layout: &self.pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: a_buf.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: b_buf.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: c_buf.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: dims_buf.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(groups_x, groups_y, 1);
+        }
+        encoder.copy_buffer_to_buffer(&c_buf, 0, &read_buf, 0, out_size);
+        self.queue.submit(Some(encoder.finish()));
+
+        if pollster::block_on(self.device.pop_error_scope()).is_some() {
+            return None;
+        }
+
+        let slice = read_buf.slice(..);
+        let (tx, rx) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |rst| {
+            let _ = tx.send(rst);
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        rx.recv().ok()?.ok()?;
+
+        let data = bytemuck::cast_slice::<u8, f32>(&slice.get_mapped_range()).to_vec();
+        read_buf.unmap();
+        Some(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multiply_gpu_matches_cpu() {
+        let a = Matrix::new((0..12).map(|v| v as f32).collect::<Vec<_>>(), 3, 4);
+        let b = Matrix::new((0..20).map(|v| v as f32 * 0.5).collect::<Vec<_>>(), 4, 5);
+
+        let expected = multiply(&a, &b).unwrap();
+        let c = multiply_gpu(&a, &b).unwrap();
+        assert_eq!((c.row(), c.col()), (3, 5));
+        assert_eq!(c.as_slice(), expected.as_slice());
+    }
+
+    #[test]
+    fn test_multiply_gpu_checks_dimensions() {
+        let a = Matrix::new([1.0, 2.0, 3.0, 4.0, 5.0, 6.0], 2, 3);
+        assert!(matches!(
+            multiply_gpu(&a, &a),
+            Err(MatrixError::DimensionMismatch { .. })
+        ));
+    }
+}
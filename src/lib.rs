@@ -1,7 +1,84 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "distributed")]
+mod distributed;
+#[cfg(feature = "std")]
+mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "gpu")]
+mod gpu;
 mod matrix;
+#[cfg(feature = "std")]
 mod metrics;
+#[cfg(all(unix, feature = "multiprocess"))]
+mod multiprocess;
+#[cfg(feature = "threads")]
+mod pool;
+#[cfg(feature = "std")]
+mod queue;
+#[cfg(feature = "random")]
+mod random;
+#[cfg(feature = "std")]
+mod resp;
+mod sparse;
+#[cfg(feature = "std")]
+mod sync;
 mod vector;
 
-pub use matrix::{multiply, Matrix};
-pub use metrics::{AmapMetrics, CmapMetrics};
-pub use vector::{dot_product, Vector};
+#[cfg(feature = "distributed")]
+pub use distributed::{multiply_distributed, serve_worker};
+#[cfg(feature = "std")]
+pub use error::{ChannelError, Error};
+#[cfg(feature = "gpu")]
+pub use gpu::{gpu_available, multiply_gpu};
+#[cfg(feature = "tokio")]
+pub use matrix::multiply_async;
+#[cfg(feature = "num-traits")]
+pub use matrix::multiply_num;
+#[cfg(feature = "csv")]
+pub use matrix::CsvError;
+#[cfg(feature = "npy")]
+pub use matrix::NpyError;
+#[cfg(feature = "simd")]
+pub use matrix::{dot_product_f32, dot_product_f64, multiply_f32, multiply_f64};
+pub use matrix::{
+    multiply, multiply_checked, multiply_into, multiply_transposed, multiply_widening,
+    multiply_with_config, multiply_with_progress, CheckedInt, Granularity, Layout, Lu, Matrix,
+    MatrixError, MatrixView, MultiplyConfig, MultiplyContext, Padding, StaticMatrix, Storage,
+    TryMul,
+};
+#[cfg(feature = "threads")]
+pub use matrix::{
+    multiply_cancellable, multiply_iter, multiply_stream, multiply_with, multiply_with_priority,
+    multiply_with_timeout, MultiplyHandle, MultiplyStream,
+};
+#[cfg(feature = "mmap")]
+pub use matrix::{MmapMatrix, MmapStorage};
+#[cfg(feature = "metrics-dashmap")]
+pub use metrics::CmapMetrics;
+#[cfg(feature = "std")]
+pub use metrics::{stress, AmapMetrics, MetricsBackend, MetricsError, Transaction};
+#[cfg(all(unix, feature = "multiprocess"))]
+pub use multiprocess::multiply_multiprocess;
+#[cfg(feature = "threads")]
+pub use pool::{CancelToken, PoolConfig, PoolMetrics, Priority, ShutdownStats, ThreadPool};
+#[cfg(feature = "std")]
+pub use queue::{Job, PersistentQueue};
+#[cfg(feature = "random")]
+pub use rand_distr;
+#[cfg(feature = "random")]
+pub use random::Sparse;
+#[cfg(feature = "std")]
+pub use resp::{
+    parse_frame, split_frame, ParseConfig, ParseMode, ProtocolError, ProtocolErrorKind, RespFrame,
+};
+pub use sparse::{dot_product_sparse, SparseMatrix, SparseVector};
+#[cfg(feature = "num-traits")]
+pub use vector::dot_product_num;
+pub use vector::{
+    dot_product, dot_product_kahan, dot_product_slice, dot_product_widening, dot_products,
+    par_dot_product, Accumulate, Vector, VectorError, VectorView,
+};
@@ -1,163 +1,2028 @@
-use anyhow::Result;
-use std::fmt::{Debug, Display};
-use std::ops::{Add, AddAssign, Mul};
-use std::sync::mpsc;
-use std::thread;
+use alloc::{borrow::Cow, sync::Arc, vec, vec::Vec};
+use core::fmt::{self, Debug, Display};
+use core::iter::StepBy;
+use core::marker::PhantomData;
+use core::ops::{Add, AddAssign, Deref, DerefMut, Index, IndexMut, Mul};
+use core::slice;
+#[cfg(feature = "threads")]
+use std::panic::{catch_unwind, AssertUnwindSafe};
+#[cfg(feature = "threads")]
+use std::{
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+use thiserror::Error;
 
-use crate::{dot_product, Vector};
+#[cfg(feature = "tokio")]
+mod blocking;
+mod checked;
+#[cfg(feature = "csv")]
+mod csv;
+mod display;
+mod fixed;
+mod lu;
+#[cfg(feature = "mmap")]
+mod mmap;
+#[cfg(feature = "npy")]
+mod npy;
+#[cfg(feature = "num-traits")]
+mod num;
+mod ops;
+#[cfg(feature = "serde")]
+mod serialize;
+#[cfg(feature = "simd")]
+mod simd;
+mod stack;
+mod transpose;
+mod view;
 
-const THREAD_NUM: usize = 4;
+#[cfg(feature = "tokio")]
+pub use blocking::multiply_async;
+pub use checked::{multiply_checked, CheckedInt};
+#[cfg(feature = "csv")]
+pub use csv::CsvError;
+pub use fixed::StaticMatrix;
+pub use lu::Lu;
+#[cfg(feature = "mmap")]
+pub use mmap::{MmapMatrix, MmapStorage};
+#[cfg(feature = "npy")]
+pub use npy::NpyError;
+#[cfg(feature = "num-traits")]
+pub use num::multiply_num;
+pub(crate) use ops::{par_row_blocks, par_zip_update, try_par_row_blocks, PARALLEL_MIN};
+#[cfg(feature = "simd")]
+pub use simd::{dot_product_f32, dot_product_f64, multiply_f32, multiply_f64};
+pub use view::MatrixView;
 
-pub struct Matrix<T> {
-    data: Vec<T>, // for better performance, did not use nest Vec,
+use crate::{Accumulate, Vector, VectorError, VectorView};
+#[cfg(feature = "threads")]
+use crate::{CancelToken, Priority, ThreadPool};
+
+pub(crate) const THREAD_NUM: usize = 4;
+// a 32x32 by 32x32 product, below this spawning the workers costs more than it saves
+const SERIAL_THRESHOLD: usize = 32 * 32 * 32;
+// side of the square output tiles `convolve` hands out as pool jobs
+const CONV_TILE: usize = 32;
+
+#[derive(Clone)]
+pub struct Matrix<T, S = Vec<T>> {
+    data: S, // for better performance, did not use nest Vec,
     row: usize,
     col: usize,
+    layout: Layout,
+    _elements: PhantomData<T>,
 }
 
-pub struct MsgInput<T> {
-    idx: usize,
-    row: Vector<T>,
-    col: Vector<T>,
+/// Where a `Matrix` keeps its elements: anything that derefs to a slice, e.g.
+/// `Vec<T>` (the default), `Box<[T]>`, or `Arc<[T]>` to share one buffer between
+/// threads without cloning it.
+///
+/// Constructors and in-place operations are on `Matrix<T>`, the products and the
+/// read-only accessors take any storage.
+pub trait Storage<T>: Deref<Target = [T]> {}
+
+impl<T, S: Deref<Target = [T]> + ?Sized> Storage<T> for S {}
+
+// a row-major matrix borrowing its operand's buffer, or owning a reordered copy
+pub(crate) type MatrixRef<'a, T> = Matrix<T, Cow<'a, [T]>>;
+
+/// The order in which a `Matrix` keeps its elements in its buffer.
+///
+/// Indexing, `Display`, `rows`/`cols` and equality look through the layout. The
+/// kernels work on rows, so operations other than indexing copy a `ColMajor`
+/// operand into row-major order first, and always return `RowMajor` matrices.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Layout {
+    /// row after row, what every constructor but `from_col_major` builds
+    #[default]
+    RowMajor,
+    /// column after column, as Fortran, BLAS or numpy's `order='F'` store them
+    ColMajor,
 }
 
+#[cfg(all(feature = "threads", not(feature = "rayon")))]
 pub struct MsgOutput<T> {
     value: T,
     idx: usize,
 }
 
-pub struct Msg<T> {
-    input: MsgInput<T>,
-    // sender to send result back
-    sender: oneshot::Sender<MsgOutput<T>>,
+// the output cells in `rows` x `cols`, sent back row by row
+#[cfg(all(feature = "threads", not(feature = "rayon")))]
+struct Tile<T> {
+    rows: core::ops::Range<usize>,
+    cols: core::ops::Range<usize>,
+    sender: oneshot::Sender<Result<Vec<T>, MatrixError>>,
+}
+
+// rows of `a` to multiply by the whole of `b`
+#[cfg(all(feature = "threads", not(feature = "rayon")))]
+struct RowBlock<T> {
+    rows: core::ops::Range<usize>,
+    sender: oneshot::Sender<Result<Vec<T>, MatrixError>>,
+}
+
+#[cfg(all(feature = "threads", not(feature = "rayon")))]
+pub struct Msg<T> {
+    // row-major index of the output cell
+    idx: usize,
+    // sender to send result back
+    sender: oneshot::Sender<Result<MsgOutput<T>, MatrixError>>,
+}
+
+/// Why a matrix operation failed, one variant per cause so callers can match on it.
+///
+/// More variants may be added, match with a wildcard arm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[non_exhaustive]
+pub enum MatrixError {
+    /// `a.col != b.row`, shapes are `(row, col)`
+    #[error(
+        "Matrix multiply error: a.col != b.row ({}x{} * {}x{})",
+        left.0, left.1, right.0, right.1
+    )]
+    DimensionMismatch {
+        left: (usize, usize),
+        right: (usize, usize),
+    },
+    /// the config asked for zero worker threads
+    #[error("Matrix multiply error: threads must be greater than 0")]
+    NoWorkers,
+    /// a worker panicked computing output cell `(row, col)`, e.g. on integer
+    /// overflow in a debug build
+    #[error("Matrix multiply error: worker panicked at cell ({row}, {col})")]
+    WorkerPanicked { row: usize, col: usize },
+    /// a worker went away before sending its result back
+    #[error("Matrix multiply error: worker failed")]
+    WorkerFailed,
+    /// the caller cancelled the product before it finished
+    #[error("Matrix multiply error: cancelled")]
+    Cancelled,
+    /// `multiply_with_timeout` gave up with `missing_rows` rows of the product never
+    /// sent back, the first of them `first_row`
+    #[error(
+        "Matrix multiply error: timed out with {missing_rows} rows missing, from row {first_row}"
+    )]
+    TimedOut {
+        first_row: usize,
+        missing_rows: usize,
+    },
+    /// a row and a vector of different lengths, e.g. in `mul_vec`
+    #[error("Matrix multiply error: {0}")]
+    Vector(#[from] VectorError),
+    /// `convolve` was given a kernel without rows or columns
+    #[error("Matrix convolve error: kernel is empty")]
+    EmptyKernel,
+    /// an element-wise operation on matrices of different shapes `(row, col)`
+    #[error(
+        "Matrix error: shapes differ ({}x{} vs {}x{})",
+        left.0, left.1, right.0, right.1
+    )]
+    ShapeMismatch {
+        left: (usize, usize),
+        right: (usize, usize),
+    },
+    /// `multiply_checked` overflowed `T` computing output cell `(row, col)`
+    #[error("Matrix multiply error: overflow at cell ({row}, {col})")]
+    Overflow { row: usize, col: usize },
+    /// an operation only defined for square matrices got a `row x col` one
+    #[error("Matrix error: {row}x{col} is not square")]
+    NotSquare { row: usize, col: usize },
+    /// `try_from_rows` was given rows of different lengths, `row` counts from 0
+    #[error("Matrix error: row {row} has {len} columns, expected {expected}")]
+    RaggedRows {
+        row: usize,
+        len: usize,
+        expected: usize,
+    },
+    /// `reshape` to a `shape` holding a different number of elements than `len`
+    #[error("Matrix error: cannot reshape {len} elements into {}x{}", shape.0, shape.1)]
+    Reshape { len: usize, shape: (usize, usize) },
+}
+
+/// How `Matrix::convolve` treats the border.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Padding {
+    /// only positions where the whole kernel overlaps the matrix, the output
+    /// shrinks by `kernel.row - 1` rows and `kernel.col - 1` columns
+    #[default]
+    Valid,
+    /// zeros around the border, the output has the shape of the input
+    Same,
+}
+
+// shared by the tile jobs of one `convolve`
+struct Stencil<T> {
+    input: Matrix<T>,
+    kernel: Matrix<T>,
+    // where output (0, 0) sits in the full convolution
+    offset: (usize, usize),
+    out: (usize, usize),
+}
+
+/// Scratch buffers for repeated products, one set per worker, kept across calls.
+///
+/// Every worker multiplies a block of rows, gathering one column of `b` at a time
+/// into its column buffer and staging its rows of the product in its output
+/// buffer. Once the buffers have grown to the sizes in use, `multiply` only
+/// allocates the returned matrix. Products below the config's `serial_threshold`
+/// run the blocks one after another on the calling thread.
+pub struct MultiplyContext<T> {
+    threads: usize,
+    serial_threshold: usize,
+    scratch: Vec<Scratch<T>>,
+}
+
+struct Scratch<T> {
+    col: Vec<T>,
+    out: Vec<T>,
+}
+
+/// Knobs for `multiply_with_config`, `multiply` uses the default.
+#[derive(Debug, Clone)]
+pub struct MultiplyConfig {
+    /// number of worker threads spawned for the map phase, unused without the
+    /// `threads` feature where every product is computed on the calling thread, and
+    /// with the `rayon` feature where rayon's global pool runs it
+    pub threads: usize,
+    /// what one message to a worker thread computes
+    pub granularity: Granularity,
+    /// products of fewer multiply-adds (`a.row * a.col * b.col`) than this skip the
+    /// workers and run on the calling thread, where they finish before the threads
+    /// would have started. 0 sends every product to the workers
+    pub serial_threshold: usize,
+}
+
+/// How `multiply_with_config` splits the product into messages for the workers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Granularity {
+    /// one message and one oneshot reply per output cell
+    #[default]
+    Cell,
+    /// one message per block of this many output rows (at least 1), far less channel
+    /// traffic for large matrices
+    Rows(usize),
+    /// one message per square tile of the output with this side (at least 1), the
+    /// shared dimension walked in steps of the same size, so the blocks of `a` and
+    /// `b` in use stay in L1/L2 cache. 32 to 64 suits most element types
+    Tiles(usize),
+}
+
+impl Default for MultiplyConfig {
+    fn default() -> Self {
+        Self {
+            threads: THREAD_NUM,
+            granularity: Granularity::Cell,
+            serial_threshold: SERIAL_THRESHOLD,
+        }
+    }
+}
+
+pub fn multiply<T>(
+    a: &Matrix<T, impl Storage<T>>,
+    b: &Matrix<T, impl Storage<T>>,
+) -> Result<Matrix<T>, MatrixError>
+where
+    T: Display + Mul<Output = T> + Add<Output = T> + AddAssign + Default + Clone + Send + Sync,
+{
+    multiply_with_config(a, b, &MultiplyConfig::default())
+}
+
+pub fn multiply_with_config<T>(
+    a: &Matrix<T, impl Storage<T>>,
+    b: &Matrix<T, impl Storage<T>>,
+    config: &MultiplyConfig,
+) -> Result<Matrix<T>, MatrixError>
+where
+    T: Display + Mul<Output = T> + Add<Output = T> + AddAssign + Default + Clone + Send + Sync,
+{
+    multiply_reporting(a, b, config, Progress::new(None, a.row * b.col))
+}
+
+/// Like `multiply_with_config`, calling `progress(done, total)` on this thread as
+/// output cells arrive, about a hundred times per product, the last time with
+/// `done == total`. Never called for an empty product.
+///
+/// With the `rayon` feature it is only called at the end.
+pub fn multiply_with_progress<T>(
+    a: &Matrix<T, impl Storage<T>>,
+    b: &Matrix<T, impl Storage<T>>,
+    config: &MultiplyConfig,
+    mut progress: impl FnMut(usize, usize),
+) -> Result<Matrix<T>, MatrixError>
+where
+    T: Display + Mul<Output = T> + Add<Output = T> + AddAssign + Default + Clone + Send + Sync,
+{
+    multiply_reporting(
+        a,
+        b,
+        config,
+        Progress::new(Some(&mut progress), a.row * b.col),
+    )
+}
+
+fn multiply_reporting<T>(
+    a: &Matrix<T, impl Storage<T>>,
+    b: &Matrix<T, impl Storage<T>>,
+    config: &MultiplyConfig,
+    mut progress: Progress<'_>,
+) -> Result<Matrix<T>, MatrixError>
+where
+    T: Mul<Output = T> + Add<Output = T> + AddAssign + Default + Clone + Send + Sync,
+{
+    let progress = &mut progress;
+    let (a, b) = &row_major_operands(a, b)?;
+    if config.threads == 0 {
+        return Err(MatrixError::NoWorkers);
+    }
+    #[cfg(feature = "threads")]
+    if a.row.saturating_mul(a.col).saturating_mul(b.col) < config.serial_threshold {
+        return multiply_sequential(a, b, progress);
+    }
+
+    #[cfg(feature = "rayon")]
+    {
+        let c = multiply_rayon(a, b);
+        progress.advance(c.data.len());
+        Ok(c)
+    }
+    #[cfg(all(feature = "threads", not(feature = "rayon")))]
+    {
+        match config.granularity {
+            Granularity::Cell => multiply_threaded(a, b, config.threads, progress),
+            Granularity::Rows(rows) => {
+                multiply_row_blocks(a, b, config.threads, rows.max(1), progress)
+            }
+            Granularity::Tiles(side) => multiply_tiles(a, b, config.threads, side.max(1), progress),
+        }
+    }
+    #[cfg(not(feature = "threads"))]
+    {
+        multiply_sequential(a, b, progress)
+    }
+}
+
+// one rayon task per output row, work stealing balances them without any channels
+#[cfg(feature = "rayon")]
+fn multiply_rayon<T>(a: &MatrixRef<'_, T>, b: &MatrixRef<'_, T>) -> Matrix<T>
+where
+    T: Mul<Output = T> + Add<Output = T> + AddAssign + Default + Clone + Send + Sync,
+{
+    use rayon::prelude::*;
+
+    let (k, n) = (a.col, b.col);
+    let columns = columns(b);
+    let mut data = vec![T::default(); a.row * n];
+    if n > 0 {
+        data.par_chunks_mut(n).enumerate().for_each(|(i, out)| {
+            let row = &a.data[i * k..(i + 1) * k];
+            for (j, cell) in out.iter_mut().enumerate() {
+                *cell = dot(row, &columns[j * k..(j + 1) * k]);
+            }
+        });
+    }
+
+    Matrix::new(data, a.row, n)
+}
+
+/// `a * b` written over `out`, which must already be `a.row x b.col`, reusing its
+/// buffer so a loop of same-shaped products does not allocate at all. `out` is
+/// left row-major.
+///
+/// Rows of `out` are split into one block per thread of the default
+/// `MultiplyConfig`, or all computed on this thread below its `serial_threshold`.
+/// Each row is accumulated from the rows of `b`, so `b` is never transposed.
+pub fn multiply_into<T>(
+    a: &Matrix<T, impl Storage<T>>,
+    b: &Matrix<T, impl Storage<T>>,
+    out: &mut Matrix<T>,
+) -> Result<(), MatrixError>
+where
+    T: Mul<Output = T> + AddAssign + Default + Clone + Send + Sync,
+{
+    let (a, b) = &row_major_operands(a, b)?;
+    if (out.row, out.col) != (a.row, b.col) {
+        return Err(MatrixError::ShapeMismatch {
+            left: (a.row, b.col),
+            right: (out.row, out.col),
+        });
+    }
+    let (k, n) = (a.col, b.col);
+    out.layout = Layout::RowMajor;
+    if out.data.is_empty() {
+        return Ok(());
+    }
+
+    let work = a.row.saturating_mul(k).saturating_mul(n);
+    par_row_blocks(&mut out.data, n, work, SERIAL_THRESHOLD, |i, out| {
+        out.fill(T::default());
+        for (x, b_row) in a.data[i * k..(i + 1) * k].iter().zip(b.data.chunks(n)) {
+            for (cell, y) in out.iter_mut().zip(b_row) {
+                *cell += x.clone() * y.clone();
+            }
+        }
+    });
+    Ok(())
+}
+
+/// `a * bᵀ`, `a.row x b.row`, without building the transpose: cell `(i, j)` is the
+/// dot product of row `i` of `a` and row `j` of `b`, both contiguous. Fails with
+/// `DimensionMismatch` unless `a.col == b.col`.
+///
+/// Rows are split into one block per thread of the default `MultiplyConfig`, or
+/// all computed on this thread below its `serial_threshold`.
+pub fn multiply_transposed<T>(
+    a: &Matrix<T, impl Storage<T>>,
+    b: &Matrix<T, impl Storage<T>>,
+) -> Result<Matrix<T>, MatrixError>
+where
+    T: Mul<Output = T> + AddAssign + Default + Clone + Send + Sync,
+{
+    if a.col != b.col {
+        return Err(MatrixError::DimensionMismatch {
+            left: (a.row, a.col),
+            right: (b.col, b.row),
+        });
+    }
+    let (a, b) = (&a.as_row_major(), &b.as_row_major());
+    let (k, n) = (a.col, b.row);
+    let mut data = vec![T::default(); a.row * n];
+    if data.is_empty() {
+        return Ok(Matrix::new(data, a.row, n));
+    }
+
+    let work = a.row.saturating_mul(k).saturating_mul(n);
+    par_row_blocks(&mut data, n, work, SERIAL_THRESHOLD, |i, out| {
+        let row = &a.data[i * k..(i + 1) * k];
+        for (cell, other) in out.iter_mut().zip(b.data.chunks(k.max(1))) {
+            *cell = dot(row, other);
+        }
+    });
+    Ok(Matrix::new(data, a.row, n))
+}
+
+/// `a * b` with every element summed into the wider `Acc`, e.g. `i64` for `i8`,
+/// `i16` or `i32` inputs that would overflow in their own type.
+///
+/// Rows are split into one block per thread of the default `MultiplyConfig`, or
+/// all computed on this thread below its `serial_threshold`.
+pub fn multiply_widening<T, Acc>(
+    a: &Matrix<T, impl Storage<T>>,
+    b: &Matrix<T, impl Storage<T>>,
+) -> Result<Matrix<Acc>, MatrixError>
+where
+    T: Clone + Sync,
+    Acc: Accumulate<T> + Send,
+{
+    let (a, b) = &row_major_operands(a, b)?;
+    let (k, n) = (a.col, b.col);
+    let mut data = vec![Acc::default(); a.row * n];
+    if data.is_empty() {
+        return Ok(Matrix::new(data, a.row, n));
+    }
+
+    let columns = columns(b);
+    let work = a.row.saturating_mul(k).saturating_mul(n);
+    par_row_blocks(&mut data, n, work, SERIAL_THRESHOLD, |i, out| {
+        let row = &a.data[i * k..(i + 1) * k];
+        for (j, cell) in out.iter_mut().enumerate() {
+            let col = &columns[j * k..(j + 1) * k];
+            *cell = row.iter().zip(col).fold(Acc::default(), |acc, (x, y)| {
+                acc.mul_add(x.clone(), y.clone())
+            });
+        }
+    });
+    Ok(Matrix::new(data, a.row, n))
+}
+
+impl<T> Matrix<T>
+where
+    T: Mul<Output = T> + AddAssign + Default + Clone + Send + Sync,
+{
+    /// `self * v`, one dot product per row, with the rows split into one block per
+    /// thread once `self` is large enough. Fails unless `v` has one element per column.
+    pub fn mul_vec(&self, v: &Vector<T>) -> Result<Vector<T>, MatrixError> {
+        if self.col != v.len() {
+            return Err(VectorError::LengthMismatch {
+                left: self.col,
+                right: v.len(),
+            }
+            .into());
+        }
+        let a = &self.row_major();
+        let k = self.col;
+        let mut data = vec![T::default(); self.row];
+        if data.is_empty() {
+            return Ok(Vector::new(data));
+        }
+
+        par_row_blocks(&mut data, 1, a.data.len(), PARALLEL_MIN, |i, out| {
+            out[0] = dot(&a.data[i * k..(i + 1) * k], v);
+        });
+        Ok(Vector::new(data))
+    }
+}
+
+impl<T> Vector<T>
+where
+    T: Mul<Output = T> + Default + Clone + Send + Sync,
+{
+    /// The outer product, a `self.len()` x `other.len()` matrix with
+    /// `self[i] * other[j]` at `(i, j)`, its rows split into one block per thread
+    /// once it is large enough.
+    pub fn outer(&self, other: &Vector<T>) -> Matrix<T> {
+        let (row, col) = (self.len(), other.len());
+        let mut data = vec![T::default(); row * col];
+        if data.is_empty() {
+            return Matrix::new(data, row, col);
+        }
+
+        par_row_blocks(&mut data, col, row * col, PARALLEL_MIN, |i, out| {
+            let x = &self[i];
+            for (cell, y) in out.iter_mut().zip(other.iter()) {
+                *cell = x.clone() * y.clone();
+            }
+        });
+        Matrix::new(data, row, col)
+    }
+}
+
+impl<T> Matrix<T>
+where
+    T: Display + Mul<Output = T> + Add<Output = T> + AddAssign + Default + Clone + Send + Sync,
+{
+    /// `self` multiplied by itself `n` times, the identity for `n == 0`, by repeated
+    /// squaring: about `2 * log2(n)` calls to `multiply` instead of `n - 1`.
+    /// Fails with `NotSquare` unless `self` is square.
+    pub fn pow(&self, n: u32) -> Result<Matrix<T>, MatrixError>
+    where
+        T: From<u8>,
+    {
+        self.check_square()?;
+        let mut result: Option<Matrix<T>> = None;
+        let mut base = self.clone();
+        let mut n = n;
+        while n > 0 {
+            if n & 1 == 1 {
+                result = Some(match result {
+                    Some(r) => multiply(&r, &base)?,
+                    None => base.clone(),
+                });
+            }
+            n >>= 1;
+            if n > 0 {
+                base = multiply(&base, &base)?;
+            }
+        }
+        Ok(result.unwrap_or_else(|| Matrix::identity(self.row)))
+    }
+}
+
+// `b` column by column, so column `j` is the contiguous `[j * b.row..(j + 1) * b.row]`.
+// Transposing once up front spares every task a strided walk through `b`
+fn columns<T: Clone, S: Storage<T>>(b: &Matrix<T, S>) -> Vec<T> {
+    let mut data = b.data.to_vec();
+    transpose::transpose_block(&b.data, b.row, b.col, &mut data, 0);
+    data
+}
+
+// a `multiply_with_progress` callback, throttled to about a hundred calls
+struct Progress<'a> {
+    report: Option<&'a mut dyn FnMut(usize, usize)>,
+    done: usize,
+    total: usize,
+    // `done` at which to report next
+    next: usize,
+}
+
+impl<'a> Progress<'a> {
+    fn new(report: Option<&'a mut dyn FnMut(usize, usize)>, total: usize) -> Self {
+        Self {
+            report,
+            done: 0,
+            total,
+            next: Self::step(total),
+        }
+    }
+
+    fn step(total: usize) -> usize {
+        (total / 100).max(1)
+    }
+
+    // `cells` more output cells have arrived
+    fn advance(&mut self, cells: usize) {
+        self.done += cells;
+        // an empty product has nothing to report, `done == total` would hold at 0
+        if self.total == 0 {
+            return;
+        }
+        if let Some(report) = self.report.as_mut() {
+            if self.done >= self.next || self.done == self.total {
+                report(self.done, self.total);
+                self.next = self.done + Self::step(self.total);
+            }
+        }
+    }
+}
+
+// runs one task of a worker, which keeps `at` on the output cell it is computing, so
+// a panic is sent back as `WorkerPanicked` at that cell instead of taking down the
+// worker or resurfacing when a thread scope ends
+#[cfg(feature = "threads")]
+fn guard<R>(task: impl FnOnce(&mut (usize, usize)) -> R) -> Result<R, MatrixError> {
+    let mut at = (0, 0);
+    catch_unwind(AssertUnwindSafe(|| task(&mut at))).map_err(|_| MatrixError::WorkerPanicked {
+        row: at.0,
+        col: at.1,
+    })
+}
+
+// joins the workers of one product after their channels closed, so none outlives
+// the call. A worker that died outside `guard` fails the product with `WorkerFailed`
+// instead of panicking when the thread scope ends
+#[cfg(all(feature = "threads", not(feature = "rayon")))]
+fn join_workers(workers: Vec<thread::ScopedJoinHandle<'_, ()>>) -> Result<(), MatrixError> {
+    let mut joined = Ok(());
+    for worker in workers {
+        if worker.join().is_err() {
+            joined = Err(MatrixError::WorkerFailed);
+        }
+    }
+    joined
+}
+
+// the sum of the pairwise products of two equally long slices
+fn dot<T>(row: &[T], col: &[T]) -> T
+where
+    T: Mul<Output = T> + AddAssign + Default + Clone,
+{
+    let mut sum = T::default();
+    for (x, y) in row.iter().zip(col) {
+        sum += x.clone() * y.clone();
+    }
+    sum
+}
+
+// `check_multiply`, then both operands in the row-major order the kernels read
+pub(crate) fn row_major_operands<'a, T, SA, SB>(
+    a: &'a Matrix<T, SA>,
+    b: &'a Matrix<T, SB>,
+) -> Result<(MatrixRef<'a, T>, MatrixRef<'a, T>), MatrixError>
+where
+    T: Clone,
+    SA: Storage<T>,
+    SB: Storage<T>,
+{
+    check_multiply(a, b)?;
+    Ok((a.as_row_major(), b.as_row_major()))
+}
+
+pub(crate) fn check_multiply<T, SA, SB>(
+    a: &Matrix<T, SA>,
+    b: &Matrix<T, SB>,
+) -> Result<(), MatrixError> {
+    if a.col != b.row {
+        return Err(MatrixError::DimensionMismatch {
+            left: (a.row, a.col),
+            right: (b.row, b.col),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(all(feature = "threads", not(feature = "rayon")))]
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip_all, fields(m = a.row, k = a.col, n = b.col, threads = thread_num))
+)]
+fn multiply_threaded<T>(
+    a: &MatrixRef<'_, T>,
+    b: &MatrixRef<'_, T>,
+    thread_num: usize,
+    progress: &mut Progress<'_>,
+) -> Result<Matrix<T>, MatrixError>
+where
+    T: Mul<Output = T> + Add<Output = T> + AddAssign + Default + Clone + Send + Sync,
+{
+    #[cfg(feature = "tracing")]
+    let parent = tracing::Span::current();
+    let (k, n) = (a.col, b.col);
+    let columns = &columns(b);
+
+    // scoped workers borrow `a` and `b`, a message only says which cell to compute
+    thread::scope(|s| {
+        let (senders, workers): (Vec<_>, Vec<_>) = (0..thread_num)
+            .map(|_worker| {
+                let (tx, rx) = mpsc::channel::<Msg<T>>();
+                #[cfg(feature = "tracing")]
+                let span = tracing::debug_span!(parent: &parent, "worker", id = _worker);
+                let worker = s.spawn(move || {
+                    #[cfg(feature = "tracing")]
+                    let _enter = span.enter();
+                    for msg in rx {
+                        #[cfg(feature = "tracing")]
+                        let _task = tracing::trace_span!("task", idx = msg.idx).entered();
+                        let (i, j) = (msg.idx / n, msg.idx % n);
+                        let rst = guard(|at| {
+                            *at = (i, j);
+                            let row = &a.data[i * k..(i + 1) * k];
+                            MsgOutput {
+                                value: dot(row, &columns[j * k..(j + 1) * k]),
+                                idx: msg.idx,
+                            }
+                        });
+                        // the reduce phase stopped at a failed task and dropped the
+                        // rest of the receivers, nothing left here is wanted
+                        if msg.sender.send(rst).is_err() {
+                            break;
+                        }
+                    }
+                });
+                (tx, worker)
+            })
+            .unzip();
+
+        let matrix_len = a.row * n;
+
+        let mut data = vec![T::default(); matrix_len];
+        let mut receivers = Vec::with_capacity(matrix_len);
+
+        // map/reduce: map phase
+        #[cfg(feature = "tracing")]
+        let map = tracing::debug_span!("map", tasks = matrix_len).entered();
+        for idx in 0..matrix_len {
+            let (tx, rx) = oneshot::channel();
+            let msg = Msg::new(idx, tx);
+            #[cfg(feature = "tracing")]
+            tracing::trace!(idx, worker = idx % thread_num, "task queued");
+            // a worker that died dropped its receiver, the reduce phase then fails
+            // the task with `WorkerFailed`
+            if let Err(_e) = senders[idx % thread_num].send(msg) {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(idx, error = %_e, "worker channel closed");
+            }
+            receivers.push(rx);
+        }
+        // the workers exit once the queued cells are done and are joined below
+        drop(senders);
+        #[cfg(feature = "tracing")]
+        drop(map);
+
+        // map/reduce: reduce phase
+        #[cfg(feature = "tracing")]
+        let _reduce = tracing::debug_span!("reduce", tasks = matrix_len).entered();
+        let reduced = receivers.into_iter().try_for_each(|rx| {
+            let rst = rx.recv().map_err(|_| MatrixError::WorkerFailed)??;
+            data[rst.idx] = rst.value;
+            progress.advance(1);
+            Ok(())
+        });
+        let joined = join_workers(workers);
+        reduced.and(joined)?;
+
+        Ok(Matrix::new(data, a.row, n))
+    })
+}
+
+#[cfg(all(feature = "threads", not(feature = "rayon")))]
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip_all, fields(m = a.row, k = a.col, n = b.col, threads = thread_num, block_rows = block_rows))
+)]
+fn multiply_row_blocks<T>(
+    a: &MatrixRef<'_, T>,
+    b: &MatrixRef<'_, T>,
+    thread_num: usize,
+    block_rows: usize,
+    progress: &mut Progress<'_>,
+) -> Result<Matrix<T>, MatrixError>
+where
+    T: Mul<Output = T> + Add<Output = T> + AddAssign + Default + Clone + Send + Sync,
+{
+    #[cfg(feature = "tracing")]
+    let parent = tracing::Span::current();
+    let (k, n) = (a.col, b.col);
+    let columns = &columns(b);
+    thread::scope(|s| {
+        let (senders, workers): (Vec<_>, Vec<_>) = (0..thread_num)
+            .map(|_worker| {
+                let (tx, rx) = mpsc::channel::<RowBlock<T>>();
+                #[cfg(feature = "tracing")]
+                let span = tracing::debug_span!(parent: &parent, "worker", id = _worker);
+                let worker = s.spawn(move || {
+                    #[cfg(feature = "tracing")]
+                    let _enter = span.enter();
+                    for block in rx {
+                        #[cfg(feature = "tracing")]
+                        let _task = tracing::trace_span!(
+                            "task",
+                            start = block.rows.start,
+                            rows = block.rows.len()
+                        )
+                        .entered();
+                        let rst = guard(|at| {
+                            let mut out = Vec::with_capacity(block.rows.len() * n);
+                            for i in block.rows {
+                                let row = &a.data[i * k..(i + 1) * k];
+                                for j in 0..n {
+                                    *at = (i, j);
+                                    out.push(dot(row, &columns[j * k..(j + 1) * k]));
+                                }
+                            }
+                            out
+                        });
+                        // the reduce phase stopped at a failed task and dropped the
+                        // rest of the receivers, nothing left here is wanted
+                        if block.sender.send(rst).is_err() {
+                            break;
+                        }
+                    }
+                });
+                (tx, worker)
+            })
+            .unzip();
+
+        // map/reduce: map phase
+        #[cfg(feature = "tracing")]
+        let map = tracing::debug_span!("map").entered();
+        let receivers = (0..a.row)
+            .step_by(block_rows)
+            .enumerate()
+            .map(|(task, start)| {
+                let (tx, rx) = oneshot::channel();
+                let block = RowBlock {
+                    rows: start..(start + block_rows).min(a.row),
+                    sender: tx,
+                };
+                // a worker that died dropped its receiver, the reduce phase then fails
+                // the task with `WorkerFailed`
+                if let Err(_e) = senders[task % thread_num].send(block) {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(start, error = %_e, "worker channel closed");
+                }
+                rx
+            })
+            .collect::<Vec<_>>();
+        // the workers exit once the queued blocks are done and are joined below
+        drop(senders);
+        #[cfg(feature = "tracing")]
+        drop(map);
+
+        // map/reduce: reduce phase
+        #[cfg(feature = "tracing")]
+        let _reduce = tracing::debug_span!("reduce", tasks = receivers.len()).entered();
+        let mut data = Vec::with_capacity(a.row * n);
+        let reduced = receivers.into_iter().try_for_each(|rx| {
+            let block = rx.recv().map_err(|_| MatrixError::WorkerFailed)??;
+            progress.advance(block.len());
+            data.extend(block);
+            Ok(())
+        });
+        let joined = join_workers(workers);
+        reduced.and(joined)?;
+
+        Ok(Matrix::new(data, a.row, n))
+    })
+}
+
+#[cfg(all(feature = "threads", not(feature = "rayon")))]
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip_all, fields(m = a.row, k = a.col, n = b.col, threads = thread_num, side = side))
+)]
+fn multiply_tiles<T>(
+    a: &MatrixRef<'_, T>,
+    b: &MatrixRef<'_, T>,
+    thread_num: usize,
+    side: usize,
+    progress: &mut Progress<'_>,
+) -> Result<Matrix<T>, MatrixError>
+where
+    T: Mul<Output = T> + Add<Output = T> + AddAssign + Default + Clone + Send + Sync,
+{
+    #[cfg(feature = "tracing")]
+    let parent = tracing::Span::current();
+    let (k, n) = (a.col, b.col);
+    let columns = &columns(b);
+    thread::scope(|s| {
+        let (senders, workers): (Vec<_>, Vec<_>) = (0..thread_num)
+            .map(|_worker| {
+                let (tx, rx) = mpsc::channel::<Tile<T>>();
+                #[cfg(feature = "tracing")]
+                let span = tracing::debug_span!(parent: &parent, "worker", id = _worker);
+                let worker = s.spawn(move || {
+                    #[cfg(feature = "tracing")]
+                    let _enter = span.enter();
+                    for tile in rx {
+                        #[cfg(feature = "tracing")]
+                        let _task = tracing::trace_span!(
+                            "task",
+                            row = tile.rows.start,
+                            col = tile.cols.start,
+                            rows = tile.rows.len(),
+                            cols = tile.cols.len()
+                        )
+                        .entered();
+                        let rst = guard(|at| {
+                            let width = tile.cols.len();
+                            let mut out = vec![T::default(); tile.rows.len() * width];
+                            // every step reuses the same `side` rows of `a` and
+                            // columns of `b`, `side` elements long
+                            for start in (0..k).step_by(side) {
+                                let end = (start + side).min(k);
+                                for (r, i) in tile.rows.clone().enumerate() {
+                                    let row = &a.data[i * k + start..i * k + end];
+                                    for (c, j) in tile.cols.clone().enumerate() {
+                                        *at = (i, j);
+                                        let col = &columns[j * k + start..j * k + end];
+                                        out[r * width + c] += dot(row, col);
+                                    }
+                                }
+                            }
+                            out
+                        });
+                        // the reduce phase stopped at a failed task and dropped the
+                        // rest of the receivers, nothing left here is wanted
+                        if tile.sender.send(rst).is_err() {
+                            break;
+                        }
+                    }
+                });
+                (tx, worker)
+            })
+            .unzip();
+
+        // map/reduce: map phase
+        #[cfg(feature = "tracing")]
+        let map = tracing::debug_span!("map").entered();
+        let receivers = (0..a.row)
+            .step_by(side)
+            .flat_map(|i| (0..n).step_by(side).map(move |j| (i, j)))
+            .enumerate()
+            .map(|(task, (i, j))| {
+                let (tx, rx) = oneshot::channel();
+                let tile = Tile {
+                    rows: i..(i + side).min(a.row),
+                    cols: j..(j + side).min(n),
+                    sender: tx,
+                };
+                // a worker that died dropped its receiver, the reduce phase then fails
+                // the task with `WorkerFailed`
+                if let Err(_e) = senders[task % thread_num].send(tile) {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(i, j, error = %_e, "worker channel closed");
+                }
+                (i, j, rx)
+            })
+            .collect::<Vec<_>>();
+        // the workers exit once the queued tiles are done and are joined below
+        drop(senders);
+        #[cfg(feature = "tracing")]
+        drop(map);
+
+        // map/reduce: reduce phase
+        #[cfg(feature = "tracing")]
+        let _reduce = tracing::debug_span!("reduce", tasks = receivers.len()).entered();
+        let mut data = vec![T::default(); a.row * n];
+        let reduced = receivers.into_iter().try_for_each(|(i, j, rx)| {
+            let tile = rx.recv().map_err(|_| MatrixError::WorkerFailed)??;
+            progress.advance(tile.len());
+            let width = (j + side).min(n) - j;
+            for (r, row) in tile.chunks(width).enumerate() {
+                data[(i + r) * n + j..][..width].clone_from_slice(row);
+            }
+            Ok(())
+        });
+        let joined = join_workers(workers);
+        reduced.and(joined)?;
+
+        Ok(Matrix::new(data, a.row, n))
+    })
+}
+
+/// Multiply on the shared `ThreadPool::global`, one job per output row.
+///
+/// Jobs of a higher `priority` jump ahead of the queued rows of lower priority
+/// products, so a small interactive multiply is not starved by a huge batch.
+#[cfg(feature = "threads")]
+pub fn multiply_with_priority<T>(
+    a: &Matrix<T, impl Storage<T>>,
+    b: &Matrix<T, impl Storage<T>>,
+    priority: Priority,
+) -> Result<Matrix<T>, MatrixError>
+where
+    T: Mul<Output = T> + Add<Output = T> + AddAssign + Default + Clone + Send + Sync + 'static,
+{
+    multiply_on(ThreadPool::global(), a, b, priority, None)
+}
+
+/// Multiply on a pool the caller keeps around, one job per output row, so a loop of
+/// products reuses the same workers instead of spawning threads every call.
+#[cfg(feature = "threads")]
+pub fn multiply_with<T>(
+    pool: &ThreadPool,
+    a: &Matrix<T, impl Storage<T>>,
+    b: &Matrix<T, impl Storage<T>>,
+) -> Result<Matrix<T>, MatrixError>
+where
+    T: Mul<Output = T> + Add<Output = T> + AddAssign + Default + Clone + Send + Sync + 'static,
+{
+    multiply_on(pool, a, b, Priority::Normal, None)
+}
+
+/// Multiply on the shared `ThreadPool::global`, one job per output row, giving up
+/// with `TimedOut` once `timeout` has passed without every row arriving.
+///
+/// Rows still queued or running when it gives up finish in the background and are
+/// thrown away.
+#[cfg(feature = "threads")]
+pub fn multiply_with_timeout<T>(
+    a: &Matrix<T, impl Storage<T>>,
+    b: &Matrix<T, impl Storage<T>>,
+    timeout: Duration,
+) -> Result<Matrix<T>, MatrixError>
+where
+    T: Mul<Output = T> + Add<Output = T> + AddAssign + Default + Clone + Send + Sync + 'static,
+{
+    let deadline = Instant::now() + timeout;
+    multiply_on(ThreadPool::global(), a, b, Priority::Normal, Some(deadline))
+}
+
+/// A product running on `ThreadPool::global`, from `multiply_cancellable`.
+#[cfg(feature = "threads")]
+pub struct MultiplyHandle<T> {
+    rx: mpsc::Receiver<RowResult<T>>,
+    row: usize,
+    col: usize,
+    cancel: CancelToken,
+}
+
+// one output row, or why it is missing
+#[cfg(feature = "threads")]
+type RowResult<T> = (usize, Result<Vec<T>, MatrixError>);
+
+/// Starts `a * b` on the shared `ThreadPool::global`, one job per output row, and
+/// returns at once with a handle to wait on and a token to abort it.
+///
+/// Rows check the token before they start, so after `cancel` the rows in progress
+/// finish and the rest are skipped, and `join` returns `Cancelled`.
+#[cfg(feature = "threads")]
+pub fn multiply_cancellable<T>(
+    a: &Matrix<T, impl Storage<T>>,
+    b: &Matrix<T, impl Storage<T>>,
+) -> Result<(MultiplyHandle<T>, CancelToken), MatrixError>
+where
+    T: Mul<Output = T> + Add<Output = T> + AddAssign + Default + Clone + Send + Sync + 'static,
+{
+    let (a, b) = &row_major_operands(a, b)?;
+    let cancel = CancelToken::default();
+    let rx = queue_rows(ThreadPool::global(), a, b, Priority::Normal, Some(&cancel));
+    let handle = MultiplyHandle {
+        rx,
+        row: a.row,
+        col: b.col,
+        cancel: cancel.clone(),
+    };
+    Ok((handle, cancel))
+}
+
+#[cfg(feature = "threads")]
+impl<T: Default + Clone> MultiplyHandle<T> {
+    /// Waits for every row, or for the first row that failed or was skipped.
+    pub fn join(self) -> Result<Matrix<T>, MatrixError> {
+        if self.cancel.is_cancelled() {
+            return Err(MatrixError::Cancelled);
+        }
+        collect_rows(self.rx, self.row, self.col, None)
+    }
+}
+
+#[cfg(feature = "threads")]
+fn multiply_on<T>(
+    pool: &ThreadPool,
+    a: &Matrix<T, impl Storage<T>>,
+    b: &Matrix<T, impl Storage<T>>,
+    priority: Priority,
+    deadline: Option<Instant>,
+) -> Result<Matrix<T>, MatrixError>
+where
+    T: Mul<Output = T> + Add<Output = T> + AddAssign + Default + Clone + Send + Sync + 'static,
+{
+    let (a, b) = &row_major_operands(a, b)?;
+    let rx = queue_rows(pool, a, b, priority, None);
+    collect_rows(rx, a.row, b.col, deadline)
+}
+
+// map/reduce: map phase, one job per row of the output. A row skipped because
+// `cancel` was set sends `Cancelled`, so the reduce phase wakes up
+#[cfg(feature = "threads")]
+fn queue_rows<T>(
+    pool: &ThreadPool,
+    a: &MatrixRef<'_, T>,
+    b: &MatrixRef<'_, T>,
+    priority: Priority,
+    cancel: Option<&CancelToken>,
+) -> mpsc::Receiver<RowResult<T>>
+where
+    T: Mul<Output = T> + AddAssign + Default + Clone + Send + Sync + 'static,
+{
+    let (k, n) = (a.col, b.col);
+    // one shared copy of each operand, a job only bumps the refcounts and slices
+    // out its row instead of allocating a copy of it
+    let rows: Arc<[T]> = Arc::from(&*a.data);
+    let columns: Arc<[T]> = columns(b).into();
+    let (tx, rx) = mpsc::channel();
+    for i in 0..a.row {
+        let rows = Arc::clone(&rows);
+        let columns = Arc::clone(&columns);
+        let cancel = cancel.cloned();
+        let tx = tx.clone();
+        pool.execute_with_priority(priority, move || {
+            if cancel.is_some_and(|c| c.is_cancelled()) {
+                let _ = tx.send((i, Err(MatrixError::Cancelled)));
+                return;
+            }
+            let out = guard(|at| {
+                (0..n)
+                    .map(|j| {
+                        *at = (i, j);
+                        dot(&rows[i * k..(i + 1) * k], &columns[j * k..(j + 1) * k])
+                    })
+                    .collect::<Vec<_>>()
+            });
+            let _ = tx.send((i, out));
+        });
+    }
+    rx
+}
+
+// map/reduce: reduce phase, the first failed row fails the product
+#[cfg(feature = "threads")]
+fn collect_rows<T: Default + Clone>(
+    rx: mpsc::Receiver<RowResult<T>>,
+    row: usize,
+    n: usize,
+    deadline: Option<Instant>,
+) -> Result<Matrix<T>, MatrixError> {
+    let mut data = vec![T::default(); row * n];
+    let mut arrived = vec![false; row];
+    for received in 0..row {
+        let (i, out) = match deadline {
+            None => rx.recv().map_err(|_| MatrixError::WorkerFailed)?,
+            Some(deadline) => {
+                match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                    Ok(msg) => msg,
+                    Err(mpsc::RecvTimeoutError::Timeout) => {
+                        return Err(MatrixError::TimedOut {
+                            first_row: arrived.iter().position(|&done| !done).unwrap_or(0),
+                            missing_rows: row - received,
+                        });
+                    }
+                    Err(mpsc::RecvTimeoutError::Disconnected) => {
+                        return Err(MatrixError::WorkerFailed)
+                    }
+                }
+            }
+        };
+        arrived[i] = true;
+        data[i * n..(i + 1) * n].clone_from_slice(&out?);
+    }
+
+    Ok(Matrix::new(data, row, n))
+}
+
+/// The cells of `a * b` as `(row, col, value)` in the order they complete, from
+/// `multiply_stream`.
+///
+/// Ends early if a job panicked, `remaining` then stays above zero and `error` is
+/// `WorkerPanicked` at the cell that panicked.
+#[cfg(feature = "threads")]
+pub struct MultiplyStream<T> {
+    rx: mpsc::Receiver<Result<(usize, usize, T), MatrixError>>,
+    remaining: usize,
+    error: Option<MatrixError>,
+}
+
+/// Multiply on the shared `ThreadPool::global`, one job per output row, handing out
+/// every cell as soon as it is computed instead of assembling the matrix.
+#[cfg(feature = "threads")]
+pub fn multiply_stream<T>(
+    a: &Matrix<T, impl Storage<T>>,
+    b: &Matrix<T, impl Storage<T>>,
+) -> Result<MultiplyStream<T>, MatrixError>
+where
+    T: Mul<Output = T> + Add<Output = T> + AddAssign + Default + Clone + Send + Sync + 'static,
+{
+    let (a, b) = &row_major_operands(a, b)?;
+    let pool = ThreadPool::global();
+    let (k, n) = (a.col, b.col);
+    // shared like in `queue_rows`, no per-row copies
+    let rows: Arc<[T]> = Arc::from(&*a.data);
+    let columns: Arc<[T]> = columns(b).into();
+
+    let (tx, rx) = mpsc::channel();
+    for i in 0..a.row {
+        let rows = Arc::clone(&rows);
+        let columns = Arc::clone(&columns);
+        let tx = tx.clone();
+        pool.execute(move || {
+            let rst = guard(|at| {
+                for j in 0..n {
+                    *at = (i, j);
+                    let sum = dot(&rows[i * k..(i + 1) * k], &columns[j * k..(j + 1) * k]);
+                    // the consumer stopped listening, no point in computing the rest
+                    if tx.send(Ok((i, j, sum))).is_err() {
+                        return;
+                    }
+                }
+            });
+            if let Err(e) = rst {
+                let _ = tx.send(Err(e));
+            }
+        });
+    }
+
+    Ok(MultiplyStream {
+        rx,
+        remaining: a.row * n,
+        error: None,
+    })
+}
+
+/// Like `multiply_stream`, handing out every cell as `(idx, value)` with `idx` the
+/// row-major index of the cell in the product.
+#[cfg(feature = "threads")]
+pub fn multiply_iter<T>(
+    a: &Matrix<T, impl Storage<T>>,
+    b: &Matrix<T, impl Storage<T>>,
+) -> Result<impl Iterator<Item = (usize, T)>, MatrixError>
+where
+    T: Mul<Output = T> + Add<Output = T> + AddAssign + Default + Clone + Send + Sync + 'static,
+{
+    let n = b.col;
+    Ok(multiply_stream(a, b)?.map(move |(i, j, value)| (i * n + j, value)))
+}
+
+#[cfg(feature = "threads")]
+impl<T> MultiplyStream<T> {
+    /// Cells not handed out yet.
+    pub fn remaining(&self) -> usize {
+        self.remaining
+    }
+
+    /// Why the stream ended early, `None` while it has not.
+    pub fn error(&self) -> Option<&MatrixError> {
+        self.error.as_ref()
+    }
+}
+
+#[cfg(feature = "threads")]
+impl<T> Iterator for MultiplyStream<T> {
+    type Item = (usize, usize, T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 || self.error.is_some() {
+            return None;
+        }
+        match self.rx.recv().ok()? {
+            Ok(cell) => {
+                self.remaining -= 1;
+                Some(cell)
+            }
+            Err(e) => {
+                self.error = Some(e);
+                None
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining))
+    }
+}
+
+impl<T> MultiplyContext<T>
+where
+    T: Mul<Output = T> + Add<Output = T> + AddAssign + Default + Clone + Send + Sync,
+{
+    /// Workers come from `config.threads`, without the `threads` feature there is
+    /// only the calling thread and a single set of buffers.
+    pub fn new(config: &MultiplyConfig) -> Self {
+        let threads = if cfg!(feature = "threads") {
+            config.threads
+        } else {
+            config.threads.min(1)
+        };
+        Self {
+            threads,
+            serial_threshold: config.serial_threshold,
+            scratch: (0..threads)
+                .map(|_| Scratch {
+                    col: Vec::new(),
+                    out: Vec::new(),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn multiply(
+        &mut self,
+        a: &Matrix<T, impl Storage<T>>,
+        b: &Matrix<T, impl Storage<T>>,
+    ) -> Result<Matrix<T>, MatrixError> {
+        let (a, b) = &row_major_operands(a, b)?;
+        if self.threads == 0 {
+            return Err(MatrixError::NoWorkers);
+        }
+
+        let block_rows = a.row.div_ceil(self.threads).max(1);
+        let blocks = a.row.div_ceil(block_rows);
+        let scratch = &mut self.scratch[..blocks];
+
+        // small products, like `multiply` below `serial_threshold`, are all computed
+        // on this thread
+        let work = a.row.saturating_mul(a.col).saturating_mul(b.col);
+        if !cfg!(feature = "threads") || work < self.serial_threshold {
+            for (w, scratch) in scratch.iter_mut().enumerate() {
+                scratch.multiply_rows(a, b, w * block_rows, block_rows, &mut (0, 0));
+            }
+        } else {
+            // map/reduce: map phase, every worker fills its own output buffer
+            #[cfg(feature = "threads")]
+            thread::scope(|s| {
+                let workers = scratch
+                    .iter_mut()
+                    .enumerate()
+                    .map(|(w, scratch)| {
+                        s.spawn(move || {
+                            guard(|at| scratch.multiply_rows(a, b, w * block_rows, block_rows, at))
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                // join every worker before failing, the first block in order decides
+                let mut joined = Ok(());
+                for worker in workers {
+                    let rst = worker.join().unwrap_or(Err(MatrixError::WorkerFailed));
+                    if joined.is_ok() {
+                        joined = rst;
+                    }
+                }
+                joined
+            })?;
+        }
+
+        // map/reduce: reduce phase
+        let mut data = Vec::with_capacity(a.row * b.col);
+        for scratch in scratch.iter() {
+            data.extend_from_slice(&scratch.out);
+        }
+        Ok(Matrix::new(data, a.row, b.col))
+    }
+}
+
+impl<T> Scratch<T>
+where
+    T: Mul<Output = T> + Add<Output = T> + AddAssign + Default + Clone,
+{
+    // rows `start..start + len` of `a * b`, clamped to `a.row`, into `self.out`,
+    // keeping `at` on the output cell being computed
+    fn multiply_rows(
+        &mut self,
+        a: &MatrixRef<'_, T>,
+        b: &MatrixRef<'_, T>,
+        start: usize,
+        len: usize,
+        at: &mut (usize, usize),
+    ) {
+        let (k, n) = (a.col, b.col);
+        let rows = start..(start + len).min(a.row);
+        self.out.clear();
+        self.out.resize(rows.len() * n, T::default());
+
+        for j in 0..n {
+            self.col.clear();
+            self.col.extend(b.data.iter().skip(j).step_by(n).cloned());
+            for (r, i) in rows.clone().enumerate() {
+                *at = (i, j);
+                let mut sum = T::default();
+                for (x, y) in a.data[i * k..(i + 1) * k].iter().zip(&self.col) {
+                    sum += x.clone() * y.clone();
+                }
+                self.out[r * n + j] = sum;
+            }
+        }
+    }
+}
+
+// products under `serial_threshold`, and every product on targets without threads,
+// e.g. wasm32-unknown-unknown
+fn multiply_sequential<T>(
+    a: &MatrixRef<'_, T>,
+    b: &MatrixRef<'_, T>,
+    progress: &mut Progress<'_>,
+) -> Result<Matrix<T>, MatrixError>
+where
+    T: Mul<Output = T> + Add<Output = T> + AddAssign + Default + Clone,
+{
+    let k = a.col;
+    let columns = columns(b);
+    let mut data = Vec::with_capacity(a.row * b.col);
+    for i in 0..a.row {
+        for j in 0..b.col {
+            data.push(dot(
+                &a.data[i * k..(i + 1) * k],
+                &columns[j * k..(j + 1) * k],
+            ));
+        }
+        progress.advance(b.col);
+    }
+
+    Ok(Matrix::new(data, a.row, b.col))
+}
+
+impl<T, S> Debug for Matrix<T, S>
+where
+    T: Debug,
+    S: Storage<T>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Matrix(row={}, col={}, {{", self.row, self.col)?;
+        for (i, row) in self.rows().enumerate() {
+            if i != 0 {
+                write!(f, ", ")?;
+            }
+            for (j, x) in row.enumerate() {
+                if j != 0 {
+                    write!(f, " ")?;
+                }
+                write!(f, "{:?}", x)?;
+            }
+        }
+        write!(f, "}})")
+    }
+}
+
+impl<T> Matrix<T> {
+    pub fn new(data: impl Into<Vec<T>>, row: usize, col: usize) -> Self {
+        Self::from_storage(data.into(), row, col)
+    }
+
+    /// Wraps `data` holding a `row` x `col` matrix column after column, without
+    /// copying it.
+    pub fn from_col_major(data: impl Into<Vec<T>>, row: usize, col: usize) -> Self {
+        Self {
+            layout: Layout::ColMajor,
+            ..Self::new(data, row, col)
+        }
+    }
+
+    /// Flattens `rows` into a `rows.len()` x `rows[0].len()` matrix, or `RaggedRows`
+    /// for the first row whose length differs from the first.
+    pub fn try_from_rows(rows: Vec<Vec<T>>) -> Result<Self, MatrixError> {
+        let row = rows.len();
+        let col = rows.first().map_or(0, Vec::len);
+        if let Some(i) = rows.iter().position(|r| r.len() != col) {
+            return Err(MatrixError::RaggedRows {
+                row: i,
+                len: rows[i].len(),
+                expected: col,
+            });
+        }
+        Ok(Self::new(
+            rows.into_iter().flatten().collect::<Vec<_>>(),
+            row,
+            col,
+        ))
+    }
+
+    /// A `row` x `col` matrix with `f(i, j)` in row `i` and column `j`.
+    pub fn from_fn(row: usize, col: usize, mut f: impl FnMut(usize, usize) -> T) -> Self {
+        let mut data = Vec::with_capacity(row * col);
+        for i in 0..row {
+            data.extend((0..col).map(|j| f(i, j)));
+        }
+        Self::new(data, row, col)
+    }
+
+    /// A `row` x `col` matrix of `T::default()`.
+    pub fn zeros(row: usize, col: usize) -> Self
+    where
+        T: Default + Clone,
+    {
+        Self::new(vec![T::default(); row * col], row, col)
+    }
+
+    /// The `n` x `n` identity, ones on the diagonal and `T::default()` elsewhere.
+    pub fn identity(n: usize) -> Self
+    where
+        T: Default + From<u8>,
+    {
+        Self::from_fn(n, n, |i, j| if i == j { T::from(1) } else { T::default() })
+    }
+
+    /// The same elements read in row-major order into `row` rows of `col`, or
+    /// `Reshape` unless `row * col == self.len()`. Reuses the buffer of a row-major
+    /// matrix.
+    pub fn reshape(self, row: usize, col: usize) -> Result<Self, MatrixError>
+    where
+        T: Clone,
+    {
+        if row.checked_mul(col) != Some(self.len()) {
+            return Err(MatrixError::Reshape {
+                len: self.len(),
+                shape: (row, col),
+            });
+        }
+        Ok(Self::new(self.into_row_major().data, row, col))
+    }
+
+    /// The same matrix in a `RowMajor` buffer, reordered only if it is `ColMajor`.
+    pub fn into_row_major(self) -> Self
+    where
+        T: Clone,
+    {
+        match self.layout {
+            Layout::RowMajor => self,
+            Layout::ColMajor => self.row_major().into_owned(),
+        }
+    }
+
+    /// Moves the elements into an `Arc<[T]>`, so clones of the matrix, e.g. one per
+    /// thread multiplying by it, share a single buffer.
+    pub fn into_shared(self) -> Matrix<T, Arc<[T]>> {
+        Matrix {
+            layout: self.layout,
+            ..Matrix::from_storage(self.data.into(), self.row, self.col)
+        }
+    }
+
+    // what the kernels read: this matrix if it is row-major, else a row-major copy
+    pub(crate) fn row_major(&self) -> Cow<'_, Matrix<T>>
+    where
+        T: Clone,
+    {
+        match self.row_major_data() {
+            Cow::Borrowed(_) => Cow::Borrowed(self),
+            Cow::Owned(data) => Cow::Owned(Matrix::new(data, self.row, self.col)),
+        }
+    }
+}
+
+impl<T, S: Storage<T>> Matrix<T, S> {
+    /// Wraps `data`, `row` rows of `col` elements back to back, in any storage.
+    pub fn from_storage(data: S, row: usize, col: usize) -> Self {
+        Self {
+            data,
+            row,
+            col,
+            layout: Layout::RowMajor,
+            _elements: PhantomData,
+        }
+    }
+
+    pub fn row(&self) -> usize {
+        self.row
+    }
+
+    pub fn col(&self) -> usize {
+        self.col
+    }
+
+    /// `(row, col)`.
+    pub fn shape(&self) -> (usize, usize) {
+        (self.row, self.col)
+    }
+
+    /// The number of elements, `row * col`.
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Whether there are no elements, with no rows or no columns.
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    /// The elements in the order of `layout`.
+    pub fn as_slice(&self) -> &[T] {
+        &self.data
+    }
+
+    /// The rows, each an iterator over `col` elements, contiguous in a `RowMajor`
+    /// buffer and `row` apart in a `ColMajor` one.
+    pub fn rows(&self) -> impl ExactSizeIterator<Item = StepBy<slice::Iter<'_, T>>> + '_ {
+        (0..self.row).map(move |i| match self.layout {
+            Layout::RowMajor => self.data[i * self.col..(i + 1) * self.col]
+                .iter()
+                .step_by(1),
+            Layout::ColMajor => strided(&self.data, i, self.row),
+        })
+    }
+
+    /// The columns, each an iterator over `row` elements, `col` apart in a
+    /// `RowMajor` buffer and contiguous in a `ColMajor` one.
+    pub fn cols(&self) -> impl ExactSizeIterator<Item = StepBy<slice::Iter<'_, T>>> + '_ {
+        (0..self.col).map(move |j| match self.layout {
+            Layout::RowMajor => strided(&self.data, j, self.col),
+            Layout::ColMajor => self.data[j * self.row..(j + 1) * self.row]
+                .iter()
+                .step_by(1),
+        })
+    }
+
+    /// Row `i` as a vector, borrowing this buffer when it is `RowMajor` and copying
+    /// the strided elements otherwise. Panics if `i` is out of bounds.
+    pub fn row_vector(&self, i: usize) -> VectorView<'_, T>
+    where
+        T: Clone,
+    {
+        assert!(
+            i < self.row,
+            "Matrix row {i} out of bounds for {} rows",
+            self.row
+        );
+        match self.layout {
+            Layout::RowMajor => VectorView::from(&self.data[i * self.col..(i + 1) * self.col]),
+            Layout::ColMajor => strided(&self.data, i, self.row).cloned().collect(),
+        }
+    }
+
+    /// Column `j` as a vector, borrowing this buffer when it is `ColMajor` and
+    /// copying the strided elements otherwise. Panics if `j` is out of bounds.
+    pub fn col_vector(&self, j: usize) -> VectorView<'_, T>
+    where
+        T: Clone,
+    {
+        assert!(
+            j < self.col,
+            "Matrix column {j} out of bounds for {} columns",
+            self.col
+        );
+        match self.layout {
+            Layout::RowMajor => strided(&self.data, j, self.col).cloned().collect(),
+            Layout::ColMajor => VectorView::from(&self.data[j * self.row..(j + 1) * self.row]),
+        }
+    }
+
+    /// A copy of the elements in a `Vec`, in the same layout.
+    pub fn to_matrix(&self) -> Matrix<T>
+    where
+        T: Clone,
+    {
+        Matrix {
+            layout: self.layout,
+            ..Matrix::new(self.data.to_vec(), self.row, self.col)
+        }
+    }
+
+    // the elements in row-major order, borrowed unless they have to be reordered
+    fn row_major_data(&self) -> Cow<'_, [T]>
+    where
+        T: Clone,
+    {
+        match self.layout {
+            Layout::RowMajor => Cow::Borrowed(&self.data),
+            Layout::ColMajor => {
+                // the buffer read row by row is the `col` x `row` transpose
+                let mut data = self.data.to_vec();
+                transpose::transpose_block(&self.data, self.col, self.row, &mut data, 0);
+                Cow::Owned(data)
+            }
+        }
+    }
+
+    // the row-major matrix the kernels read, borrowing this buffer if it can
+    pub(crate) fn as_row_major(&self) -> MatrixRef<'_, T>
+    where
+        T: Clone,
+    {
+        Matrix::from_storage(self.row_major_data(), self.row, self.col)
+    }
+
+    /// The element in row `i` and column `j`, or `None` out of bounds.
+    pub fn get(&self, i: usize, j: usize) -> Option<&T> {
+        let idx = self.offset(i, j)?;
+        self.data.get(idx)
+    }
+
+    /// The elements `(i, i)` of a square matrix, or `NotSquare`.
+    pub fn diagonal(&self) -> Result<Vector<T>, MatrixError>
+    where
+        T: Clone,
+    {
+        self.check_square()?;
+        // (i, i) sits at `i * (col + 1)` in either layout of a square matrix
+        let diagonal = self.data.iter().step_by(self.col + 1).cloned();
+        Ok(Vector::new(diagonal.collect::<Vec<_>>()))
+    }
+
+    /// The sum of the diagonal of a square matrix, or `NotSquare`.
+    pub fn trace(&self) -> Result<T, MatrixError>
+    where
+        T: AddAssign + Default + Clone,
+    {
+        self.check_square()?;
+        let mut sum = T::default();
+        for x in self.data.iter().step_by(self.col + 1) {
+            sum += x.clone();
+        }
+        Ok(sum)
+    }
+
+    pub(crate) fn check_square(&self) -> Result<(), MatrixError> {
+        if self.row != self.col {
+            return Err(MatrixError::NotSquare {
+                row: self.row,
+                col: self.col,
+            });
+        }
+        Ok(())
+    }
+
+    // the buffer index of `(i, j)`, checked against both dimensions, not just
+    // the buffer length, so `(0, col)` does not alias `(1, 0)`
+    fn offset(&self, i: usize, j: usize) -> Option<usize> {
+        (i < self.row && j < self.col).then(|| match self.layout {
+            Layout::RowMajor => i * self.col + j,
+            Layout::ColMajor => j * self.row + i,
+        })
+    }
+}
+
+impl<T, S: Storage<T> + DerefMut> Matrix<T, S> {
+    pub fn get_mut(&mut self, i: usize, j: usize) -> Option<&mut T> {
+        let idx = self.offset(i, j)?;
+        self.data.get_mut(idx)
+    }
+}
+
+// every `step`th element of `data` from `first` on, empty past the end
+fn strided<T>(data: &[T], first: usize, step: usize) -> StepBy<slice::Iter<'_, T>> {
+    data.get(first..).unwrap_or(&[]).iter().step_by(step)
+}
+
+/// Equal when the shapes and the elements at every position are, whatever the
+/// layouts and storages.
+impl<T, S, S2> PartialEq<Matrix<T, S2>> for Matrix<T, S>
+where
+    T: PartialEq,
+    S: Storage<T>,
+    S2: Storage<T>,
+{
+    fn eq(&self, other: &Matrix<T, S2>) -> bool {
+        if (self.row, self.col) != (other.row, other.col) {
+            return false;
+        }
+        if self.layout == other.layout {
+            return *self.data == *other.data;
+        }
+        self.rows().zip(other.rows()).all(|(x, y)| x.eq(y))
+    }
+}
+
+impl<T: Eq, S: Storage<T>> Eq for Matrix<T, S> {}
+
+impl<T, S: Storage<T>> Matrix<T, S> {
+    /// Equal shapes and every pair of elements within `epsilon` of each other,
+    /// absolutely or relative to the larger of the two, so sums added up in a
+    /// different order on other threads still compare equal. `NaN` never does.
+    pub fn approx_eq<S2: Storage<T>>(&self, other: &Matrix<T, S2>, epsilon: f64) -> bool
+    where
+        T: Clone + Into<f64>,
+    {
+        if (self.row, self.col) != (other.row, other.col) {
+            return false;
+        }
+        let abs = |x: f64| if x < 0.0 { -x } else { x };
+        self.rows().zip(other.rows()).all(|(x, y)| {
+            x.zip(y).all(|(x, y)| {
+                let (x, y) = (x.clone().into(), y.clone().into());
+                let diff = abs(x - y);
+                diff <= epsilon || diff <= epsilon * abs(x).max(abs(y))
+            })
+        })
+    }
+}
+
+/// Panics out of bounds, `get` returns `None` instead.
+impl<T, S: Storage<T>> Index<(usize, usize)> for Matrix<T, S> {
+    type Output = T;
+    fn index(&self, (i, j): (usize, usize)) -> &Self::Output {
+        let (row, col) = (self.row, self.col);
+        self.get(i, j)
+            .unwrap_or_else(|| panic!("Matrix index ({i}, {j}) out of bounds for {row}x{col}"))
+    }
+}
+
+impl<T, S: Storage<T> + DerefMut> IndexMut<(usize, usize)> for Matrix<T, S> {
+    fn index_mut(&mut self, (i, j): (usize, usize)) -> &mut Self::Output {
+        let (row, col) = (self.row, self.col);
+        self.get_mut(i, j)
+            .unwrap_or_else(|| panic!("Matrix index ({i}, {j}) out of bounds for {row}x{col}"))
+    }
+}
+
+impl<T> Matrix<T>
+where
+    T: Mul<Output = T> + Add<Output = T> + AddAssign + Default + Clone + Send + Sync + 'static,
+{
+    /// The 2D convolution with `kernel`, flipped as in the mathematical definition,
+    /// so a symmetric kernel gives the same result as a stencil.
+    ///
+    /// The output is cut into square tiles computed as jobs on `ThreadPool::global`,
+    /// or one after another without the `threads` feature.
+    pub fn convolve(&self, kernel: &Matrix<T>, padding: Padding) -> Result<Self, MatrixError> {
+        if kernel.row == 0 || kernel.col == 0 {
+            return Err(MatrixError::EmptyKernel);
+        }
+        let (offset, out) = match padding {
+            Padding::Valid => (
+                (kernel.row - 1, kernel.col - 1),
+                (
+                    (self.row + 1).saturating_sub(kernel.row),
+                    (self.col + 1).saturating_sub(kernel.col),
+                ),
+            ),
+            Padding::Same => (
+                ((kernel.row - 1) / 2, (kernel.col - 1) / 2),
+                (self.row, self.col),
+            ),
+        };
+        let stencil = Stencil {
+            input: self.row_major().into_owned(),
+            kernel: kernel.row_major().into_owned(),
+            offset,
+            out,
+        };
+        let tiles = (0..out.0)
+            .step_by(CONV_TILE)
+            .flat_map(|i| (0..out.1).step_by(CONV_TILE).map(move |j| (i, j)));
+        let mut data = vec![T::default(); out.0 * out.1];
+
+        #[cfg(feature = "threads")]
+        {
+            let pool = ThreadPool::global();
+            let stencil = Arc::new(stencil);
+
+            // map/reduce: map phase
+            let (tx, rx) = mpsc::channel();
+            let mut queued = 0;
+            for (i, j) in tiles {
+                let stencil = Arc::clone(&stencil);
+                let tx = tx.clone();
+                pool.execute(move || {
+                    let _ = tx.send((i, j, stencil.tile(i, j)));
+                });
+                queued += 1;
+            }
+            drop(tx);
+
+            // map/reduce: reduce phase, a job that panicked drops its sender without sending
+            for _ in 0..queued {
+                let (i, j, tile) = rx.recv().map_err(|_| MatrixError::WorkerFailed)?;
+                stencil.place(&mut data, i, j, &tile);
+            }
+        }
+        #[cfg(not(feature = "threads"))]
+        for (i, j) in tiles {
+            stencil.place(&mut data, i, j, &stencil.tile(i, j));
+        }
+
+        Ok(Matrix::new(data, out.0, out.1))
+    }
 }
 
-pub fn multiply<T>(a: &Matrix<T>, b: &Matrix<T>) -> Result<Matrix<T>>
+impl<T> Stencil<T>
 where
-    T: Display + Mul<Output = T> + Add<Output = T> + AddAssign + Default + Copy + Send + 'static,
+    T: Mul<Output = T> + Add<Output = T> + AddAssign + Default + Clone,
 {
-    if a.col != b.row {
-        anyhow::bail!("Matrix multiply error: a.col != b.row");
-    }
-
-    let senders = (0..THREAD_NUM)
-        .map(|_| {
-            let (tx, rx) = mpsc::channel::<Msg<T>>();
-            thread::spawn(|| {
-                for msg in rx {
-                    let value = dot_product(msg.input.row, msg.input.col)?;
-                    if let Err(e) = msg.sender.send(MsgOutput {
-                        value,
-                        idx: msg.input.idx,
-                    }) {
-                        eprintln!("Send error: {}", e);
+    fn tile_cols(&self, j: usize) -> usize {
+        CONV_TILE.min(self.out.1 - j)
+    }
+
+    // the tile with its top left corner at output (i, j), row-major
+    fn tile(&self, i: usize, j: usize) -> Vec<T> {
+        let (input, kernel) = (&self.input, &self.kernel);
+        let rows = i..(i + CONV_TILE).min(self.out.0);
+        let cols = j..j + self.tile_cols(j);
+        let mut tile = Vec::with_capacity(rows.len() * cols.len());
+        for y in rows {
+            for x in cols.clone() {
+                let mut sum = T::default();
+                for p in 0..kernel.row {
+                    // rows past either border of the input are zero padding
+                    let Some(r) = (y + self.offset.0).checked_sub(p) else {
+                        continue;
+                    };
+                    if r >= input.row {
+                        continue;
+                    }
+                    for q in 0..kernel.col {
+                        let Some(c) = (x + self.offset.1).checked_sub(q) else {
+                            continue;
+                        };
+                        if c < input.col {
+                            let (k, v) = (
+                                &kernel.data[p * kernel.col + q],
+                                &input.data[r * input.col + c],
+                            );
+                            sum += k.clone() * v.clone();
+                        }
                     }
                 }
-                Ok::<_, anyhow::Error>(())
-            });
-            tx
-        })
-        .collect::<Vec<_>>();
-
-    let matrix_len = a.row * b.col;
-
-    let mut data = vec![T::default(); matrix_len];
-    let mut receivers = Vec::with_capacity(matrix_len);
-
-    // map/reduce: map phase
-    for i in 0..a.row {
-        for j in 0..b.col {
-            let row = Vector::new(&a.data[i * a.col..(i + 1) * a.col]);
-            let col_data = b.data[j..]
-                .iter()
-                .step_by(b.col)
-                .copied()
-                .collect::<Vec<_>>();
-            let col = Vector::new(col_data);
-            let idx = i * b.col + j;
-
-            let input = MsgInput::new(idx, row, col);
-            let (tx, rx) = oneshot::channel();
-            let msg = Msg::new(input, tx);
-            if let Err(e) = senders[idx % THREAD_NUM].send(msg) {
-                eprintln!("Result send error: {}", e);
+                tile.push(sum);
             }
-            receivers.push(rx);
         }
+        tile
     }
 
-    // map/reduce: reduce phase
-    for rx in receivers {
-        let rst = rx.recv()?;
-        data[rst.idx] = rst.value;
+    fn place(&self, data: &mut [T], i: usize, j: usize, tile: &[T]) {
+        let cols = self.tile_cols(j);
+        for (r, row) in tile.chunks(cols).enumerate() {
+            let start = (i + r) * self.out.1 + j;
+            data[start..start + cols].clone_from_slice(row);
+        }
     }
-
-    Ok(Matrix {
-        data,
-        row: a.row,
-        col: b.col,
-    })
 }
 
-impl<T> Display for Matrix<T>
+#[cfg(feature = "complex")]
+impl<T> Matrix<num_complex::Complex<T>>
 where
-    T: Display,
+    T: Clone + core::ops::Neg<Output = T>,
 {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{{")?;
-        for i in 0..self.row {
-            for j in 0..self.col {
-                write!(f, "{}", self.data[i * self.col + j])?;
-                if j != self.col - 1 {
-                    write!(f, " ")?;
-                }
-            }
-            if i != self.row - 1 {
-                write!(f, ", ")?;
+    /// The conjugate transpose, a `col` x `row` matrix.
+    pub fn hermitian(&self) -> Self {
+        let mut data = Vec::with_capacity(self.data.len());
+        for j in 0..self.col {
+            for i in 0..self.row {
+                let v = &self[(i, j)];
+                data.push(num_complex::Complex::new(v.re.clone(), -v.im.clone()));
             }
         }
-        write!(f, "}}")?;
-        Ok(())
+        Matrix::new(data, self.col, self.row)
     }
 }
 
-impl<T> Debug for Matrix<T>
+/// `*` that reports a shape mismatch instead of panicking, e.g. `a.try_mul(&b)?`.
+pub trait TryMul<Rhs = Self> {
+    type Output;
+    type Error;
+
+    fn try_mul(self, rhs: Rhs) -> Result<Self::Output, Self::Error>;
+}
+
+/// Panics when `a.col != b.row`, `TryMul` returns the error instead.
+impl<T> Mul for Matrix<T>
 where
-    T: Display,
+    T: Display + Mul<Output = T> + Add<Output = T> + AddAssign + Default + Clone + Send + Sync,
 {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Matrix(row={}, col={}, {})", self.row, self.col, self)
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self::Output {
+        multiply(&self, &rhs).expect("Matrix multiply error")
     }
 }
 
-impl<T> Matrix<T> {
-    pub fn new(data: impl Into<Vec<T>>, row: usize, col: usize) -> Self {
-        Self {
-            data: data.into(),
-            row,
-            col,
-        }
+/// Panics when `a.col != b.row`, `TryMul` returns the error instead.
+impl<T> Mul for &Matrix<T>
+where
+    T: Display + Mul<Output = T> + Add<Output = T> + AddAssign + Default + Clone + Send + Sync,
+{
+    type Output = Matrix<T>;
+    fn mul(self, rhs: Self) -> Self::Output {
+        multiply(self, rhs).expect("Matrix multiply error")
     }
 }
 
-impl<T> Mul for Matrix<T>
+impl<T> TryMul for Matrix<T>
 where
-    T: Display + Mul<Output = T> + Add<Output = T> + AddAssign + Default + Copy + Send + 'static,
+    T: Display + Mul<Output = T> + Add<Output = T> + AddAssign + Default + Clone + Send + Sync,
 {
     type Output = Self;
-    fn mul(self, rhs: Self) -> Self::Output {
-        multiply(&self, &rhs).expect("Matrix multiply error")
+    type Error = MatrixError;
+    fn try_mul(self, rhs: Self) -> Result<Self::Output, Self::Error> {
+        multiply(&self, &rhs)
     }
 }
 
-impl<T> MsgInput<T> {
-    pub fn new(idx: usize, row: Vector<T>, col: Vector<T>) -> Self {
-        Self { idx, row, col }
+impl<T> TryMul for &Matrix<T>
+where
+    T: Display + Mul<Output = T> + Add<Output = T> + AddAssign + Default + Clone + Send + Sync,
+{
+    type Output = Matrix<T>;
+    type Error = MatrixError;
+    fn try_mul(self, rhs: Self) -> Result<Self::Output, Self::Error> {
+        multiply(self, rhs)
     }
 }
 
+#[cfg(all(feature = "threads", not(feature = "rayon")))]
 impl<T> Msg<T> {
-    pub fn new(input: MsgInput<T>, sender: oneshot::Sender<MsgOutput<T>>) -> Self {
-        Self { input, sender }
+    pub fn new(idx: usize, sender: oneshot::Sender<Result<MsgOutput<T>, MatrixError>>) -> Self {
+        Self { idx, sender }
     }
 }
 
@@ -165,11 +2030,288 @@ impl<T> Msg<T> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_vector_outer() {
+        let a = Vector::new([1, 2, 3]);
+        let b = Vector::new([4, 5]);
+        assert_eq!(a.outer(&b), Matrix::new([4, 5, 8, 10, 12, 15], 3, 2));
+        // a column times a row, as a product of matrices
+        let (col, row) = (Matrix::new([1, 2, 3], 3, 1), Matrix::new([4, 5], 1, 2));
+        assert_eq!(a.outer(&b), multiply(&col, &row).unwrap());
+        assert_eq!(a.outer(&Vector::new([])).shape(), (3, 0));
+
+        // large enough for the threads
+        let v = (0..200).collect::<Vector<i64>>();
+        let m = v.outer(&v);
+        assert_eq!(m[(199, 198)], 199 * 198);
+        assert_eq!(m, m.transpose());
+    }
+
+    #[test]
+    fn test_matrix_row_and_col_vectors() {
+        let a = Matrix::new([1, 2, 3, 4, 5, 6], 2, 3);
+        let b = Matrix::from_col_major([1, 4, 2, 5, 3, 6], 2, 3);
+        for m in [&a, &b] {
+            assert_eq!(*m.row_vector(1), [4, 5, 6]);
+            assert_eq!(*m.col_vector(2), [3, 6]);
+        }
+        // only the contiguous ones borrow
+        assert!(a.row_vector(0).is_borrowed());
+        assert!(!a.col_vector(0).is_borrowed());
+        assert!(b.col_vector(0).is_borrowed());
+        assert_eq!(
+            crate::dot_product_slice(&a.row_vector(0), &b.row_vector(1)).unwrap(),
+            32
+        );
+    }
+
+    #[test]
+    fn test_matrix_approx_eq() {
+        let a = Matrix::new([0.1f64 + 0.2, 1e9, -3.0, 0.0], 2, 2);
+        let b = Matrix::from_col_major([0.3, -3.0, 1e9 + 1.0, 1e-12], 2, 2);
+        assert_ne!(a, b);
+        assert!(a.approx_eq(&b, 1e-6));
+        assert!(!a.approx_eq(&b, 1e-12));
+        assert!(!a.approx_eq(&Matrix::new([0.3, 1e9], 1, 2), 1.0));
+
+        let nan = Matrix::new([f32::NAN], 1, 1);
+        assert!(!nan.approx_eq(&nan, f64::MAX));
+    }
+
     #[test]
     fn test_matrix_display_and_debug() {
         let a = Matrix::new([1, 2, 3, 4], 2, 2);
         assert_eq!(format!("{}", a), "{1 2, 3 4}");
         assert_eq!(format!("{:?}", a), "Matrix(row=2, col=2, {1 2, 3 4})");
+
+        // Debug needs only Debug elements
+        let b = Matrix::new([Some(1), None], 1, 2);
+        assert_eq!(format!("{:?}", b), "Matrix(row=1, col=2, {Some(1) None})");
+    }
+
+    #[test]
+    fn test_matrix_index() {
+        let mut a = Matrix::new([1, 2, 3, 4, 5, 6], 2, 3);
+        assert_eq!(a[(0, 2)], 3);
+        assert_eq!(a[(1, 0)], 4);
+        assert_eq!(a.get(1, 2), Some(&6));
+        assert_eq!(a.get(0, 3), None);
+        assert_eq!(a.get(2, 0), None);
+
+        a[(1, 1)] = 50;
+        *a.get_mut(0, 0).unwrap() = 10;
+        assert_eq!(a.as_slice(), [10, 2, 3, 4, 50, 6]);
+        assert!(a.get_mut(2, 2).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn test_matrix_index_out_of_bounds() {
+        let a = Matrix::new([1, 2, 3, 4], 2, 2);
+        let _ = a[(0, 2)];
+    }
+
+    #[test]
+    fn test_matrix_constructors() {
+        assert_eq!(Matrix::<i32>::zeros(2, 3), Matrix::new([0; 6], 2, 3));
+        assert_eq!(
+            Matrix::<f64>::identity(3),
+            Matrix::new([1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0], 3, 3)
+        );
+        let a = Matrix::from_fn(2, 3, |i, j| 10 * i + j);
+        assert_eq!(a, Matrix::new([0, 1, 2, 10, 11, 12], 2, 3));
+        assert_eq!(multiply(&a, &Matrix::identity(3)).unwrap(), a);
+        assert!(Matrix::<i32>::identity(0).as_slice().is_empty());
+    }
+
+    #[test]
+    fn test_matrix_try_from_rows() {
+        let a = Matrix::try_from_rows(vec![vec![1, 2, 3], vec![4, 5, 6]]).unwrap();
+        assert_eq!(a, Matrix::new([1, 2, 3, 4, 5, 6], 2, 3));
+        let empty = Matrix::<i32>::try_from_rows(vec![]).unwrap();
+        assert_eq!((empty.row, empty.col), (0, 0));
+
+        let err = Matrix::try_from_rows(vec![vec![1, 2], vec![3, 4], vec![5]]).unwrap_err();
+        assert_eq!(
+            err,
+            MatrixError::RaggedRows {
+                row: 2,
+                len: 1,
+                expected: 2
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "Matrix error: row 2 has 1 columns, expected 2"
+        );
+    }
+
+    #[test]
+    fn test_matrix_rows_and_cols() {
+        let a = Matrix::new([1, 2, 3, 4, 5, 6], 2, 3);
+        let rows = a
+            .rows()
+            .map(|r| r.copied().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(rows, [[1, 2, 3], [4, 5, 6]]);
+        let cols = a
+            .cols()
+            .map(|c| c.copied().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(cols, [[1, 4], [2, 5], [3, 6]]);
+        assert_eq!(a.cols().len(), 3);
+
+        // no elements, but still the right number of empty rows
+        let a = Matrix::new(Vec::<i32>::new(), 2, 0);
+        assert_eq!(a.rows().map(|r| r.len()).collect::<Vec<_>>(), [0, 0]);
+        assert_eq!(a.cols().len(), 0);
+        let a = Matrix::new(Vec::<i32>::new(), 0, 2);
+        assert_eq!(a.rows().len(), 0);
+        assert!(a.cols().all(|mut c| c.next().is_none()));
+    }
+
+    #[test]
+    fn test_matrix_shape_and_reshape() {
+        let a = Matrix::new([1, 2, 3, 4, 5, 6], 2, 3);
+        assert_eq!((a.shape(), a.len(), a.is_empty()), ((2, 3), 6, false));
+        let b = a.clone().reshape(3, 2).unwrap();
+        assert_eq!(b, Matrix::new([1, 2, 3, 4, 5, 6], 3, 2));
+        assert_eq!(b.reshape(1, 6).unwrap().shape(), (1, 6));
+        // read row by row whatever the layout
+        let c = Matrix::from_col_major([1, 4, 2, 5, 3, 6], 2, 3);
+        assert_eq!(c.reshape(6, 1).unwrap().as_slice(), a.as_slice());
+
+        assert_eq!(
+            a.clone().reshape(4, 2).unwrap_err(),
+            MatrixError::Reshape {
+                len: 6,
+                shape: (4, 2)
+            }
+        );
+        assert_eq!(
+            a.reshape(usize::MAX, 2).unwrap_err().to_string(),
+            format!(
+                "Matrix error: cannot reshape 6 elements into {}x2",
+                usize::MAX
+            )
+        );
+        assert!(Matrix::<i32>::zeros(0, 3).reshape(5, 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_matrix_storage() {
+        let a = Matrix::from_fn(40, 40, |i, j| (i * 40 + j) as i64 % 13 - 6);
+        let expected = multiply(&a, &a).unwrap();
+
+        // one buffer, multiplied on several threads at once without being cloned
+        let shared = a.clone().into_shared();
+        std::thread::scope(|s| {
+            for _ in 0..3 {
+                let shared = shared.clone();
+                let expected = &expected;
+                s.spawn(move || assert_eq!(&multiply(&shared, &shared).unwrap(), expected));
+            }
+        });
+        assert_eq!(shared, a);
+        assert!(core::ptr::eq(shared.as_slice(), shared.clone().as_slice()));
+
+        let boxed = Matrix::from_storage(a.as_slice().to_vec().into_boxed_slice(), 40, 40);
+        assert_eq!(multiply(&boxed, &a).unwrap(), expected);
+        assert_eq!((boxed[(1, 2)], boxed.shape()), (a[(1, 2)], (40, 40)));
+        assert_eq!(boxed.to_matrix(), a);
+        let slice = Matrix::from_storage(&a.as_slice()[..6], 2, 3);
+        assert_eq!(format!("{}", slice), "{-6 -5 -4, -3 -2 -1}");
+    }
+
+    #[test]
+    fn test_matrix_col_major() {
+        // [[1, 2, 3], [4, 5, 6]] stored column after column
+        let a = Matrix::from_col_major([1, 4, 2, 5, 3, 6], 2, 3);
+        let expected = Matrix::new([1, 2, 3, 4, 5, 6], 2, 3);
+        assert_eq!(a.layout(), Layout::ColMajor);
+        assert_eq!((a[(0, 2)], a[(1, 0)]), (3, 4));
+        assert_eq!(a.get(2, 0), None);
+        assert_eq!(a, expected);
+        assert_eq!(format!("{}", a), "{1 2 3, 4 5 6}");
+        assert_eq!(format!("{:?}", a), "Matrix(row=2, col=3, {1 2 3, 4 5 6})");
+        let rows = a
+            .rows()
+            .map(|r| r.copied().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(rows, [[1, 2, 3], [4, 5, 6]]);
+        assert_eq!(
+            a.cols().next().unwrap().copied().collect::<Vec<_>>(),
+            [1, 4]
+        );
+
+        // products and element-wise ops come back row-major, whatever the operands
+        let b = Matrix::from_col_major([1, 0, 2, 0, 1, 1], 3, 2);
+        let c = multiply(&a, &b).unwrap();
+        assert_eq!(c.layout(), Layout::RowMajor);
+        assert_eq!(c.as_slice(), [7, 5, 16, 11]);
+        assert_eq!(c, multiply(&expected, &b.clone().into_row_major()).unwrap());
+        assert_eq!((&a + &expected).as_slice(), [2, 4, 6, 8, 10, 12]);
+        let mut d = a.clone();
+        d -= &expected;
+        assert!(d.as_slice().iter().all(|&x| x == 0));
+
+        // the buffer of a column-major matrix read row by row is its transpose
+        assert_eq!(a.transpose().as_slice(), a.as_slice());
+        assert_eq!(a.transpose(), expected.transpose());
+        assert_eq!(a.into_row_major().as_slice(), expected.as_slice());
+    }
+
+    #[test]
+    fn test_matrix_error_messages() {
+        let cases = [
+            (
+                MatrixError::DimensionMismatch {
+                    left: (2, 3),
+                    right: (2, 3),
+                },
+                "Matrix multiply error: a.col != b.row (2x3 * 2x3)",
+            ),
+            (
+                MatrixError::WorkerPanicked { row: 1, col: 4 },
+                "Matrix multiply error: worker panicked at cell (1, 4)",
+            ),
+            (MatrixError::Cancelled, "Matrix multiply error: cancelled"),
+            (
+                VectorError::LengthMismatch { left: 1, right: 2 }.into(),
+                "Matrix multiply error: Dot product error: a.len != b.len (1 != 2)",
+            ),
+        ];
+        for (err, msg) in cases {
+            assert_eq!(err.to_string(), msg);
+        }
+    }
+
+    #[test]
+    fn test_matrix_trace_and_diagonal() {
+        let a = Matrix::from_fn(3, 3, |i, j| 10 * i + j);
+        assert_eq!(a.diagonal().unwrap(), Vector::new([0, 11, 22]));
+        assert_eq!(a.trace().unwrap(), 33);
+
+        let empty = Matrix::<i32>::zeros(0, 0);
+        assert!(empty.diagonal().unwrap().is_empty());
+        assert_eq!(empty.trace().unwrap(), 0);
+
+        let err = MatrixError::NotSquare { row: 2, col: 3 };
+        assert_eq!(Matrix::<i32>::zeros(2, 3).trace().unwrap_err(), err);
+        assert_eq!(Matrix::<i32>::zeros(2, 3).diagonal().unwrap_err(), err);
+    }
+
+    #[test]
+    fn test_matrix_eq_and_clone() {
+        let a = Matrix::new([1, 2, 3, 4, 5, 6], 2, 3);
+        let b = a.clone();
+        assert_eq!(a, b);
+        assert_ne!(a, Matrix::new([1, 2, 3, 4, 5, 6], 3, 2));
+        assert_ne!(a, Matrix::new([1, 2, 3, 4, 5, 7], 2, 3));
+
+        let v = Vector::new([1, 2]);
+        assert_eq!(v.clone(), v);
+        assert_ne!(v, Vector::new([2, 1]));
     }
 
     #[test]
@@ -177,26 +2319,648 @@ mod tests {
         let a = Matrix::new([1, 2, 3, 4], 2, 2);
         let b = Matrix::new([1, 2, 3, 4], 2, 2);
         let c = a * b;
-        assert_eq!(c.row, 2);
-        assert_eq!(c.col, 2);
-        assert_eq!(c.data, [7, 10, 15, 22]);
+        assert_eq!(c, Matrix::new([7, 10, 15, 22], 2, 2));
         assert_eq!(format!("{c:?}"), "Matrix(row=2, col=2, {7 10, 15 22})");
 
         let a = Matrix::new([1, 2, 3, 4], 2, 2);
         let b = Matrix::new([1, 2, 3, 4, 5, 6], 2, 3);
         let c = multiply(&a, &b).unwrap();
-        assert_eq!(c.row, 2);
-        assert_eq!(c.col, 3);
-        assert_eq!(c.data, [9, 12, 15, 19, 26, 33]);
+        assert_eq!(c, Matrix::new([9, 12, 15, 19, 26, 33], 2, 3));
         assert_eq!(format!("{c}"), "{9 12 15, 19 26 33}");
     }
 
+    #[test]
+    fn test_matrix_mul_vec() {
+        let a = Matrix::new([1, 2, 3, 4, 5, 6], 3, 2);
+        let v = a.mul_vec(&Vector::new([1, -1])).unwrap();
+        assert_eq!(v.as_slice(), [-1, -1, -1]);
+
+        let a = Matrix::new((0..40).collect::<Vec<i64>>(), 10, 4);
+        let v = a.mul_vec(&Vector::new([1, 0, 0, 1])).unwrap();
+        assert_eq!(v.as_slice(), (0..10).map(|i| 8 * i + 3).collect::<Vec<_>>());
+
+        // big enough for the worker threads
+        let a = Matrix::from_fn(300, 64, |i, j| (i + j) as i64);
+        let v = a.mul_vec(&Vector::new(vec![1; 64])).unwrap();
+        assert_eq!(
+            v.as_slice(),
+            (0..300).map(|i| 64 * i + 2016).collect::<Vec<_>>()
+        );
+
+        assert_eq!(
+            a.mul_vec(&Vector::new([1, 2])).unwrap_err(),
+            MatrixError::Vector(VectorError::LengthMismatch { left: 64, right: 2 })
+        );
+        let empty = Matrix::new(Vec::<i64>::new(), 0, 2);
+        assert!(empty.mul_vec(&Vector::new([1, 2])).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_matrix_pow() {
+        // Fibonacci numbers
+        let fib = Matrix::new([1u64, 1, 1, 0], 2, 2);
+        assert_eq!(fib.pow(0).unwrap(), Matrix::identity(2));
+        assert_eq!(fib.pow(1).unwrap(), fib);
+        assert_eq!(fib.pow(10).unwrap().as_slice(), [89, 55, 55, 34]);
+        assert_eq!(fib.pow(90).unwrap()[(0, 1)], 2_880_067_194_370_816_120);
+
+        let a = Matrix::from_fn(5, 5, |i, j| ((i + j) % 3) as i64 - 1);
+        let mut expected = Matrix::identity(5);
+        for _ in 0..7 {
+            expected = multiply(&expected, &a).unwrap();
+        }
+        assert_eq!(a.pow(7).unwrap(), expected);
+        assert_eq!(
+            Matrix::<i64>::zeros(2, 3).pow(2).unwrap_err(),
+            MatrixError::NotSquare { row: 2, col: 3 }
+        );
+    }
+
+    #[test]
+    fn test_columns_transposes_once() {
+        let b = Matrix::new([1, 2, 3, 4, 5, 6], 2, 3);
+        assert_eq!(columns(&b), [1, 4, 2, 5, 3, 6]);
+        assert!(columns(&Matrix::new(Vec::<i64>::new(), 2, 0)).is_empty());
+    }
+
+    #[test]
+    fn test_matrix_multiply_with_config() {
+        let a = Matrix::new([1, 2, 3, 4, 5, 6], 2, 3);
+        let b = Matrix::new([1, 2, 3, 4, 5, 6], 3, 2);
+        for threads in [1, 3, 8] {
+            for granularity in [
+                Granularity::Cell,
+                Granularity::Rows(0),
+                Granularity::Rows(3),
+                Granularity::Tiles(0),
+                Granularity::Tiles(2),
+            ] {
+                let config = MultiplyConfig {
+                    threads,
+                    granularity,
+                    serial_threshold: 0,
+                };
+                let c = multiply_with_config(&a, &b, &config).unwrap();
+                assert_eq!(c.as_slice(), [22, 28, 49, 64]);
+            }
+        }
+
+        let config = MultiplyConfig {
+            threads: 0,
+            ..Default::default()
+        };
+        let c = multiply_with_config(&a, &b, &config);
+        assert_eq!(c.unwrap_err(), MatrixError::NoWorkers);
+    }
+
+    #[test]
+    fn test_matrix_multiply_tiles_uneven() {
+        // neither dimension a multiple of the tile side, nor of each other
+        let a = Matrix::from_fn(13, 17, |i, j| (i * 17 + j) as i64 % 11 - 5);
+        let b = Matrix::from_fn(17, 9, |i, j| (i * 9 + j) as i64 % 7 - 3);
+        let expected = multiply_with_config(
+            &a,
+            &b,
+            &MultiplyConfig {
+                granularity: Granularity::Rows(1),
+                serial_threshold: 0,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        for side in [1, 4, 5, 64] {
+            let config = MultiplyConfig {
+                threads: 3,
+                granularity: Granularity::Tiles(side),
+                serial_threshold: 0,
+            };
+            assert_eq!(multiply_with_config(&a, &b, &config).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_matrix_multiply_with_progress() {
+        let a = Matrix::from_fn(30, 7, |i, j| (i + j) as i64);
+        let b = Matrix::from_fn(7, 20, |i, j| (i * j) as i64 % 5);
+        let expected = multiply(&a, &b).unwrap();
+        for granularity in [
+            Granularity::Cell,
+            Granularity::Rows(4),
+            Granularity::Tiles(8),
+        ] {
+            for serial_threshold in [0, usize::MAX] {
+                let config = MultiplyConfig {
+                    granularity,
+                    serial_threshold,
+                    ..Default::default()
+                };
+                let mut calls = Vec::new();
+                let c = multiply_with_progress(&a, &b, &config, |done, total| {
+                    calls.push((done, total))
+                })
+                .unwrap();
+                assert_eq!(c, expected);
+                // throttled to about 100 calls, increasing and ending at the total
+                assert!(calls.len() <= 101, "{} calls", calls.len());
+                assert!(calls.windows(2).all(|w| w[0].0 < w[1].0));
+                assert_eq!(calls.last(), Some(&(600, 600)));
+
+                // an empty product never reports
+                let empty = Matrix::new(Vec::<i64>::new(), 7, 0);
+                let mut called = false;
+                let c = multiply_with_progress(&a, &empty, &config, |_, _| called = true).unwrap();
+                assert_eq!(c.as_slice(), &[] as &[i64]);
+                assert!(!called);
+            }
+        }
+    }
+
+    #[test]
+    fn test_matrix_multiply_into() {
+        let mut out = Matrix::zeros(3, 4);
+        let ptr = out.as_slice().as_ptr();
+        for n in [1i64, 2, 3] {
+            let a = Matrix::from_fn(3, 5, |i, j| (i + j) as i64 * n);
+            let b = Matrix::from_fn(5, 4, |i, j| (i * j) as i64 - n);
+            multiply_into(&a, &b, &mut out).unwrap();
+            assert_eq!(out, multiply(&a, &b).unwrap());
+        }
+        assert_eq!(out.as_slice().as_ptr(), ptr);
+
+        // big enough for the worker threads
+        let a = Matrix::from_fn(40, 40, |i, j| (i * 3 + j) as i64 % 13);
+        let mut out = Matrix::zeros(40, 40);
+        multiply_into(&a, &a, &mut out).unwrap();
+        assert_eq!(out, multiply(&a, &a).unwrap());
+
+        let err = multiply_into(&a, &a, &mut Matrix::zeros(40, 39)).unwrap_err();
+        assert_eq!(
+            err,
+            MatrixError::ShapeMismatch {
+                left: (40, 40),
+                right: (40, 39)
+            }
+        );
+        let err = multiply_into(&a, &Matrix::zeros(3, 3), &mut out).unwrap_err();
+        assert!(matches!(err, MatrixError::DimensionMismatch { .. }));
+    }
+
+    #[test]
+    fn test_matrix_multiply_transposed() {
+        for (m, k, n) in [(3, 4, 5), (40, 30, 50)] {
+            let a = Matrix::from_fn(m, k, |i, j| (i * k + j) as i64 % 7 - 3);
+            let b = Matrix::from_fn(n, k, |i, j| (i + 2 * j) as i64 % 5);
+            let expected = multiply(&a, &b.transpose()).unwrap();
+            assert_eq!(multiply_transposed(&a, &b).unwrap(), expected);
+        }
+
+        // an empty shared dimension gives a matrix of zeros
+        let a = Matrix::<i64>::zeros(2, 0);
+        let b = Matrix::<i64>::zeros(3, 0);
+        assert_eq!(multiply_transposed(&a, &b).unwrap(), Matrix::zeros(2, 3));
+
+        let err = multiply_transposed(&Matrix::<i64>::zeros(2, 3), &Matrix::zeros(3, 2));
+        assert_eq!(
+            err.unwrap_err(),
+            MatrixError::DimensionMismatch {
+                left: (2, 3),
+                right: (2, 3)
+            }
+        );
+    }
+
+    #[test]
+    fn test_matrix_multiply_serial_threshold() {
+        let a = Matrix::from_fn(6, 5, |i, j| (i * 5 + j) as i64);
+        let b = Matrix::from_fn(5, 4, |i, j| (i as i64) - (j as i64));
+        let serial = multiply_with_config(&a, &b, &MultiplyConfig::default()).unwrap();
+        for serial_threshold in [0, 6 * 5 * 4, 6 * 5 * 4 + 1] {
+            let config = MultiplyConfig {
+                serial_threshold,
+                ..Default::default()
+            };
+            assert_eq!(multiply_with_config(&a, &b, &config).unwrap(), serial);
+        }
+
+        // the worker count is still checked on the serial path
+        let config = MultiplyConfig {
+            threads: 0,
+            serial_threshold: usize::MAX,
+            ..Default::default()
+        };
+        let c = multiply_with_config(&a, &b, &config);
+        assert_eq!(c.unwrap_err(), MatrixError::NoWorkers);
+    }
+
+    // i8 arithmetic panics on overflow in debug builds, rayon passes the panic on
+    #[cfg(all(feature = "threads", not(feature = "rayon"), debug_assertions))]
+    #[test]
+    fn test_matrix_multiply_worker_panic() {
+        // only cell (2, 1) overflows
+        let a = Matrix::new([1i8, 1, 1, 1, 50, 10, 1, 1, 1, 1], 5, 2);
+        let b = Matrix::new([1i8, 3, 1, 1], 2, 2);
+        let failed = MatrixError::WorkerPanicked { row: 2, col: 1 };
+        for granularity in [
+            Granularity::Cell,
+            Granularity::Rows(1),
+            Granularity::Rows(2),
+            Granularity::Tiles(2),
+        ] {
+            let config = MultiplyConfig {
+                granularity,
+                serial_threshold: 0,
+                ..Default::default()
+            };
+            let c = multiply_with_config(&a, &b, &config);
+            assert_eq!(c.unwrap_err(), failed);
+        }
+
+        let pool = ThreadPool::new(2);
+        assert_eq!(multiply_with(&pool, &a, &b).unwrap_err(), failed);
+        // the workers survived the panic
+        let ok = Matrix::new([1i8, 2, 3, 4], 2, 2);
+        assert_eq!(
+            multiply_with(&pool, &ok, &ok).unwrap().as_slice(),
+            [7, 10, 15, 22]
+        );
+    }
+
+    #[test]
+    fn test_matrix_multiply_widening() {
+        // every element of the product is 8 * 127 * 127, far past i8::MAX
+        let a = Matrix::new([i8::MAX; 40], 5, 8);
+        let b = Matrix::new([i8::MAX; 24], 8, 3);
+        let c: Matrix<i64> = multiply_widening(&a, &b).unwrap();
+        assert_eq!((c.row, c.col), (5, 3));
+        assert_eq!(c.as_slice(), [8 * 127 * 127; 15]);
+
+        let a = Matrix::new([1i32, 2, 3, 4, 5, 6], 2, 3);
+        let b = Matrix::new([1i32, 2, 3, 4, 5, 6], 3, 2);
+        let c: Matrix<i64> = multiply_widening(&a, &b).unwrap();
+        assert_eq!(c.as_slice(), [22, 28, 49, 64]);
+
+        // big enough for the worker threads
+        let a = Matrix::new(vec![i8::MAX; 40 * 40], 40, 40);
+        let c: Matrix<i64> = multiply_widening(&a, &a).unwrap();
+        assert_eq!(c.as_slice(), [40 * 127 * 127; 1600]);
+
+        let c = multiply_widening::<i32, i64>(&b, &b);
+        assert!(matches!(c, Err(MatrixError::DimensionMismatch { .. })));
+        let empty = Matrix::new(Vec::<i32>::new(), 0, 3);
+        assert_eq!(multiply_widening::<i32, i64>(&empty, &b).unwrap().row, 0);
+    }
+
+    #[cfg(feature = "threads")]
+    #[test]
+    fn test_matrix_multiply_with_priority() {
+        let a = Matrix::new((1..=12).collect::<Vec<i64>>(), 4, 3);
+        let b = Matrix::new((1..=6).collect::<Vec<i64>>(), 3, 2);
+        let expected = multiply(&a, &b).unwrap();
+        for priority in [Priority::Low, Priority::Normal, Priority::High] {
+            let c = multiply_with_priority(&a, &b, priority).unwrap();
+            assert_eq!(c.as_slice(), expected.as_slice());
+        }
+    }
+
+    #[cfg(feature = "threads")]
+    #[test]
+    fn test_matrix_multiply_with_timeout() {
+        let a = Matrix::new((1..=12).collect::<Vec<i64>>(), 4, 3);
+        let b = Matrix::new((1..=6).collect::<Vec<i64>>(), 3, 2);
+        let c = multiply_with_timeout(&a, &b, Duration::from_secs(10)).unwrap();
+        assert_eq!(c, multiply(&a, &b).unwrap());
+
+        // the only worker is busy until the product has given up
+        let pool = ThreadPool::new(1);
+        let (started_tx, started) = mpsc::channel();
+        let (release, wait) = mpsc::channel::<()>();
+        pool.execute(move || {
+            started_tx.send(()).unwrap();
+            let _ = wait.recv();
+        });
+        started.recv().unwrap();
+        let deadline = Instant::now() + Duration::from_millis(50);
+        let c = multiply_on(&pool, &a, &b, Priority::Normal, Some(deadline));
+        assert_eq!(
+            c.unwrap_err(),
+            MatrixError::TimedOut {
+                first_row: 0,
+                missing_rows: 4
+            }
+        );
+        release.send(()).unwrap();
+    }
+
+    #[cfg(feature = "threads")]
+    #[test]
+    fn test_matrix_multiply_cancellable() {
+        let a = Matrix::new((1..=12).collect::<Vec<i64>>(), 4, 3);
+        let b = Matrix::new((1..=6).collect::<Vec<i64>>(), 3, 2);
+        let (handle, _) = multiply_cancellable(&a, &b).unwrap();
+        assert_eq!(handle.join().unwrap(), multiply(&a, &b).unwrap());
+
+        let (handle, cancel) = multiply_cancellable(&a, &b).unwrap();
+        cancel.cancel();
+        assert_eq!(handle.join().unwrap_err(), MatrixError::Cancelled);
+
+        // cancelled while the rows are still queued behind a busy worker
+        let pool = ThreadPool::new(1);
+        let (started_tx, started) = mpsc::channel();
+        let (release, wait) = mpsc::channel::<()>();
+        pool.execute(move || {
+            started_tx.send(()).unwrap();
+            let _ = wait.recv();
+        });
+        started.recv().unwrap();
+        let cancel = CancelToken::default();
+        let rx = queue_rows(
+            &pool,
+            &a.as_row_major(),
+            &b.as_row_major(),
+            Priority::Normal,
+            Some(&cancel),
+        );
+        cancel.cancel();
+        release.send(()).unwrap();
+        let c = collect_rows(rx, a.row, b.col, None);
+        assert_eq!(c.unwrap_err(), MatrixError::Cancelled);
+    }
+
+    #[cfg(feature = "threads")]
+    #[test]
+    fn test_matrix_multiply_with_pool() {
+        let pool = ThreadPool::new(2);
+        let a = Matrix::new((1..=12).collect::<Vec<i64>>(), 4, 3);
+        let b = Matrix::new((1..=6).collect::<Vec<i64>>(), 3, 2);
+        let expected = multiply(&a, &b).unwrap();
+        for _ in 0..10 {
+            assert_eq!(multiply_with(&pool, &a, &b).unwrap().data, expected.data);
+        }
+
+        let c = multiply_with(&pool, &b, &b);
+        assert!(matches!(c, Err(MatrixError::DimensionMismatch { .. })));
+        assert_eq!(pool.drain().completed, 40);
+    }
+
+    #[cfg(feature = "threads")]
+    #[test]
+    fn test_matrix_multiply_stream() {
+        let a = Matrix::new((1..=12).collect::<Vec<i64>>(), 4, 3);
+        let b = Matrix::new((1..=6).collect::<Vec<i64>>(), 3, 2);
+        let expected = multiply(&a, &b).unwrap();
+
+        let mut stream = multiply_stream(&a, &b).unwrap();
+        let mut data = vec![0; 8];
+        for (i, j, value) in stream.by_ref() {
+            data[i * 2 + j] = value;
+        }
+        assert_eq!(stream.remaining(), 0);
+        assert_eq!(stream.error(), None);
+        assert_eq!(data, expected.as_slice());
+
+        assert!(matches!(
+            multiply_stream(&b, &b),
+            Err(MatrixError::DimensionMismatch { .. })
+        ));
+
+        let mut data = vec![0; 8];
+        for (idx, value) in multiply_iter(&a, &b).unwrap() {
+            data[idx] = value;
+        }
+        assert_eq!(data, expected.as_slice());
+        assert!(multiply_iter(&b, &b).is_err());
+    }
+
+    #[cfg(all(feature = "threads", debug_assertions))]
+    #[test]
+    fn test_matrix_multiply_stream_ends_on_panic() {
+        // only row 0 overflows, MAX + MAX
+        let a = Matrix::new([i32::MAX, i32::MAX, 1, 1], 2, 2);
+        let b = Matrix::new([1, 1], 2, 1);
+        let mut stream = multiply_stream(&a, &b).unwrap();
+        let cells = stream.by_ref().collect::<Vec<_>>();
+        assert!(cells.iter().all(|&cell| cell == (1, 0, 2)), "{cells:?}");
+        assert!(stream.remaining() > 0);
+        assert_eq!(
+            stream.error(),
+            Some(&MatrixError::WorkerPanicked { row: 0, col: 0 })
+        );
+        assert_eq!(stream.next(), None);
+    }
+
+    #[test]
+    fn test_multiply_context_reuses_buffers() {
+        let a = Matrix::new((1..=15).collect::<Vec<i64>>(), 5, 3);
+        let b = Matrix::new((1..=12).collect::<Vec<i64>>(), 3, 4);
+        let expected = multiply(&a, &b).unwrap();
+
+        let mut ctx = MultiplyContext::new(&MultiplyConfig {
+            threads: 2,
+            serial_threshold: 0,
+            ..Default::default()
+        });
+        let c = ctx.multiply(&a, &b).unwrap();
+        assert_eq!((c.row, c.col), (5, 4));
+        assert_eq!(c.data, expected.data);
+
+        let buffers = |ctx: &MultiplyContext<i64>| {
+            ctx.scratch
+                .iter()
+                .map(|s| (s.col.as_ptr(), s.out.as_ptr()))
+                .collect::<Vec<_>>()
+        };
+        let before = buffers(&ctx);
+        let c = ctx.multiply(&a, &b).unwrap();
+        assert_eq!(c.data, expected.data);
+        assert_eq!(buffers(&ctx), before);
+
+        // fewer rows than workers, and an empty product
+        let row = Matrix::new([1, 2, 3], 1, 3);
+        assert_eq!(ctx.multiply(&row, &b).unwrap().data, [38, 44, 50, 56]);
+        let empty = Matrix::new(Vec::<i64>::new(), 0, 3);
+        assert_eq!(ctx.multiply(&empty, &b).unwrap().row, 0);
+
+        let mut ctx = MultiplyContext::<i64>::new(&MultiplyConfig {
+            threads: 0,
+            ..Default::default()
+        });
+        assert_eq!(ctx.multiply(&a, &b).unwrap_err(), MatrixError::NoWorkers);
+
+        // below the serial threshold every block is multiplied on this thread
+        let mut ctx = MultiplyContext::new(&MultiplyConfig {
+            threads: 2,
+            ..Default::default()
+        });
+        assert_eq!(ctx.multiply(&a, &b).unwrap().data, expected.data);
+    }
+
+    // the workers are joined, a panicking one fails the product instead of the scope
+    #[cfg(all(feature = "threads", debug_assertions))]
+    #[test]
+    fn test_multiply_context_joins_panicked_worker() {
+        // both blocks overflow, the first one is reported at its cell
+        let a = Matrix::new([i32::MAX, 1, 1, 1], 2, 2);
+        let mut ctx = MultiplyContext::new(&MultiplyConfig {
+            threads: 2,
+            serial_threshold: 0,
+            ..Default::default()
+        });
+        assert_eq!(
+            ctx.multiply(&a, &a).unwrap_err(),
+            MatrixError::WorkerPanicked { row: 0, col: 0 }
+        );
+        // the context is still usable after a worker panicked
+        let b = Matrix::new([1, 2, 3, 4], 2, 2);
+        assert_eq!(ctx.multiply(&b, &b).unwrap().as_slice(), [7, 10, 15, 22]);
+    }
+
+    #[test]
+    fn test_matrix_convolve() {
+        let a = Matrix::new((1..=16).collect::<Vec<i64>>(), 4, 4);
+        // flipped to [[0, 1], [-1, 0]] when slid over `a`
+        let k = Matrix::new([0, -1, 1, 0], 2, 2);
+        let c = a.convolve(&k, Padding::Valid).unwrap();
+        assert_eq!((c.row, c.col), (3, 3));
+        assert_eq!(c.as_slice(), [-3; 9]);
+
+        let box3 = Matrix::new([1; 9], 3, 3);
+        let c = a.convolve(&box3, Padding::Same).unwrap();
+        assert_eq!((c.row, c.col), (4, 4));
+        assert_eq!(c.as_slice()[..4], [14, 24, 30, 22]);
+        assert_eq!(c.as_slice()[5], 54);
+
+        // kernel larger than the matrix
+        let c = box3
+            .convolve(&Matrix::new([1; 16], 4, 4), Padding::Valid)
+            .unwrap();
+        assert_eq!((c.row, c.col), (0, 0));
+        let empty = Matrix::new(Vec::<i64>::new(), 0, 3);
+        assert_eq!(
+            a.convolve(&empty, Padding::Same).unwrap_err(),
+            MatrixError::EmptyKernel
+        );
+    }
+
+    #[test]
+    fn test_matrix_convolve_spans_tiles() {
+        // more than one tile each way, with ragged edges
+        let (m, n) = (CONV_TILE * 2 + 5, CONV_TILE + 3);
+        let a = Matrix::new((0..m * n).map(|v| v as i64 % 7).collect::<Vec<_>>(), m, n);
+        let k = Matrix::new([1, 2, 3, 4, 5, 6], 2, 3);
+        let c = a.convolve(&k, Padding::Valid).unwrap();
+        assert_eq!((c.row, c.col), (m - 1, n - 2));
+        for y in 0..c.row {
+            for x in 0..c.col {
+                let mut sum = 0;
+                for p in 0..2 {
+                    for q in 0..3 {
+                        sum += k.data[(1 - p) * 3 + (2 - q)] * a.data[(y + p) * n + x + q];
+                    }
+                }
+                assert_eq!(c.data[y * c.col + x], sum, "at ({y}, {x})");
+            }
+        }
+    }
+
+    #[test]
+    fn test_matrix_multiply_exact() {
+        use num_bigint::BigInt;
+        use num_rational::{BigRational, Ratio};
+
+        // 2^64 overflows every primitive accumulator
+        let big = |v: i64| BigInt::from(v) << 32;
+        let a = Matrix::new([big(1), big(2), big(3), big(4)], 2, 2);
+        let b = Matrix::new([big(1), big(0), big(0), big(1)], 2, 2);
+        let expected = [big(1) << 32, big(2) << 32, big(3) << 32, big(4) << 32];
+        assert_eq!(multiply(&a, &b).unwrap().as_slice(), expected);
+        #[cfg(feature = "threads")]
+        assert_eq!(
+            multiply_with_priority(&a, &b, Priority::Normal)
+                .unwrap()
+                .as_slice(),
+            expected
+        );
+
+        let third = |v: i64| Ratio::new(BigInt::from(v), BigInt::from(3));
+        let a: Matrix<BigRational> = Matrix::new([third(1), third(2)], 1, 2);
+        let b = Matrix::new([third(3), third(6)], 2, 1);
+        let c = MultiplyContext::new(&MultiplyConfig::default())
+            .multiply(&a, &b)
+            .unwrap();
+        assert_eq!(
+            c.as_slice(),
+            [Ratio::from_integer(BigInt::from(5)) / BigInt::from(3)]
+        );
+    }
+
+    #[cfg(feature = "complex")]
+    #[test]
+    fn test_complex_multiply_and_hermitian() {
+        use num_complex::Complex;
+
+        let a = Matrix::new(
+            [
+                Complex::new(1.0, 1.0),
+                Complex::new(0.0, 2.0),
+                Complex::new(3.0, 0.0),
+                Complex::new(1.0, -1.0),
+            ],
+            2,
+            2,
+        );
+        let h = a.hermitian();
+        assert_eq!(
+            h.as_slice(),
+            [
+                Complex::new(1.0, -1.0),
+                Complex::new(3.0, 0.0),
+                Complex::new(0.0, -2.0),
+                Complex::new(1.0, 1.0),
+            ]
+        );
+
+        // A * A^H is hermitian itself, with a real diagonal
+        let c = multiply(&a, &h).unwrap();
+        assert_eq!(
+            c.as_slice(),
+            [
+                Complex::new(6.0, 0.0),
+                Complex::new(1.0, 5.0),
+                Complex::new(1.0, -5.0),
+                Complex::new(11.0, 0.0),
+            ]
+        );
+    }
+
     #[test]
     fn test_a_can_not_multiply_b() {
         let a = Matrix::new([1, 2, 3, 4, 5, 6], 2, 3);
         let b = Matrix::new([1, 2, 3, 4], 2, 2);
         let c = multiply(&a, &b);
-        assert!(c.is_err())
+        assert_eq!(
+            c.unwrap_err(),
+            MatrixError::DimensionMismatch {
+                left: (2, 3),
+                right: (2, 2)
+            }
+        );
+    }
+
+    #[test]
+    fn test_matrix_mul_operators() {
+        let a = Matrix::new([1, 2, 3, 4], 2, 2);
+        let b = Matrix::new([1, 2, 3, 4, 5, 6], 2, 3);
+        let c = &a * &a;
+        assert_eq!(c.data, [7, 10, 15, 22]);
+        assert_eq!((&a).try_mul(&b).unwrap().data, [9, 12, 15, 19, 26, 33]);
+        // the operands are still around after borrowing
+        assert_eq!(a.try_mul(b).unwrap().col, 3);
+
+        let b = Matrix::new([1, 2, 3, 4, 5, 6], 2, 3);
+        assert!(matches!(
+            (&b).try_mul(&b),
+            Err(MatrixError::DimensionMismatch { .. })
+        ));
     }
 
     #[test]
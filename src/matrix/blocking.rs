@@ -0,0 +1,52 @@
+//! Products for async callers, run on tokio's blocking threads so the runtime's
+//! workers stay free.
+
+use core::fmt::Display;
+use core::future::Future;
+use core::ops::{Add, AddAssign, Mul};
+
+use super::{multiply, Matrix, MatrixError};
+
+/// `a * b` as a future for async services, `multiply` run under
+/// `tokio::task::spawn_blocking` on copies of `a` and `b`.
+///
+/// Must be awaited inside a tokio runtime. A panic in the product fails it with
+/// `WorkerFailed`.
+pub fn multiply_async<T>(
+    a: &Matrix<T>,
+    b: &Matrix<T>,
+) -> impl Future<Output = Result<Matrix<T>, MatrixError>> + Send + 'static
+where
+    T: Display
+        + Mul<Output = T>
+        + Add<Output = T>
+        + AddAssign
+        + Default
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    let (a, b) = (a.clone(), b.clone());
+    async move {
+        tokio::task::spawn_blocking(move || multiply(&a, &b))
+            .await
+            .map_err(|_| MatrixError::WorkerFailed)?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_multiply_async() {
+        let a = Matrix::from_fn(40, 30, |i, j| (i * 30 + j) as i64 % 9);
+        let b = Matrix::from_fn(30, 20, |i, j| (i + j) as i64 % 4);
+        let c = multiply_async(&a, &b).await.unwrap();
+        assert_eq!(c, multiply(&a, &b).unwrap());
+
+        let err = multiply_async(&a, &a).await.unwrap_err();
+        assert!(matches!(err, MatrixError::DimensionMismatch { .. }));
+    }
+}
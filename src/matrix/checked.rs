@@ -0,0 +1,103 @@
+//! Products of integer matrices that report overflow instead of wrapping.
+
+use alloc::vec;
+
+use super::{
+    columns, row_major_operands, try_par_row_blocks, Matrix, MatrixError, SERIAL_THRESHOLD,
+};
+
+/// Integers with overflow-checked arithmetic, implemented for every primitive
+/// integer type.
+pub trait CheckedInt: Copy + Default + Send + Sync {
+    fn checked_add(self, rhs: Self) -> Option<Self>;
+    fn checked_mul(self, rhs: Self) -> Option<Self>;
+}
+
+macro_rules! impl_checked_int {
+    ($($t:ty),*) => {$(
+        impl CheckedInt for $t {
+            fn checked_add(self, rhs: Self) -> Option<Self> {
+                <$t>::checked_add(self, rhs)
+            }
+
+            fn checked_mul(self, rhs: Self) -> Option<Self> {
+                <$t>::checked_mul(self, rhs)
+            }
+        }
+    )*};
+}
+
+impl_checked_int!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+/// `a * b` with every multiply and add checked, failing with `Overflow` at the first
+/// cell in row-major order that does not fit in `T`, in debug and release builds
+/// alike.
+///
+/// Rows are split into one block per thread of the default `MultiplyConfig`, or
+/// all computed on this thread below its `serial_threshold`.
+pub fn multiply_checked<T: CheckedInt>(
+    a: &Matrix<T>,
+    b: &Matrix<T>,
+) -> Result<Matrix<T>, MatrixError> {
+    let (a, b) = &row_major_operands(a, b)?;
+    let (k, n) = (a.col, b.col);
+    let mut data = vec![T::default(); a.row * n];
+    if data.is_empty() {
+        return Ok(Matrix::new(data, a.row, n));
+    }
+
+    let columns = columns(b);
+    let work = a.row.saturating_mul(k).saturating_mul(n);
+    try_par_row_blocks(&mut data, n, work, SERIAL_THRESHOLD, |i, out| {
+        let row = &a.data[i * k..(i + 1) * k];
+        for (j, cell) in out.iter_mut().enumerate() {
+            let col = &columns[j * k..(j + 1) * k];
+            *cell = row
+                .iter()
+                .zip(col)
+                .try_fold(T::default(), |sum, (&x, &y)| {
+                    x.checked_mul(y).and_then(|p| sum.checked_add(p))
+                })
+                .ok_or(MatrixError::Overflow { row: i, col: j })?;
+        }
+        Ok(())
+    })?;
+    Ok(Matrix::new(data, a.row, n))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::multiply;
+
+    #[test]
+    fn test_multiply_checked() {
+        let a = Matrix::new([1i8, 2, 3, 4], 2, 2);
+        assert_eq!(multiply_checked(&a, &a).unwrap(), multiply(&a, &a).unwrap());
+
+        // 8 * 8 + 8 * 8 = 128 overflows only in cell (1, 1)
+        let a = Matrix::new([1i8, 1, 8, 8], 2, 2);
+        let b = Matrix::new([1i8, 8, 1, 8], 2, 2);
+        assert_eq!(
+            multiply_checked(&a, &b).unwrap_err(),
+            MatrixError::Overflow { row: 1, col: 1 }
+        );
+        let c = multiply_checked(&Matrix::new([200u8], 1, 1), &Matrix::new([2u8], 1, 1));
+        assert_eq!(c.unwrap_err(), MatrixError::Overflow { row: 0, col: 0 });
+    }
+
+    #[test]
+    fn test_multiply_checked_on_threads() {
+        // the first overflow in row-major order wins, across blocks
+        let n = 40;
+        let mut a = Matrix::from_fn(n, n, |i, j| ((i + j) % 5) as i32);
+        let b = Matrix::from_fn(n, n, |i, j| ((i * j) % 3) as i32);
+        assert_eq!(multiply_checked(&a, &b).unwrap(), multiply(&a, &b).unwrap());
+
+        // column 0 of `b` is all zeros, so only column 1 onwards overflows
+        a[(33, 4)] = i32::MAX;
+        a[(25, 4)] = i32::MAX;
+        let err = multiply_checked(&a, &b).unwrap_err();
+        assert_eq!(err, MatrixError::Overflow { row: 25, col: 1 });
+    }
+}
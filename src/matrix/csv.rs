@@ -0,0 +1,121 @@
+//! Reading and writing matrices as CSV, one matrix row per record and no header.
+
+use alloc::{string::String, vec::Vec};
+use core::{fmt::Display, str::FromStr};
+use std::io;
+use thiserror::Error;
+
+use super::{Matrix, MatrixError};
+
+/// Why `Matrix::from_csv_reader` or `Matrix::to_csv_writer` failed.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum CsvError {
+    /// reading or writing failed, or the input is not valid CSV
+    #[error("CSV error: {0}")]
+    Csv(#[from] ::csv::Error),
+    /// the field at `line` and `column`, both counting from 1, is not a `T`
+    #[error("CSV error: invalid number {value:?} at line {line}, column {column}")]
+    Parse {
+        line: u64,
+        column: usize,
+        value: String,
+    },
+    /// records of different lengths
+    #[error("CSV error: {0}")]
+    Matrix(#[from] MatrixError),
+}
+
+impl<T> Matrix<T> {
+    /// A matrix with one row per CSV record of `reader`, every field parsed as a `T`
+    /// after trimming whitespace. Empty lines are skipped.
+    ///
+    /// Fails with `Parse` at the first field that does not parse and with
+    /// `RaggedRows` unless all records have as many fields as the first.
+    pub fn from_csv_reader(reader: impl io::Read) -> Result<Self, CsvError>
+    where
+        T: FromStr,
+    {
+        let mut reader = ::csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .trim(::csv::Trim::All)
+            .from_reader(reader);
+        let mut rows = Vec::new();
+        for record in reader.records() {
+            let record = record?;
+            let line = record.position().map_or(0, ::csv::Position::line);
+            let row = record
+                .iter()
+                .enumerate()
+                .map(|(j, field)| {
+                    field.parse().map_err(|_| CsvError::Parse {
+                        line,
+                        column: j + 1,
+                        value: field.into(),
+                    })
+                })
+                .collect::<Result<Vec<T>, _>>()?;
+            rows.push(row);
+        }
+        Ok(Self::try_from_rows(rows)?)
+    }
+
+    /// Writes one CSV record per row, every element formatted with `Display`.
+    pub fn to_csv_writer(&self, writer: impl io::Write) -> Result<(), CsvError>
+    where
+        T: Display,
+    {
+        let mut writer = ::csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(writer);
+        for row in self.rows() {
+            writer.write_record(row.map(|x| x.to_string()))?;
+        }
+        writer.flush().map_err(::csv::Error::from)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matrix_csv_round_trip() {
+        let a = Matrix::<f64>::from_csv_reader("1, 2.5,3\n\n-4,5,6e1\n".as_bytes()).unwrap();
+        assert_eq!(a, Matrix::new([1.0, 2.5, 3.0, -4.0, 5.0, 60.0], 2, 3));
+
+        let mut out = Vec::new();
+        Matrix::from_col_major([1, 4, 2, 5], 2, 2)
+            .to_csv_writer(&mut out)
+            .unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "1,2\n4,5\n");
+
+        let empty = Matrix::<i32>::from_csv_reader(&b""[..]).unwrap();
+        assert_eq!(empty.shape(), (0, 0));
+    }
+
+    #[test]
+    fn test_matrix_csv_errors() {
+        let err = Matrix::<i32>::from_csv_reader("1,2\n3,x\n".as_bytes()).unwrap_err();
+        assert!(matches!(
+            &err,
+            CsvError::Parse { line: 2, column: 2, value } if value == "x"
+        ));
+        assert_eq!(
+            err.to_string(),
+            "CSV error: invalid number \"x\" at line 2, column 2"
+        );
+
+        let err = Matrix::<i32>::from_csv_reader("1,2\n3\n".as_bytes()).unwrap_err();
+        assert!(matches!(
+            err,
+            CsvError::Matrix(MatrixError::RaggedRows {
+                row: 1,
+                len: 1,
+                expected: 2
+            })
+        ));
+    }
+}
@@ -0,0 +1,142 @@
+//! `Display` for `Matrix`: `{1 2, 3 4}` on one line, or with `{:#}` one aligned
+//! line per row, eliding the middle rows and columns of large matrices.
+
+use alloc::{format, string::String, vec::Vec};
+use core::fmt::{self, Display};
+
+use super::{Matrix, Storage};
+
+// `{:#}` shows at most this many rows (columns), larger dimensions keep only the
+// first and last `DISPLAY_EDGE` around a `...`
+const DISPLAY_MAX: usize = 10;
+const DISPLAY_EDGE: usize = 3;
+
+/// `{}` writes the rows on one line, `{1 2, 3 4}`. Width and precision apply to
+/// every element, so `{:.2}` prints floats with two decimals and `{:3}` pads each
+/// element to three characters.
+///
+/// `{:#}` writes one line per row with the columns right-aligned, e.g.
+///
+/// ```text
+/// [ 1  2]
+/// [30 40]
+/// ```
+///
+/// and replaces all but the first and last three rows (columns) by `...` when a
+/// matrix has more than ten of them.
+impl<T, S> Display for Matrix<T, S>
+where
+    T: Display,
+    S: Storage<T>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            return self.fmt_pretty(f);
+        }
+        write!(f, "{{")?;
+        for i in 0..self.row {
+            for j in 0..self.col {
+                // forwards the width and precision to the element
+                Display::fmt(&self[(i, j)], f)?;
+                if j != self.col - 1 {
+                    write!(f, " ")?;
+                }
+            }
+            if i != self.row - 1 {
+                write!(f, ", ")?;
+            }
+        }
+        write!(f, "}}")?;
+        Ok(())
+    }
+}
+
+impl<T: Display, S: Storage<T>> Matrix<T, S> {
+    fn fmt_pretty(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (rows, cols) = (shown(self.row), shown(self.col));
+        let cells = rows
+            .iter()
+            .map(|&i| {
+                cols.iter()
+                    .map(|&j| match (i, j) {
+                        (Some(i), Some(j)) => match f.precision() {
+                            Some(p) => format!("{:.*}", p, self[(i, j)]),
+                            None => format!("{}", self[(i, j)]),
+                        },
+                        _ => String::from("..."),
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let widths = (0..cols.len())
+            .map(|j| {
+                cells
+                    .iter()
+                    .map(|row| row[j].chars().count())
+                    .fold(f.width().unwrap_or(0), usize::max)
+            })
+            .collect::<Vec<_>>();
+
+        for (i, row) in cells.iter().enumerate() {
+            if i != 0 {
+                writeln!(f)?;
+            }
+            write!(f, "[")?;
+            for (j, (cell, &width)) in row.iter().zip(&widths).enumerate() {
+                if j != 0 {
+                    write!(f, " ")?;
+                }
+                write!(f, "{:>width$}", cell)?;
+            }
+            write!(f, "]")?;
+        }
+        Ok(())
+    }
+}
+
+// the indices `{:#}` shows out of `len`, `None` for the elided middle
+fn shown(len: usize) -> Vec<Option<usize>> {
+    if len <= DISPLAY_MAX {
+        return (0..len).map(Some).collect();
+    }
+    (0..DISPLAY_EDGE)
+        .map(Some)
+        .chain([None])
+        .chain((len - DISPLAY_EDGE..len).map(Some))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matrix_display_flags_and_pretty() {
+        let a = Matrix::new([1.0, 2.5, -3.25, 4.0], 2, 2);
+        assert_eq!(format!("{}", a), "{1 2.5, -3.25 4}");
+        assert_eq!(format!("{:.1}", a), "{1.0 2.5, -3.2 4.0}");
+        assert_eq!(format!("{:5}", Matrix::new([1, 20], 1, 2)), "{    1    20}");
+
+        assert_eq!(format!("{:#}", a), "[    1 2.5]\n[-3.25   4]");
+        assert_eq!(format!("{:#.2}", a), "[ 1.00 2.50]\n[-3.25 4.00]");
+        let b = Matrix::from_col_major([1, 2, 30, 40], 2, 2);
+        assert_eq!(format!("{:#}", b), "[1 30]\n[2 40]");
+        assert_eq!(format!("{:#3}", Matrix::new([1], 1, 1)), "[  1]");
+        assert_eq!(format!("{:#}", Matrix::<i32>::new([], 0, 0)), "");
+    }
+
+    #[test]
+    fn test_matrix_display_elides_large() {
+        let a = Matrix::new((0..12 * 12).collect::<Vec<_>>(), 12, 12);
+        let pretty = format!("{:#}", a);
+        let lines = pretty.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 7);
+        assert_eq!(lines[0], "[  0   1   2 ...   9  10  11]");
+        assert_eq!(lines[3], "[... ... ... ... ... ... ...]");
+        assert_eq!(lines[6], "[132 133 134 ... 141 142 143]");
+
+        // at the limit nothing is elided
+        let b = Matrix::new(vec![0; 10 * 10], 10, 10);
+        assert!(!format!("{:#}", b).contains("..."));
+    }
+}
@@ -0,0 +1,159 @@
+//! `StaticMatrix`, a matrix with its shape in its type, for the small fixed sizes
+//! of graphics and games: multiplying mismatched shapes does not compile and the
+//! loops have constant bounds the compiler unrolls.
+
+use alloc::vec::Vec;
+use core::array;
+use core::ops::{Add, Index, IndexMut, Mul};
+
+use super::{Matrix, MatrixError, Storage};
+
+/// An `R` x `C` matrix stored inline as `R` arrays of `C` elements.
+///
+/// `a * b` needs `b` to have `C` rows and gives an `R` x `K` matrix, so a shape
+/// mismatch is a compile error instead of a `DimensionMismatch`. Convert to and
+/// from `Matrix` with `From` and `TryFrom`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StaticMatrix<T, const R: usize, const C: usize> {
+    data: [[T; C]; R],
+}
+
+impl<T, const R: usize, const C: usize> StaticMatrix<T, R, C> {
+    /// Wraps `rows`, one array per row.
+    pub const fn new(rows: [[T; C]; R]) -> Self {
+        Self { data: rows }
+    }
+
+    /// The element at `(i, j)` computed by `f(i, j)`.
+    pub fn from_fn(mut f: impl FnMut(usize, usize) -> T) -> Self {
+        Self::new(array::from_fn(|i| array::from_fn(|j| f(i, j))))
+    }
+
+    pub fn rows(&self) -> &[[T; C]; R] {
+        &self.data
+    }
+
+    pub fn into_rows(self) -> [[T; C]; R] {
+        self.data
+    }
+
+    pub fn transpose(&self) -> StaticMatrix<T, C, R>
+    where
+        T: Clone,
+    {
+        StaticMatrix::from_fn(|i, j| self.data[j][i].clone())
+    }
+}
+
+impl<T, const N: usize> StaticMatrix<T, N, N>
+where
+    T: Default + From<u8>,
+{
+    /// The `N` x `N` identity, ones on the diagonal.
+    pub fn identity() -> Self {
+        Self::from_fn(|i, j| if i == j { T::from(1) } else { T::default() })
+    }
+}
+
+impl<T, const R: usize, const C: usize> Index<(usize, usize)> for StaticMatrix<T, R, C> {
+    type Output = T;
+
+    fn index(&self, (i, j): (usize, usize)) -> &T {
+        &self.data[i][j]
+    }
+}
+
+impl<T, const R: usize, const C: usize> IndexMut<(usize, usize)> for StaticMatrix<T, R, C> {
+    fn index_mut(&mut self, (i, j): (usize, usize)) -> &mut T {
+        &mut self.data[i][j]
+    }
+}
+
+impl<T, const R: usize, const C: usize, const K: usize> Mul<&StaticMatrix<T, C, K>>
+    for &StaticMatrix<T, R, C>
+where
+    T: Mul<Output = T> + Add<Output = T> + Default + Clone,
+{
+    type Output = StaticMatrix<T, R, K>;
+
+    fn mul(self, rhs: &StaticMatrix<T, C, K>) -> Self::Output {
+        // constant trip counts, unrolled completely for 2x2, 3x3 and 4x4
+        StaticMatrix::from_fn(|i, j| {
+            (0..C).fold(T::default(), |sum, k| {
+                sum + self.data[i][k].clone() * rhs.data[k][j].clone()
+            })
+        })
+    }
+}
+
+impl<T, const R: usize, const C: usize, const K: usize> Mul<StaticMatrix<T, C, K>>
+    for StaticMatrix<T, R, C>
+where
+    T: Mul<Output = T> + Add<Output = T> + Default + Clone,
+{
+    type Output = StaticMatrix<T, R, K>;
+
+    fn mul(self, rhs: StaticMatrix<T, C, K>) -> Self::Output {
+        &self * &rhs
+    }
+}
+
+impl<T, const R: usize, const C: usize> From<StaticMatrix<T, R, C>> for Matrix<T> {
+    fn from(m: StaticMatrix<T, R, C>) -> Self {
+        Matrix::new(m.data.into_iter().flatten().collect::<Vec<_>>(), R, C)
+    }
+}
+
+/// `ShapeMismatch` unless `m` is `R` x `C`.
+impl<T, S, const R: usize, const C: usize> TryFrom<&Matrix<T, S>> for StaticMatrix<T, R, C>
+where
+    T: Clone,
+    S: Storage<T>,
+{
+    type Error = MatrixError;
+
+    fn try_from(m: &Matrix<T, S>) -> Result<Self, Self::Error> {
+        if m.shape() != (R, C) {
+            return Err(MatrixError::ShapeMismatch {
+                left: m.shape(),
+                right: (R, C),
+            });
+        }
+        Ok(Self::from_fn(|i, j| m[(i, j)].clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_static_matrix_multiply() {
+        let a = StaticMatrix::new([[1, 2, 3], [4, 5, 6]]);
+        let b = StaticMatrix::new([[1, 2], [3, 4], [5, 6]]);
+        let c: StaticMatrix<i32, 2, 2> = a * b;
+        assert_eq!(c, StaticMatrix::new([[22, 28], [49, 64]]));
+        // the same product as the dynamic kernels
+        let dynamic = crate::multiply(&Matrix::from(a), &Matrix::from(b)).unwrap();
+        assert_eq!(Matrix::from(c), dynamic);
+
+        let r = StaticMatrix::<f32, 4, 4>::from_fn(|i, j| (i * 4 + j) as f32);
+        assert_eq!(r * StaticMatrix::identity(), r);
+        assert_eq!(a.transpose(), StaticMatrix::new([[1, 4], [2, 5], [3, 6]]));
+        assert_eq!(c[(1, 0)], 49);
+    }
+
+    #[test]
+    fn test_static_matrix_try_from() {
+        let m = Matrix::from_col_major([1, 3, 2, 4], 2, 2);
+        let s = StaticMatrix::<i32, 2, 2>::try_from(&m).unwrap();
+        assert_eq!(s.into_rows(), [[1, 2], [3, 4]]);
+        assert_eq!(
+            StaticMatrix::<i32, 3, 2>::try_from(&m),
+            Err(MatrixError::ShapeMismatch {
+                left: (2, 2),
+                right: (3, 2)
+            })
+        );
+    }
+}
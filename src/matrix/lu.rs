@@ -0,0 +1,169 @@
+//! LU decomposition with partial pivoting, and determinants, for float matrices.
+
+use alloc::vec::Vec;
+
+use super::{par_row_blocks, Matrix, MatrixError, PARALLEL_MIN};
+
+/// `P * A = L * U` for a square `A`, from `Matrix::lu`.
+///
+/// `L` is unit lower triangular and `U` upper triangular, both kept in one matrix
+/// with the ones of `L`'s diagonal implied.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lu<T> {
+    lu: Matrix<T>,
+    // row `i` of `P * A` is row `perm[i]` of `A`
+    perm: Vec<usize>,
+    // an odd number of row swaps flips the sign of the determinant
+    odd_swaps: bool,
+}
+
+impl<T> Lu<T> {
+    /// Row `i` of `P * A` is row `permutation()[i]` of `A`.
+    pub fn permutation(&self) -> &[usize] {
+        &self.perm
+    }
+}
+
+macro_rules! impl_lu {
+    ($($t:ty),*) => {$(
+        impl Matrix<$t> {
+            /// The LU decomposition with partial pivoting, or `NotSquare`.
+            ///
+            /// Eliminating a column updates every row below the pivot, those rows
+            /// are split into one block per thread once there are enough of them.
+            /// A singular matrix still decomposes, with a zero on `U`'s diagonal.
+            pub fn lu(&self) -> Result<Lu<$t>, MatrixError> {
+                self.check_square()?;
+                let n = self.row;
+                let mut lu = self.row_major().into_owned();
+                let mut perm = (0..n).collect::<Vec<_>>();
+                let mut odd_swaps = false;
+                for k in 0..n {
+                    let pivot = (k..n)
+                        .max_by(|&x, &y| {
+                            lu.data[x * n + k].abs().total_cmp(&lu.data[y * n + k].abs())
+                        })
+                        .unwrap_or(k);
+                    if pivot != k {
+                        for j in 0..n {
+                            lu.data.swap(k * n + j, pivot * n + j);
+                        }
+                        perm.swap(k, pivot);
+                        odd_swaps = !odd_swaps;
+                    }
+                    let (top, below) = lu.data.split_at_mut((k + 1) * n);
+                    let pivot_row = &top[k * n..];
+                    if pivot_row[k] != 0.0 {
+                        eliminate(pivot_row, below, k, n);
+                    }
+                }
+                Ok(Lu { lu, perm, odd_swaps })
+            }
+
+            /// The determinant, from the LU decomposition, or `NotSquare`.
+            pub fn det(&self) -> Result<$t, MatrixError> {
+                Ok(self.lu()?.det())
+            }
+        }
+
+        impl Lu<$t> {
+            /// The determinant of the decomposed matrix.
+            pub fn det(&self) -> $t {
+                let n = self.lu.row;
+                let det = (0..n).map(|i| self.lu.data[i * n + i]).product::<$t>();
+                if self.odd_swaps { -det } else { det }
+            }
+
+            /// The unit lower triangular factor.
+            pub fn l(&self) -> Matrix<$t> {
+                let n = self.lu.row;
+                Matrix::from_fn(n, n, |i, j| match i.cmp(&j) {
+                    core::cmp::Ordering::Greater => self.lu.data[i * n + j],
+                    core::cmp::Ordering::Equal => 1.0,
+                    core::cmp::Ordering::Less => 0.0,
+                })
+            }
+
+            /// The upper triangular factor.
+            pub fn u(&self) -> Matrix<$t> {
+                let n = self.lu.row;
+                Matrix::from_fn(n, n, |i, j| if i <= j { self.lu.data[i * n + j] } else { 0.0 })
+            }
+        }
+
+        impl Eliminate for $t {
+            fn eliminate_row(pivot_row: &[$t], row: &mut [$t], k: usize) {
+                let factor = row[k] / pivot_row[k];
+                row[k] = factor;
+                for (x, p) in row[k + 1..].iter_mut().zip(&pivot_row[k + 1..]) {
+                    *x -= factor * p;
+                }
+            }
+        }
+    )*};
+}
+
+// one step of Gaussian elimination on a row below the pivot, storing the
+// multiplier where the eliminated element was
+trait Eliminate: Sized + Send + Sync {
+    fn eliminate_row(pivot_row: &[Self], row: &mut [Self], k: usize);
+}
+
+impl_lu!(f32, f64);
+
+// eliminates column `k` from every row in `below`, one block of rows per thread
+// once there are enough elements to update
+fn eliminate<T: Eliminate>(pivot_row: &[T], below: &mut [T], k: usize, n: usize) {
+    let work = below.len();
+    par_row_blocks(below, n, work, PARALLEL_MIN, |_, row| {
+        T::eliminate_row(pivot_row, row, k)
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::multiply;
+
+    #[test]
+    fn test_matrix_lu() {
+        let a = Matrix::new([2.0f64, 1.0, 1.0, 4.0, -6.0, 0.0, -2.0, 7.0, 2.0], 3, 3);
+        let lu = a.lu().unwrap();
+        assert_eq!(lu.permutation(), [1, 2, 0]);
+        let pa = Matrix::from_fn(3, 3, |i, j| a[(lu.permutation()[i], j)]);
+        let prod = multiply(&lu.l(), &lu.u()).unwrap();
+        for (x, y) in prod.as_slice().iter().zip(pa.as_slice()) {
+            assert!((x - y).abs() < 1e-12, "{x} != {y}");
+        }
+        assert!((a.det().unwrap() - -16.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_matrix_det() {
+        assert_eq!(Matrix::<f64>::identity(4).det().unwrap(), 1.0);
+        assert_eq!(
+            Matrix::new([0.0f32, 1.0, 1.0, 0.0], 2, 2).det().unwrap(),
+            -1.0
+        );
+        assert_eq!(
+            Matrix::new([1.0f64, 2.0, 2.0, 4.0], 2, 2).det().unwrap(),
+            0.0
+        );
+        assert_eq!(Matrix::<f64>::zeros(0, 0).det().unwrap(), 1.0);
+        assert_eq!(
+            Matrix::<f64>::zeros(2, 3).det().unwrap_err(),
+            MatrixError::NotSquare { row: 2, col: 3 }
+        );
+
+        // big enough to eliminate on the worker threads, a diagonal matrix with a
+        // permutation and a lower triangle mixed in
+        let n = 200;
+        let a = Matrix::from_fn(n, n, |i, j| match i.cmp(&j) {
+            core::cmp::Ordering::Equal => 2.0f64,
+            core::cmp::Ordering::Greater => 1.0,
+            core::cmp::Ordering::Less => 0.0,
+        });
+        let det = a.det().unwrap();
+        assert!((det.log2() - n as f64).abs() < 1e-9, "{det}");
+    }
+}
@@ -0,0 +1,94 @@
+//! Read-only matrices backed by a memory-mapped file, paged in by the OS as the
+//! kernels read them instead of loaded onto the heap up front.
+
+use core::{marker::PhantomData, ops::Deref};
+use std::{fs::File, io, path::Path};
+
+use bytemuck::Pod;
+use memmap2::Mmap;
+
+use super::Matrix;
+
+/// A read-only file map holding `T`s, the storage of an `MmapMatrix`.
+#[derive(Debug)]
+pub struct MmapStorage<T> {
+    map: Mmap,
+    _elements: PhantomData<T>,
+}
+
+/// A matrix whose elements are the bytes of a mapped file, from `Matrix::open_mmap`.
+pub type MmapMatrix<T> = Matrix<T, MmapStorage<T>>;
+
+impl<T: Pod> Deref for MmapStorage<T> {
+    type Target = [T];
+    fn deref(&self) -> &[T] {
+        // length and alignment checked in `open_mmap`
+        bytemuck::cast_slice(&self.map)
+    }
+}
+
+impl<T: Pod> Matrix<T> {
+    /// Maps the file at `path`, holding exactly `row * col` native-endian `T`s in
+    /// row-major order, read-only.
+    ///
+    /// Fails with `InvalidData` if the file has another length. The file must not
+    /// be modified while the map is alive, the elements would change under the
+    /// readers.
+    pub fn open_mmap(path: impl AsRef<Path>, row: usize, col: usize) -> io::Result<MmapMatrix<T>> {
+        let file = File::open(path)?;
+        // SAFETY: mapped read-only, and the caller keeps the file unmodified while
+        // the map is alive, as documented above
+        let map = unsafe { Mmap::map(&file)? };
+        let expected = row
+            .checked_mul(col)
+            .and_then(|len| len.checked_mul(size_of::<T>()));
+        if expected != Some(map.len()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{} bytes mapped for a {row}x{col} matrix of {}-byte elements",
+                    map.len(),
+                    size_of::<T>()
+                ),
+            ));
+        }
+        // maps start on a page boundary, aligned for any `T`
+        bytemuck::try_cast_slice::<u8, T>(&map)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{e:?}")))?;
+        let storage = MmapStorage {
+            map,
+            _elements: PhantomData,
+        };
+        Ok(Matrix::from_storage(storage, row, col))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+    use crate::multiply;
+
+    #[test]
+    fn test_matrix_open_mmap() {
+        let a = Matrix::from_fn(40, 30, |i, j| (i as f64) - (j as f64) * 0.5);
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(bytemuck::cast_slice(a.as_slice())).unwrap();
+        file.flush().unwrap();
+
+        let m = Matrix::<f64>::open_mmap(file.path(), 40, 30).unwrap();
+        assert_eq!(m.shape(), (40, 30));
+        assert_eq!(m, a);
+        let b = Matrix::from_fn(30, 20, |i, j| (i * j % 7) as f64);
+        assert_eq!(multiply(&m, &b).unwrap(), multiply(&a, &b).unwrap());
+        assert_eq!(
+            m.as_view().multiply(&b.as_view()).unwrap(),
+            multiply(&a, &b).unwrap()
+        );
+
+        let err = Matrix::<f64>::open_mmap(file.path(), 40, 31).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(Matrix::<f64>::open_mmap(file.path(), usize::MAX, 2).is_err());
+    }
+}
@@ -0,0 +1,242 @@
+//! NumPy `.npy` files for `Matrix<f32>` and `Matrix<f64>`, so products can be
+//! exchanged with `numpy.load`/`numpy.save`.
+
+use alloc::{format, string::String, vec::Vec};
+use std::io::{self, Read, Write};
+use thiserror::Error;
+
+use super::{Layout, Matrix};
+
+const MAGIC: &[u8] = b"\x93NUMPY";
+// the data starts on a multiple of this, as numpy writes it
+const ALIGN: usize = 64;
+
+/// Why `read_npy` or `write_npy` failed.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum NpyError {
+    #[error("npy error: {0}")]
+    Io(#[from] io::Error),
+    /// the file does not start with the `.npy` magic string
+    #[error("npy error: not an npy file")]
+    Magic,
+    #[error("npy error: unsupported format version {0}.{1}")]
+    Version(u8, u8),
+    /// the header dictionary is missing a key or cannot be parsed
+    #[error("npy error: malformed header {0:?}")]
+    Header(String),
+    /// the array holds another element type than the matrix, e.g. `<f4` for `f64`
+    #[error("npy error: dtype {found:?}, expected {expected:?}")]
+    Dtype {
+        expected: &'static str,
+        found: String,
+    },
+    /// the array is not two-dimensional
+    #[error("npy error: shape {0:?} is not 2-dimensional")]
+    Shape(Vec<usize>),
+}
+
+macro_rules! impl_npy {
+    ($($t:ty => $descr:literal),*) => {$(
+        impl Matrix<$t> {
+            /// Reads a two-dimensional little-endian `.npy` array of this element
+            /// type. A Fortran-order array is wrapped as `ColMajor` without a copy.
+            pub fn read_npy(mut reader: impl Read) -> Result<Self, NpyError> {
+                let header = read_header(&mut reader)?;
+                let (descr, fortran_order, shape) = parse_header(&header)?;
+                if descr != $descr {
+                    return Err(NpyError::Dtype {
+                        expected: $descr,
+                        found: descr.into(),
+                    });
+                }
+                let &[row, col] = shape.as_slice() else {
+                    return Err(NpyError::Shape(shape));
+                };
+                let size = core::mem::size_of::<$t>();
+                let bytes_len = row
+                    .checked_mul(col)
+                    .and_then(|len| len.checked_mul(size))
+                    .ok_or_else(|| NpyError::Header(header.clone()))?;
+
+                let mut bytes = Vec::new();
+                reader.take(bytes_len as u64).read_to_end(&mut bytes)?;
+                if bytes.len() != bytes_len {
+                    return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+                }
+                let data = bytes
+                    .chunks_exact(size)
+                    .map(|b| <$t>::from_le_bytes(b.try_into().expect("chunks of the element size")))
+                    .collect::<Vec<_>>();
+                Ok(if fortran_order {
+                    Matrix::from_col_major(data, row, col)
+                } else {
+                    Matrix::new(data, row, col)
+                })
+            }
+
+            /// Writes a version 1.0 `.npy` array, in Fortran order for a `ColMajor`
+            /// matrix so the buffer is written as it is.
+            pub fn write_npy(&self, mut writer: impl Write) -> Result<(), NpyError> {
+                let fortran_order = if self.layout == Layout::ColMajor { "True" } else { "False" };
+                write_header(
+                    &mut writer,
+                    &format!(
+                        "{{'descr': '{}', 'fortran_order': {fortran_order}, 'shape': ({}, {}), }}",
+                        $descr, self.row, self.col
+                    ),
+                )?;
+                for x in &self.data {
+                    writer.write_all(&x.to_le_bytes())?;
+                }
+                writer.flush()?;
+                Ok(())
+            }
+        }
+    )*};
+}
+
+impl_npy!(f32 => "<f4", f64 => "<f8");
+
+// the header dictionary after the magic string, version and header length
+fn read_header(reader: &mut impl Read) -> Result<String, NpyError> {
+    let mut magic = [0; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic[..6] != MAGIC {
+        return Err(NpyError::Magic);
+    }
+    let len = match (magic[6], magic[7]) {
+        (1, 0) => {
+            let mut len = [0; 2];
+            reader.read_exact(&mut len)?;
+            u16::from_le_bytes(len) as usize
+        }
+        (2 | 3, 0) => {
+            let mut len = [0; 4];
+            reader.read_exact(&mut len)?;
+            u32::from_le_bytes(len) as usize
+        }
+        (major, minor) => return Err(NpyError::Version(major, minor)),
+    };
+    let mut header = Vec::new();
+    reader.take(len as u64).read_to_end(&mut header)?;
+    if header.len() != len {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+    String::from_utf8(header)
+        .map_err(|e| NpyError::Header(String::from_utf8_lossy(e.as_bytes()).into()))
+}
+
+// `descr`, `fortran_order` and `shape` out of a header like
+// `{'descr': '<f8', 'fortran_order': False, 'shape': (2, 3), }`
+fn parse_header(header: &str) -> Result<(&str, bool, Vec<usize>), NpyError> {
+    let malformed = || NpyError::Header(header.trim_end().into());
+    // the text after `'key':`
+    let value = |key: &str| {
+        let start = header.find(&format!("'{key}':"))? + key.len() + 3;
+        Some(header[start..].trim_start())
+    };
+
+    let descr = value("descr").ok_or_else(malformed)?;
+    let descr = descr
+        .strip_prefix('\'')
+        .and_then(|d| d.split('\'').next())
+        .ok_or_else(malformed)?;
+    let fortran_order = match value("fortran_order").ok_or_else(malformed)? {
+        v if v.starts_with("True") => true,
+        v if v.starts_with("False") => false,
+        _ => return Err(malformed()),
+    };
+    let shape = value("shape")
+        .and_then(|s| s.strip_prefix('('))
+        .and_then(|s| s.split(')').next())
+        .ok_or_else(malformed)?;
+    let shape = shape
+        .split(',')
+        .map(str::trim)
+        .filter(|d| !d.is_empty())
+        .map(|d| d.parse().map_err(|_| malformed()))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((descr, fortran_order, shape))
+}
+
+// magic, version 1.0 and `dict` padded with spaces and a newline so the data
+// starts aligned
+fn write_header(writer: &mut impl Write, dict: &str) -> io::Result<()> {
+    let unpadded = MAGIC.len() + 2 + 2 + dict.len() + 1;
+    let len = dict.len() + 1 + (ALIGN - unpadded % ALIGN) % ALIGN;
+    let len = u16::try_from(len).map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+    writer.write_all(MAGIC)?;
+    writer.write_all(&[1, 0])?;
+    writer.write_all(&len.to_le_bytes())?;
+    writeln!(writer, "{dict:<width$}", width = len as usize - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matrix_npy_round_trip() {
+        let a = Matrix::new([1.0f64, -2.5, 3.0, 4.0, 5.0, 6.25], 2, 3);
+        let mut buf = Vec::new();
+        a.write_npy(&mut buf).unwrap();
+        assert_eq!(&buf[..8], b"\x93NUMPY\x01\x00");
+        assert_eq!((buf.len() - 6 * 8) % ALIGN, 0);
+        assert_eq!(buf.last(), Some(&6.25f64.to_le_bytes()[7]));
+        assert_eq!(Matrix::<f64>::read_npy(&buf[..]).unwrap(), a);
+
+        // a column-major matrix is written and read back in Fortran order
+        let c = Matrix::from_col_major([1.0f32, 4.0, 2.0, 5.0], 2, 2);
+        let mut buf = Vec::new();
+        c.write_npy(&mut buf).unwrap();
+        let back = Matrix::<f32>::read_npy(&buf[..]).unwrap();
+        assert_eq!((back.layout(), back[(0, 1)]), (Layout::ColMajor, 2.0));
+    }
+
+    #[test]
+    fn test_matrix_npy_header() {
+        // as written by numpy 1.x for `np.arange(6.0).reshape(2, 3)`
+        let mut file = Vec::new();
+        write_header(
+            &mut file,
+            "{'descr': '<f8', 'fortran_order': False, 'shape': (2, 3), }",
+        )
+        .unwrap();
+        for x in 0..6 {
+            file.extend_from_slice(&(x as f64).to_le_bytes());
+        }
+        let a = Matrix::<f64>::read_npy(&file[..]).unwrap();
+        assert_eq!(a, Matrix::from_fn(2, 3, |i, j| (i * 3 + j) as f64));
+
+        assert!(matches!(
+            Matrix::<f32>::read_npy(&file[..]),
+            Err(NpyError::Dtype { expected: "<f4", ref found }) if found == "<f8"
+        ));
+        assert!(matches!(
+            Matrix::<f64>::read_npy(&file[..file.len() - 1]),
+            Err(NpyError::Io(e)) if e.kind() == io::ErrorKind::UnexpectedEof
+        ));
+        assert!(matches!(
+            Matrix::<f64>::read_npy(&b"PK\x03\x04 not npy"[..]),
+            Err(NpyError::Magic)
+        ));
+
+        let mut vector = Vec::new();
+        write_header(
+            &mut vector,
+            "{'descr': '<f8', 'fortran_order': False, 'shape': (3,), }",
+        )
+        .unwrap();
+        assert!(matches!(
+            Matrix::<f64>::read_npy(&vector[..]),
+            Err(NpyError::Shape(shape)) if shape == [3]
+        ));
+        let mut broken = Vec::new();
+        write_header(&mut broken, "{'descr': '<f8', 'shape': (1, 1), }").unwrap();
+        assert!(matches!(
+            Matrix::<f64>::read_npy(&broken[..]),
+            Err(NpyError::Header(_))
+        ));
+    }
+}
@@ -0,0 +1,174 @@
+//! Products and identities built on `num_traits::{Zero, One}`, for element types whose
+//! `Default` is not their additive identity, or that implement no `Default` at all.
+
+use core::ops::Mul;
+use num_traits::{One, Zero};
+
+#[cfg(not(feature = "threads"))]
+use super::par_row_blocks;
+use super::{columns, row_major_operands, Matrix, MatrixError, SERIAL_THRESHOLD};
+#[cfg(feature = "threads")]
+use super::{guard, try_par_row_blocks};
+
+impl<T: Zero + Clone> Matrix<T> {
+    /// A `row` x `col` matrix of `T::zero()`.
+    pub fn zero(row: usize, col: usize) -> Self {
+        Self::new(alloc::vec![T::zero(); row * col], row, col)
+    }
+
+    /// Whether every element is `T::zero()`.
+    pub fn is_zero(&self) -> bool {
+        self.data.iter().all(Zero::is_zero)
+    }
+
+    /// The sum of the diagonal from `T::zero()`, `NotSquare` unless `row == col`.
+    pub fn trace_num(&self) -> Result<T, MatrixError> {
+        self.check_square()?;
+        let diagonal = (0..self.row).map(|i| self.data[i * self.col + i].clone());
+        Ok(diagonal.fold(T::zero(), |sum, x| sum + x))
+    }
+}
+
+impl<T: Zero + One + Clone> Matrix<T> {
+    /// The `n` x `n` identity, `T::one()` on the diagonal and `T::zero()` elsewhere.
+    pub fn one(n: usize) -> Self {
+        Self::from_fn(n, n, |i, j| if i == j { T::one() } else { T::zero() })
+    }
+}
+
+// like `dot`, seeded with the additive identity instead of `T::default()`
+fn dot_zero<T: Zero + Mul<Output = T> + Clone>(row: &[T], col: &[T]) -> T {
+    row.iter()
+        .zip(col)
+        .fold(T::zero(), |sum, (x, y)| sum + x.clone() * y.clone())
+}
+
+/// `a * b` with every sum starting from `T::zero()`, rows split into one block per
+/// thread of the default `MultiplyConfig`, or all computed on this thread below its
+/// `serial_threshold`. A panicking `Mul` or `Add` fails with `WorkerPanicked` at its
+/// cell.
+///
+/// Needs neither `Default` nor `AddAssign` nor `Display`, so semirings such as
+/// (min, +) work as long as they implement `Zero`.
+pub fn multiply_num<T>(a: &Matrix<T>, b: &Matrix<T>) -> Result<Matrix<T>, MatrixError>
+where
+    T: Zero + Mul<Output = T> + Clone + Send + Sync,
+{
+    let (a, b) = &row_major_operands(a, b)?;
+    let (k, n) = (a.col, b.col);
+    let mut data = alloc::vec![T::zero(); a.row * n];
+    if data.is_empty() {
+        return Ok(Matrix::new(data, a.row, n));
+    }
+
+    let columns = columns(b);
+    // row `i` into `out`, keeping `at` on the cell being computed
+    let fill = |i: usize, out: &mut [T], at: &mut (usize, usize)| {
+        let row = &a.data[i * k..(i + 1) * k];
+        for (j, cell) in out.iter_mut().enumerate() {
+            *at = (i, j);
+            *cell = dot_zero(row, &columns[j * k..(j + 1) * k]);
+        }
+    };
+    let work = a.row.saturating_mul(k).saturating_mul(n);
+    #[cfg(feature = "threads")]
+    try_par_row_blocks(&mut data, n, work, SERIAL_THRESHOLD, |i, out| {
+        guard(|at| fill(i, out, at))
+    })?;
+    #[cfg(not(feature = "threads"))]
+    par_row_blocks(&mut data, n, work, SERIAL_THRESHOLD, |i, out| {
+        fill(i, out, &mut (0, 0))
+    });
+    Ok(Matrix::new(data, a.row, n))
+}
+
+#[cfg(test)]
+mod tests {
+    use core::ops::Add;
+
+    use super::*;
+    use crate::multiply;
+
+    // the (min, +) semiring: "adding" takes the minimum and "multiplying" adds, so
+    // the zero is infinity while `f64::default()` would be 0
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct MinPlus(f64);
+
+    impl Add for MinPlus {
+        type Output = Self;
+        fn add(self, rhs: Self) -> Self {
+            MinPlus(self.0.min(rhs.0))
+        }
+    }
+
+    impl Mul for MinPlus {
+        type Output = Self;
+        #[allow(clippy::suspicious_arithmetic_impl)]
+        fn mul(self, rhs: Self) -> Self {
+            MinPlus(self.0 + rhs.0)
+        }
+    }
+
+    impl Zero for MinPlus {
+        fn zero() -> Self {
+            MinPlus(f64::INFINITY)
+        }
+        fn is_zero(&self) -> bool {
+            self.0 == f64::INFINITY
+        }
+    }
+
+    #[test]
+    fn test_multiply_num_min_plus() {
+        // edge weights of a 3-node graph, squared once gives the shortest paths of at
+        // most two edges
+        let inf = f64::INFINITY;
+        let w = [0.0, 4.0, inf, inf, 0.0, 1.0, 2.0, inf, 0.0];
+        let w = Matrix::new(w.map(MinPlus).to_vec(), 3, 3);
+        let d = multiply_num(&w, &w).unwrap();
+        let expected = [0.0, 4.0, 5.0, 3.0, 0.0, 1.0, 2.0, 6.0, 0.0];
+        assert_eq!(d.as_slice(), expected.map(MinPlus));
+
+        assert!(Matrix::<MinPlus>::zero(2, 3).is_zero());
+        assert!(!w.is_zero());
+        assert_eq!(w.trace_num().unwrap(), MinPlus(0.0));
+        assert!(matches!(
+            multiply_num(&w, &Matrix::zero(2, 3)),
+            Err(MatrixError::DimensionMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_multiply_num_agrees_with_multiply() {
+        let a = Matrix::from_fn(9, 6, |i, j| (i * 6 + j) as i64 - 20);
+        let b = Matrix::from_fn(6, 5, |i, j| (i + 2 * j) as i64);
+        assert_eq!(multiply_num(&a, &b).unwrap(), multiply(&a, &b).unwrap());
+        assert_eq!(multiply_num(&a, &Matrix::one(6)).unwrap(), a);
+        assert_eq!(Matrix::<i64>::one(3), Matrix::identity(3));
+        assert_eq!(Matrix::<i64>::one(4).trace_num().unwrap(), 4);
+
+        // big enough for the worker threads
+        let a = Matrix::from_fn(40, 40, |i, j| (i * j) as i64 % 11 - 5);
+        assert_eq!(multiply_num(&a, &a).unwrap(), multiply(&a, &a).unwrap());
+    }
+
+    #[cfg(all(feature = "threads", debug_assertions))]
+    #[test]
+    fn test_multiply_num_reports_panicked_cell() {
+        // only cell (30, 0) overflows, MAX * 1 + 1 * MAX
+        let a = Matrix::from_fn(40, 40, |i, j| match (i, j) {
+            (30, 0) => i64::MAX,
+            _ if i == j => 1,
+            _ => 0,
+        });
+        assert_eq!(
+            multiply_num(&a, &a).unwrap_err(),
+            MatrixError::WorkerPanicked { row: 30, col: 0 }
+        );
+        let small = Matrix::new([i64::MAX, 1, 1, 1], 2, 2);
+        assert_eq!(
+            multiply_num(&small, &small).unwrap_err(),
+            MatrixError::WorkerPanicked { row: 0, col: 0 }
+        );
+    }
+}
@@ -0,0 +1,546 @@
+//! Element-wise arithmetic, scaling and the Kronecker product, split over scoped
+//! threads for large matrices.
+
+use alloc::vec::Vec;
+use core::fmt::Display;
+use core::ops::{Add, AddAssign, Mul, Sub, SubAssign};
+#[cfg(feature = "threads")]
+use std::thread;
+
+#[cfg(feature = "threads")]
+use super::THREAD_NUM;
+use super::{Layout, Matrix, MatrixError};
+
+// below this many elements spawning threads costs more than it saves
+pub(crate) const PARALLEL_MIN: usize = 1 << 14;
+
+impl<T> Matrix<T> {
+    pub(crate) fn check_same_shape(&self, other: &Matrix<T>) -> Result<(), MatrixError> {
+        if (self.row, self.col) != (other.row, other.col) {
+            return Err(MatrixError::ShapeMismatch {
+                left: (self.row, self.col),
+                right: (other.row, other.col),
+            });
+        }
+        Ok(())
+    }
+
+    /// `self + other`, or `ShapeMismatch` unless both have the same shape.
+    pub fn try_add(&self, other: &Matrix<T>) -> Result<Matrix<T>, MatrixError>
+    where
+        T: Add<Output = T> + Clone + Send + Sync,
+    {
+        self.zip_with(other, |x, y| x + y)
+    }
+
+    /// `self - other`, or `ShapeMismatch` unless both have the same shape.
+    pub fn try_sub(&self, other: &Matrix<T>) -> Result<Matrix<T>, MatrixError>
+    where
+        T: Sub<Output = T> + Clone + Send + Sync,
+    {
+        self.zip_with(other, |x, y| x - y)
+    }
+
+    /// The element-wise (Hadamard) product, or `ShapeMismatch` unless both have the
+    /// same shape.
+    pub fn hadamard(&self, other: &Matrix<T>) -> Result<Matrix<T>, MatrixError>
+    where
+        T: Display + Mul<Output = T> + Add<Output = T> + AddAssign + Default + Clone + Send + Sync,
+    {
+        self.zip_with(other, |x, y| x * y)
+    }
+
+    /// The Kronecker product, `self.row * other.row` x `self.col * other.col`, with
+    /// block `(i, j)` equal to `self[(i, j)] * other`.
+    ///
+    /// Blocks of output rows, one row of `self` each, are split over the threads once
+    /// the output is large enough.
+    pub fn kron(&self, other: &Matrix<T>) -> Matrix<T>
+    where
+        T: Mul<Output = T> + Default + Clone + Send + Sync,
+    {
+        let (a, other) = (self.row_major(), other.row_major());
+        let (row, col) = (a.row * other.row, a.col * other.col);
+        let mut data = alloc::vec![T::default(); row * col];
+        // all output rows coming from row `i1` of `self`
+        let fill = |i1: usize, out: &mut [T]| {
+            for (i2, out) in out.chunks_mut(col.max(1)).enumerate() {
+                let b_row = &other.data[i2 * other.col..(i2 + 1) * other.col];
+                for (j1, out) in out.chunks_mut(other.col.max(1)).enumerate() {
+                    let x = &a.data[i1 * a.col + j1];
+                    for (cell, y) in out.iter_mut().zip(b_row) {
+                        *cell = x.clone() * y.clone();
+                    }
+                }
+            }
+        };
+        par_row_blocks(&mut data, other.row * col, row * col, PARALLEL_MIN, fill);
+        Matrix::new(data, row, col)
+    }
+
+    /// Multiplies every element by `k` in place.
+    pub fn scale_mut(&mut self, k: T)
+    where
+        T: Mul<Output = T> + Clone + Send + Sync,
+    {
+        par_update(&mut self.data, |x| *x = x.clone() * k.clone());
+    }
+
+    /// Runs `f` on every element in place, e.g. `m.par_apply(|x| *x = x.clamp(0.0, 1.0))`,
+    /// in one chunk of the buffer per thread for large matrices.
+    pub fn par_apply<F>(&mut self, f: F)
+    where
+        T: Send,
+        F: Fn(&mut T) + Sync,
+    {
+        par_update(&mut self.data, f);
+    }
+
+    /// A new matrix of the same shape and layout with `f` applied to every element,
+    /// computed in one chunk of the buffer per thread for large matrices.
+    pub fn par_map<U, F>(&self, f: F) -> Matrix<U>
+    where
+        T: Sync,
+        U: Send,
+        F: Fn(&T) -> U + Sync,
+    {
+        let data = par_map_slice(&self.data, f);
+        Matrix {
+            layout: self.layout,
+            ..Matrix::new(data, self.row, self.col)
+        }
+    }
+
+    // a new matrix of `f` applied to every pair of elements at the same position
+    pub(crate) fn zip_with<F>(&self, other: &Matrix<T>, f: F) -> Result<Matrix<T>, MatrixError>
+    where
+        T: Clone + Send + Sync,
+        F: Fn(T, T) -> T + Sync,
+    {
+        self.check_same_shape(other)?;
+        if self.layout != other.layout {
+            return self.row_major().zip_with(&other.row_major(), f);
+        }
+        Ok(self.clone_with(|data| {
+            par_zip_update(data, &other.data, |x, y| *x = f(x.clone(), y.clone()))
+        }))
+    }
+
+    // a copy of this matrix, with `f` applied to its elements
+    fn clone_with(&self, f: impl FnOnce(&mut [T])) -> Matrix<T>
+    where
+        T: Clone,
+    {
+        let mut data = self.data.clone();
+        f(&mut data);
+        Matrix {
+            layout: self.layout,
+            ..Matrix::new(data, self.row, self.col)
+        }
+    }
+
+    // `f` in place on every element and the element of `other` at the same position
+    pub(crate) fn zip_mut<F>(&mut self, other: &Matrix<T>, f: F) -> Result<(), MatrixError>
+    where
+        T: Send + Sync,
+        F: Fn(&mut T, &T) + Sync,
+    {
+        self.check_same_shape(other)?;
+        if self.layout != other.layout {
+            // walk `other` in the order of this buffer
+            let (row, col, layout) = (self.row, self.col, self.layout);
+            for (idx, x) in self.data.iter_mut().enumerate() {
+                let (i, j) = match layout {
+                    Layout::RowMajor => (idx / col, idx % col),
+                    Layout::ColMajor => (idx % row, idx / row),
+                };
+                f(x, &other[(i, j)]);
+            }
+            return Ok(());
+        }
+        par_zip_update(&mut self.data, &other.data, f);
+        Ok(())
+    }
+}
+
+// `f` on every element of `data` and the element of `other` at the same index, in
+// one chunk per thread once there are enough elements
+pub(crate) fn par_zip_update<T, F>(data: &mut [T], other: &[T], f: F)
+where
+    T: Send + Sync,
+    F: Fn(&mut T, &T) + Sync,
+{
+    #[cfg(feature = "threads")]
+    if data.len() >= PARALLEL_MIN {
+        let chunk = data.len().div_ceil(THREAD_NUM);
+        let f = &f;
+        thread::scope(|s| {
+            for (data, other) in data.chunks_mut(chunk).zip(other.chunks(chunk)) {
+                s.spawn(move || data.iter_mut().zip(other).for_each(|(x, y)| f(x, y)));
+            }
+        });
+        return;
+    }
+    data.iter_mut().zip(other).for_each(|(x, y)| f(x, y));
+}
+
+// `f` on every element of `data`, in one chunk per thread once there are enough
+pub(crate) fn par_update<T, F>(data: &mut [T], f: F)
+where
+    T: Send,
+    F: Fn(&mut T) + Sync,
+{
+    #[cfg(feature = "threads")]
+    if data.len() >= PARALLEL_MIN {
+        let chunk = data.len().div_ceil(THREAD_NUM);
+        let f = &f;
+        thread::scope(|s| {
+            for data in data.chunks_mut(chunk) {
+                s.spawn(move || data.iter_mut().for_each(f));
+            }
+        });
+        return;
+    }
+    data.iter_mut().for_each(f);
+}
+
+// `fill(i, out)` for every row `i` of `data`, `n` elements each, in one block of
+// rows per thread once `work` reaches `threshold`.
+//
+// map/reduce: map phase, every block writes its own rows of the output
+pub(crate) fn par_row_blocks<T, F>(data: &mut [T], n: usize, work: usize, threshold: usize, fill: F)
+where
+    T: Send,
+    F: Fn(usize, &mut [T]) + Sync,
+{
+    if n == 0 || data.is_empty() {
+        return;
+    }
+    #[cfg(feature = "threads")]
+    if work >= threshold {
+        let block_rows = (data.len() / n).div_ceil(THREAD_NUM);
+        let fill = &fill;
+        thread::scope(|s| {
+            for (block, out) in data.chunks_mut(block_rows * n).enumerate() {
+                s.spawn(move || {
+                    for (r, out) in out.chunks_mut(n).enumerate() {
+                        fill(block * block_rows + r, out);
+                    }
+                });
+            }
+        });
+        return;
+    }
+    #[cfg(not(feature = "threads"))]
+    let _ = (work, threshold);
+    for (i, out) in data.chunks_mut(n).enumerate() {
+        fill(i, out);
+    }
+}
+
+// `par_row_blocks` for a `fill` that can fail. The reduce phase keeps the error of
+// the first block that failed, a worker that died fails with `WorkerFailed`
+pub(crate) fn try_par_row_blocks<T, F>(
+    data: &mut [T],
+    n: usize,
+    work: usize,
+    threshold: usize,
+    fill: F,
+) -> Result<(), MatrixError>
+where
+    T: Send,
+    F: Fn(usize, &mut [T]) -> Result<(), MatrixError> + Sync,
+{
+    if n == 0 || data.is_empty() {
+        return Ok(());
+    }
+    #[cfg(feature = "threads")]
+    if work >= threshold {
+        let block_rows = (data.len() / n).div_ceil(THREAD_NUM);
+        let fill = &fill;
+        return thread::scope(|s| {
+            let handles = data
+                .chunks_mut(block_rows * n)
+                .enumerate()
+                .map(|(block, out)| {
+                    s.spawn(move || {
+                        out.chunks_mut(n)
+                            .enumerate()
+                            .try_for_each(|(r, out)| fill(block * block_rows + r, out))
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .try_for_each(|h| h.join().map_err(|_| MatrixError::WorkerFailed)?)
+        });
+    }
+    #[cfg(not(feature = "threads"))]
+    let _ = (work, threshold);
+    data.chunks_mut(n)
+        .enumerate()
+        .try_for_each(|(i, out)| fill(i, out))
+}
+
+// `f` of every element of `data` in order, each thread mapping one chunk into its
+// own buffer once there are enough elements
+fn par_map_slice<T, U, F>(data: &[T], f: F) -> Vec<U>
+where
+    T: Sync,
+    U: Send,
+    F: Fn(&T) -> U + Sync,
+{
+    #[cfg(feature = "threads")]
+    if data.len() >= PARALLEL_MIN {
+        let chunk = data.len().div_ceil(THREAD_NUM);
+        let f = &f;
+        return thread::scope(|s| {
+            let handles = data
+                .chunks(chunk)
+                .map(|data| s.spawn(move || data.iter().map(f).collect::<Vec<_>>()))
+                .collect::<Vec<_>>();
+            let mut out = Vec::with_capacity(data.len());
+            for handle in handles {
+                // a panic in `f` is passed on to the caller
+                match handle.join() {
+                    Ok(part) => out.extend(part),
+                    Err(e) => std::panic::resume_unwind(e),
+                }
+            }
+            out
+        });
+    }
+    data.iter().map(f).collect()
+}
+
+impl<T> Mul<T> for Matrix<T>
+where
+    T: Mul<Output = T> + Clone + Send + Sync,
+{
+    type Output = Matrix<T>;
+    fn mul(mut self, k: T) -> Self::Output {
+        self.scale_mut(k);
+        self
+    }
+}
+
+impl<T> Mul<T> for &Matrix<T>
+where
+    T: Mul<Output = T> + Clone + Send + Sync,
+{
+    type Output = Matrix<T>;
+    fn mul(self, k: T) -> Self::Output {
+        self.clone_with(|data| par_update(data, |x| *x = x.clone() * k.clone()))
+    }
+}
+
+/// Panics unless both have the same shape, `try_add` returns the error instead.
+impl<T> Add for &Matrix<T>
+where
+    T: Add<Output = T> + Clone + Send + Sync,
+{
+    type Output = Matrix<T>;
+    fn add(self, rhs: Self) -> Self::Output {
+        self.try_add(rhs).expect("Matrix add error")
+    }
+}
+
+impl<T> Add for Matrix<T>
+where
+    T: Add<Output = T> + Clone + Send + Sync,
+{
+    type Output = Matrix<T>;
+    fn add(mut self, rhs: Self) -> Self::Output {
+        self.zip_mut(&rhs, |x, y| *x = x.clone() + y.clone())
+            .expect("Matrix add error");
+        self
+    }
+}
+
+/// Panics unless both have the same shape, `try_sub` returns the error instead.
+impl<T> Sub for &Matrix<T>
+where
+    T: Sub<Output = T> + Clone + Send + Sync,
+{
+    type Output = Matrix<T>;
+    fn sub(self, rhs: Self) -> Self::Output {
+        self.try_sub(rhs).expect("Matrix sub error")
+    }
+}
+
+impl<T> Sub for Matrix<T>
+where
+    T: Sub<Output = T> + Clone + Send + Sync,
+{
+    type Output = Matrix<T>;
+    fn sub(mut self, rhs: Self) -> Self::Output {
+        self.zip_mut(&rhs, |x, y| *x = x.clone() - y.clone())
+            .expect("Matrix sub error");
+        self
+    }
+}
+
+/// Panics unless both have the same shape.
+impl<T> AddAssign<&Matrix<T>> for Matrix<T>
+where
+    T: AddAssign + Clone + Send + Sync,
+{
+    fn add_assign(&mut self, rhs: &Matrix<T>) {
+        self.zip_mut(rhs, |x, y| *x += y.clone())
+            .expect("Matrix add error");
+    }
+}
+
+/// Panics unless both have the same shape.
+impl<T> SubAssign<&Matrix<T>> for Matrix<T>
+where
+    T: SubAssign + Clone + Send + Sync,
+{
+    fn sub_assign(&mut self, rhs: &Matrix<T>) {
+        self.zip_mut(rhs, |x, y| *x -= y.clone())
+            .expect("Matrix sub error");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matrix_add_sub() {
+        let a = Matrix::new([1, 2, 3, 4, 5, 6], 2, 3);
+        let b = Matrix::new([6, 5, 4, 3, 2, 1], 2, 3);
+        assert_eq!((&a + &b).as_slice(), [7; 6]);
+        assert_eq!((&a - &b).as_slice(), [-5, -3, -1, 1, 3, 5]);
+
+        let mut c = Matrix::new([0; 6], 2, 3);
+        c += &a;
+        c += &b;
+        c -= &a;
+        assert_eq!(c.as_slice(), b.as_slice());
+        assert_eq!((a + b).as_slice(), [7; 6]);
+
+        let d = Matrix::new([1, 2, 3, 4, 5, 6], 3, 2);
+        let c = Matrix::new([0; 6], 2, 3);
+        assert_eq!(
+            c.try_add(&d).unwrap_err(),
+            MatrixError::ShapeMismatch {
+                left: (2, 3),
+                right: (3, 2)
+            }
+        );
+        assert!(c.try_sub(&d).is_err());
+    }
+
+    #[cfg(feature = "threads")]
+    #[test]
+    fn test_matrix_add_in_parallel_chunks() {
+        use alloc::vec::Vec;
+
+        let n = PARALLEL_MIN + 3;
+        let a = Matrix::new((0..n as i64).collect::<Vec<_>>(), 1, n);
+        let mut b = Matrix::new((0..n as i64).rev().collect::<Vec<_>>(), 1, n);
+        assert!((&a + &b).as_slice().iter().all(|&v| v == n as i64 - 1));
+        b -= &a;
+        assert_eq!(b.as_slice()[0], n as i64 - 1);
+        assert_eq!(b.as_slice()[n - 1], 1 - n as i64);
+    }
+
+    #[test]
+    fn test_matrix_hadamard() {
+        let a = Matrix::new([1, 2, 3, 4], 2, 2);
+        let b = Matrix::new([5, 6, 7, 8], 2, 2);
+        assert_eq!(a.hadamard(&b).unwrap().as_slice(), [5, 12, 21, 32]);
+        assert_eq!(
+            a.hadamard(&Matrix::new([1, 2, 3, 4], 1, 4)).unwrap_err(),
+            MatrixError::ShapeMismatch {
+                left: (2, 2),
+                right: (1, 4)
+            }
+        );
+    }
+
+    #[test]
+    fn test_matrix_kron() {
+        let a = Matrix::new([1, 2, 3, 4], 2, 2);
+        let b = Matrix::new([0, 5, 6, 7], 2, 2);
+        #[rustfmt::skip]
+        let expected = Matrix::new([
+            0, 5, 0, 10,
+            6, 7, 12, 14,
+            0, 15, 0, 20,
+            18, 21, 24, 28,
+        ], 4, 4);
+        assert_eq!(a.kron(&b), expected);
+
+        let row = Matrix::new([1, -1], 1, 2);
+        assert_eq!(row.kron(&Matrix::new([3], 1, 1)).as_slice(), [3, -3]);
+        let empty = a.kron(&Matrix::new(alloc::vec::Vec::new(), 0, 3));
+        assert_eq!((empty.row, empty.col), (0, 6));
+    }
+
+    #[cfg(feature = "threads")]
+    #[test]
+    fn test_matrix_kron_in_parallel_bands() {
+        let a = Matrix::from_fn(13, 11, |i, j| (i * 11 + j) as i64);
+        let b = Matrix::from_fn(12, 14, |i, j| (i as i64) - (j as i64));
+        let k = a.kron(&b);
+        assert!(k.data.len() >= PARALLEL_MIN);
+        for i in 0..k.row {
+            for j in 0..k.col {
+                let expected = a[(i / 12, j / 14)] * b[(i % 12, j % 14)];
+                assert_eq!(k[(i, j)], expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_matrix_scale() {
+        let a = Matrix::new([1, -2, 3, 4, 0, 6], 2, 3);
+        assert_eq!((&a * 3).as_slice(), [3, -6, 9, 12, 0, 18]);
+
+        let mut b = a * -1;
+        assert_eq!(b.as_slice(), [-1, 2, -3, -4, 0, -6]);
+        b.scale_mut(2);
+        assert_eq!(b.as_slice(), [-2, 4, -6, -8, 0, -12]);
+        assert_eq!((b.row, b.col), (2, 3));
+    }
+
+    #[cfg(feature = "threads")]
+    #[test]
+    fn test_matrix_scale_in_parallel_chunks() {
+        let n = PARALLEL_MIN * 2 + 1;
+        let mut a = Matrix::new(alloc::vec![1.5f64; n], n, 1);
+        a.scale_mut(2.0);
+        assert!(a.as_slice().iter().all(|&v| v == 3.0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_matrix_add_shape_mismatch_panics() {
+        let _ = &Matrix::new([1, 2], 1, 2) + &Matrix::new([1, 2], 2, 1);
+    }
+
+    #[test]
+    fn test_matrix_par_map_and_apply() {
+        let mut a = Matrix::from_col_major([1.5f64, -2.0, 3.0, 0.5], 2, 2);
+        let rounded = a.par_map(|x| *x as i32);
+        assert_eq!(rounded.layout(), Layout::ColMajor);
+        assert_eq!(rounded, Matrix::new([1, 3, -2, 0], 2, 2));
+        a.par_apply(|x| *x = x.clamp(0.0, 1.0));
+        assert_eq!(a, Matrix::new([1.0, 1.0, 0.0, 0.5], 2, 2));
+    }
+
+    #[cfg(feature = "threads")]
+    #[test]
+    fn test_matrix_par_map_in_parallel_chunks() {
+        let n = PARALLEL_MIN * 2 + 1;
+        let a = Matrix::from_fn(n, 1, |i, _| i as u64);
+        let b = a.par_map(|x| x * 2);
+        assert!(b
+            .as_slice()
+            .iter()
+            .enumerate()
+            .all(|(i, &v)| v == 2 * i as u64));
+    }
+}
@@ -0,0 +1,83 @@
+//! `Serialize`/`Deserialize` for `Matrix`, as `{ row, col, data }` with `data` in
+//! row-major order whatever the layout.
+
+use alloc::vec::Vec;
+use serde::{
+    de::Error as _, ser::SerializeStruct, Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use super::Matrix;
+
+impl<T: Serialize> Serialize for Matrix<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // the elements row after row, without copying a column-major buffer
+        struct Data<'a, T>(&'a Matrix<T>);
+        impl<T: Serialize> Serialize for Data<'_, T> {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_seq(self.0.rows().flatten())
+            }
+        }
+
+        let mut state = serializer.serialize_struct("Matrix", 3)?;
+        state.serialize_field("row", &self.row)?;
+        state.serialize_field("col", &self.col)?;
+        state.serialize_field("data", &Data(self))?;
+        state.end()
+    }
+}
+
+/// Fails unless `data` holds exactly `row * col` elements.
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Matrix<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(rename = "Matrix")]
+        struct Raw<T> {
+            row: usize,
+            col: usize,
+            data: Vec<T>,
+        }
+
+        let Raw { row, col, data } = Raw::deserialize(deserializer)?;
+        if row.checked_mul(col) != Some(data.len()) {
+            return Err(D::Error::custom(format_args!(
+                "{} elements for a {row}x{col} matrix",
+                data.len()
+            )));
+        }
+        Ok(Matrix::new(data, row, col))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matrix_serde_round_trip() {
+        let a = Matrix::new([1, 2, 3, 4, 5, 6], 2, 3);
+        let json = serde_json::to_string(&a).unwrap();
+        assert_eq!(json, r#"{"row":2,"col":3,"data":[1,2,3,4,5,6]}"#);
+        assert_eq!(serde_json::from_str::<Matrix<i32>>(&json).unwrap(), a);
+
+        // written row-major, so it reads back equal but row-major
+        let c = Matrix::from_col_major([1, 4, 2, 5, 3, 6], 2, 3);
+        assert_eq!(serde_json::to_string(&c).unwrap(), json);
+
+        let empty = Matrix::<f64>::zeros(0, 4);
+        let json = serde_json::to_string(&empty).unwrap();
+        assert_eq!(serde_json::from_str::<Matrix<f64>>(&json).unwrap(), empty);
+    }
+
+    #[test]
+    fn test_matrix_deserialize_rejects_bad_shape() {
+        let err =
+            serde_json::from_str::<Matrix<i32>>(r#"{"row":2,"col":3,"data":[1,2,3]}"#).unwrap_err();
+        assert!(
+            err.to_string().starts_with("3 elements for a 2x3 matrix"),
+            "{err}"
+        );
+        let overflow = format!(r#"{{"row":{},"col":2,"data":[]}}"#, usize::MAX);
+        assert!(serde_json::from_str::<Matrix<i32>>(&overflow).is_err());
+        assert!(serde_json::from_str::<Matrix<i32>>(r#"{"row":1,"data":[1]}"#).is_err());
+    }
+}
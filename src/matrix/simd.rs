@@ -0,0 +1,118 @@
+//! `f32`/`f64` products whose inner dot product keeps eight partial sums, one per
+//! lane, so the compiler turns it into vector instructions. This works on stable,
+//! unlike `std::simd`.
+
+use alloc::vec;
+
+use super::{columns, par_row_blocks, row_major_operands, Matrix, MatrixError, SERIAL_THRESHOLD};
+use crate::VectorError;
+
+const LANES: usize = 8;
+
+macro_rules! impl_simd {
+    ($($t:ty => $multiply:ident, $dot_product:ident, $dot:ident);*) => {$(
+        // the lanes are independent, unlike a single running sum, so they vectorize
+        // without reassociating float additions
+        fn $dot(row: &[$t], col: &[$t]) -> $t {
+            let mut lanes = [0.0; LANES];
+            let (rows, row_tail) = row.split_at(row.len() - row.len() % LANES);
+            let (cols, col_tail) = col.split_at(rows.len());
+            for (x, y) in rows.chunks_exact(LANES).zip(cols.chunks_exact(LANES)) {
+                for l in 0..LANES {
+                    lanes[l] += x[l] * y[l];
+                }
+            }
+            let tail = row_tail.iter().zip(col_tail).map(|(x, y)| x * y).sum::<$t>();
+            lanes.iter().sum::<$t>() + tail
+        }
+
+        /// `dot_product_slice` on the eight-lane kernel of the products below. The
+        /// sums are grouped differently, so results can differ in the last bits.
+        pub fn $dot_product(a: &[$t], b: &[$t]) -> Result<$t, VectorError> {
+            if a.len() != b.len() {
+                return Err(VectorError::LengthMismatch {
+                    left: a.len(),
+                    right: b.len(),
+                });
+            }
+            Ok($dot(a, b))
+        }
+
+        /// `a * b` on the eight-lane inner kernel, rows split into one block per
+        /// thread of the default `MultiplyConfig`, or all computed on this thread
+        /// below its `serial_threshold`. The sums are grouped differently from
+        /// `multiply`, so results can differ in the last bits.
+        pub fn $multiply(a: &Matrix<$t>, b: &Matrix<$t>) -> Result<Matrix<$t>, MatrixError> {
+            let (a, b) = &row_major_operands(a, b)?;
+            let (k, n) = (a.col, b.col);
+            let mut data = vec![0.0; a.row * n];
+            if data.is_empty() {
+                return Ok(Matrix::new(data, a.row, n));
+            }
+
+            let columns = columns(b);
+            let work = a.row.saturating_mul(k).saturating_mul(n);
+            par_row_blocks(&mut data, n, work, SERIAL_THRESHOLD, |i, out| {
+                let row = &a.data[i * k..(i + 1) * k];
+                for (j, cell) in out.iter_mut().enumerate() {
+                    *cell = $dot(row, &columns[j * k..(j + 1) * k]);
+                }
+            });
+            Ok(Matrix::new(data, a.row, n))
+        }
+    )*};
+}
+
+impl_simd!(
+    f32 => multiply_f32, dot_product_f32, dot_f32;
+    f64 => multiply_f64, dot_product_f64, dot_f64
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{dot_product_slice, multiply, Vector};
+    use alloc::vec::Vec;
+
+    #[test]
+    fn test_multiply_simd() {
+        // shared dimensions below, at and past a multiple of the lane count, the
+        // last one big enough for the worker threads
+        for k in [0, 3, 8, 19, 1000] {
+            let a = Matrix::from_fn(7, k, |i, j| (i * k + j) as f64 * 0.25 - 3.0);
+            let b = Matrix::from_fn(k, 5, |i, j| (i + j) as f64 * 0.5);
+            let expected = multiply(&a, &b).unwrap();
+            let c = multiply_f64(&a, &b).unwrap();
+            assert_eq!((c.row, c.col), (7, 5));
+            for (x, y) in c.as_slice().iter().zip(expected.as_slice()) {
+                assert!((x - y).abs() < 1e-9, "{x} != {y}");
+            }
+        }
+
+        let a = Matrix::from_fn(4, 10, |i, j| (i + j) as f32);
+        let c = multiply_f32(&a, &a.transpose()).unwrap();
+        assert_eq!(
+            c[(1, 2)],
+            (0..10).map(|j| ((1 + j) * (2 + j)) as f32).sum::<f32>()
+        );
+        assert!(matches!(
+            multiply_f32(&a, &a),
+            Err(MatrixError::DimensionMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_dot_product_simd() {
+        for len in [0, 5, 8, 21] {
+            let a = Vector::new((0..len).map(|i| i as f32 * 0.5).collect::<Vec<_>>());
+            let b = (0..len).map(|i| 3.0 - i as f32).collect::<Vec<_>>();
+            let expected = dot_product_slice(&a, &b).unwrap();
+            assert!((dot_product_f32(&a, &b).unwrap() - expected).abs() < 1e-3);
+        }
+        assert_eq!(dot_product_f64(&[1.5, 2.0], &[2.0, 0.25]).unwrap(), 3.5);
+        assert_eq!(
+            dot_product_f64(&[1.0], &[]).unwrap_err(),
+            VectorError::LengthMismatch { left: 1, right: 0 }
+        );
+    }
+}
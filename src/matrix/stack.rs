@@ -0,0 +1,115 @@
+//! Concatenation of matrices side by side or on top of each other, copying blocks
+//! of output rows on scoped threads for large outputs.
+
+use alloc::{vec, vec::Vec};
+
+use super::{par_row_blocks, Matrix, MatrixError, PARALLEL_MIN};
+
+impl<T: Default + Clone + Send + Sync> Matrix<T> {
+    /// `parts` side by side, row `i` the rows `i` of every part in order, or
+    /// `ShapeMismatch` for the first part with a different number of rows than the
+    /// first. No parts give a `0 x 0` matrix.
+    pub fn hstack(parts: &[&Matrix<T>]) -> Result<Matrix<T>, MatrixError> {
+        check_parts(parts, |m| m.row)?;
+        let parts = parts.iter().map(|m| m.row_major()).collect::<Vec<_>>();
+        let row = parts.first().map_or(0, |m| m.row);
+        let col = parts.iter().map(|m| m.col).sum();
+        Ok(stack_rows(row, col, |i, mut out| {
+            for m in &parts {
+                let (dst, rest) = out.split_at_mut(m.col);
+                dst.clone_from_slice(&m.data[i * m.col..(i + 1) * m.col]);
+                out = rest;
+            }
+        }))
+    }
+
+    /// `parts` on top of each other, the rows of the first part first, or
+    /// `ShapeMismatch` for the first part with a different number of columns than
+    /// the first. No parts give a `0 x 0` matrix.
+    pub fn vstack(parts: &[&Matrix<T>]) -> Result<Matrix<T>, MatrixError> {
+        check_parts(parts, |m| m.col)?;
+        let parts = parts.iter().map(|m| m.row_major()).collect::<Vec<_>>();
+        let row = parts.iter().map(|m| m.row).sum();
+        let col = parts.first().map_or(0, |m| m.col);
+        // every output row, borrowed from the part it comes from
+        let rows = parts
+            .iter()
+            .flat_map(|m| m.data.chunks(col.max(1)).take(m.row))
+            .collect::<Vec<_>>();
+        Ok(stack_rows(row, col, |i, out| out.clone_from_slice(rows[i])))
+    }
+}
+
+// `ShapeMismatch` for the first part whose `dim` differs from the first part's
+fn check_parts<T>(
+    parts: &[&Matrix<T>],
+    dim: impl Fn(&Matrix<T>) -> usize,
+) -> Result<(), MatrixError> {
+    let Some(first) = parts.first() else {
+        return Ok(());
+    };
+    match parts.iter().find(|m| dim(m) != dim(first)) {
+        Some(m) => Err(MatrixError::ShapeMismatch {
+            left: first.shape(),
+            right: m.shape(),
+        }),
+        None => Ok(()),
+    }
+}
+
+// a `row` x `col` matrix with `fill(i, out)` writing row `i` into `out`, blocks of
+// rows on one thread each once there are enough elements
+fn stack_rows<T, F>(row: usize, col: usize, fill: F) -> Matrix<T>
+where
+    T: Default + Clone + Send,
+    F: Fn(usize, &mut [T]) + Sync,
+{
+    let mut data = vec![T::default(); row * col];
+    par_row_blocks(&mut data, col, row * col, PARALLEL_MIN, fill);
+    Matrix::new(data, row, col)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matrix_hstack_vstack() {
+        let a = Matrix::new([1, 2, 3, 4], 2, 2);
+        let b = Matrix::from_col_major([5, 6], 2, 1);
+        let h = Matrix::hstack(&[&a, &b, &a]).unwrap();
+        assert_eq!(h, Matrix::new([1, 2, 5, 1, 2, 3, 4, 6, 3, 4], 2, 5));
+        let v = Matrix::vstack(&[&a, &b.transpose(), &a]).unwrap();
+        assert_eq!(v.shape(), (5, 2));
+        assert_eq!(v.as_slice(), [1, 2, 3, 4, 5, 6, 1, 2, 3, 4]);
+
+        assert_eq!(
+            Matrix::vstack(&[&a, &b]).unwrap_err(),
+            MatrixError::ShapeMismatch {
+                left: (2, 2),
+                right: (2, 1)
+            }
+        );
+        assert!(Matrix::hstack(&[&a, &b.transpose()]).is_err());
+        assert_eq!(Matrix::<i32>::hstack(&[]).unwrap().shape(), (0, 0));
+        let empty = Matrix::<i32>::zeros(0, 3);
+        assert_eq!(Matrix::vstack(&[&empty, &empty]).unwrap().shape(), (0, 3));
+        assert_eq!(Matrix::hstack(&[&empty, &empty]).unwrap().shape(), (0, 6));
+        let empty = Matrix::<i32>::zeros(3, 0);
+        assert_eq!(Matrix::vstack(&[&empty, &empty]).unwrap().shape(), (6, 0));
+    }
+
+    #[test]
+    fn test_matrix_stack_large() {
+        // past `PARALLEL_MIN`, so the rows are copied on several threads
+        let a = Matrix::from_fn(200, 70, |i, j| (i * 70 + j) as i64);
+        let b = Matrix::from_fn(200, 30, |i, j| -((i * 30 + j) as i64));
+        let h = Matrix::hstack(&[&a, &b]).unwrap();
+        assert_eq!(
+            (h[(199, 69)], h[(199, 70)], h[(7, 99)]),
+            (a[(199, 69)], b[(199, 0)], b[(7, 29)])
+        );
+        let v = Matrix::vstack(&[&a.transpose(), &b.transpose()]).unwrap();
+        assert_eq!(v, h.transpose());
+    }
+}
@@ -0,0 +1,121 @@
+//! Blocked transpose, split over scoped threads for large matrices.
+
+#[cfg(feature = "threads")]
+use std::thread;
+
+#[cfg(feature = "threads")]
+use super::{ops::PARALLEL_MIN, THREAD_NUM};
+use super::{Layout, Matrix, MatrixError};
+
+// side of the square blocks copied at a time, so both the rows read and the rows
+// written stay in cache
+const BLOCK: usize = 32;
+
+impl<T: Clone + Send + Sync> Matrix<T> {
+    /// A new `col x row` matrix with `self[i][j]` at `[j][i]`.
+    pub fn transpose(&self) -> Matrix<T> {
+        // the clone is only there to give every slot a value, all get overwritten,
+        // unless `self` is column-major, whose buffer already is the transpose
+        let mut out = Matrix::new(self.data.clone(), self.col, self.row);
+        if self.layout == Layout::RowMajor {
+            transpose_rows(self, &mut out.data, 0);
+        }
+        out
+    }
+
+    /// Writes the transpose into `out`, reusing its buffer and leaving it row-major,
+    /// or `ShapeMismatch` unless `out` is `col x row`.
+    pub fn transpose_into(&self, out: &mut Matrix<T>) -> Result<(), MatrixError> {
+        if (out.row, out.col) != (self.col, self.row) {
+            return Err(MatrixError::ShapeMismatch {
+                left: (self.col, self.row),
+                right: (out.row, out.col),
+            });
+        }
+        out.layout = Layout::RowMajor;
+        match self.layout {
+            Layout::RowMajor => transpose_rows(self, &mut out.data, 0),
+            Layout::ColMajor => out.data.clone_from_slice(&self.data),
+        }
+        Ok(())
+    }
+}
+
+// fills `out`, the rows of the transpose from `first` on, one chunk of whole rows
+// per thread once there are enough elements
+fn transpose_rows<T: Clone + Send + Sync>(m: &Matrix<T>, out: &mut [T], first: usize) {
+    #[cfg(feature = "threads")]
+    if out.len() >= PARALLEL_MIN && first == 0 {
+        let chunk_rows = m.col.div_ceil(THREAD_NUM);
+        thread::scope(|s| {
+            for (k, out) in out.chunks_mut(chunk_rows * m.row).enumerate() {
+                s.spawn(move || transpose_rows(m, out, k * chunk_rows));
+            }
+        });
+        return;
+    }
+    transpose_block(&m.data, m.row, m.col, out, first);
+}
+
+// the single-threaded kernel behind `transpose_rows`, for callers without `Send`,
+// transposing the `row` x `col` row-major `data`
+pub(super) fn transpose_block<T: Clone>(
+    data: &[T],
+    row: usize,
+    col: usize,
+    out: &mut [T],
+    first: usize,
+) {
+    let rows = out.len() / row.max(1);
+    for j0 in (first..first + rows).step_by(BLOCK) {
+        for i0 in (0..row).step_by(BLOCK) {
+            for j in j0..(j0 + BLOCK).min(first + rows) {
+                for i in i0..(i0 + BLOCK).min(row) {
+                    out[(j - first) * row + i] = data[i * col + j].clone();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn test_matrix_transpose() {
+        let a = Matrix::new([1, 2, 3, 4, 5, 6], 2, 3);
+        let t = a.transpose();
+        assert_eq!((t.row, t.col), (3, 2));
+        assert_eq!(t.as_slice(), [1, 4, 2, 5, 3, 6]);
+        assert_eq!(t.transpose().as_slice(), a.as_slice());
+
+        let mut out = Matrix::new([0; 6], 3, 2);
+        a.transpose_into(&mut out).unwrap();
+        assert_eq!(out.as_slice(), t.as_slice());
+        assert_eq!(
+            a.transpose_into(&mut Matrix::new([0; 6], 2, 3)),
+            Err(MatrixError::ShapeMismatch {
+                left: (3, 2),
+                right: (2, 3)
+            })
+        );
+
+        let empty = Matrix::new(Vec::<i32>::new(), 0, 3).transpose();
+        assert_eq!((empty.row, empty.col), (3, 0));
+    }
+
+    #[test]
+    fn test_matrix_transpose_spans_blocks() {
+        // uneven in both directions, and big enough to be split across threads
+        let (row, col) = (131, 257);
+        let a = Matrix::new((0..row * col).collect::<Vec<_>>(), row, col);
+        let t = a.transpose();
+        for i in 0..row {
+            for j in 0..col {
+                assert_eq!(t.data[j * row + i], a.data[i * col + j]);
+            }
+        }
+    }
+}
@@ -0,0 +1,216 @@
+//! Borrowed windows onto a matrix's buffer, for working on tiles without copying.
+
+use alloc::{vec, vec::Vec};
+use core::ops::{AddAssign, Index, Mul, Range};
+
+use super::{dot, par_row_blocks, Layout, Matrix, MatrixError, Storage, SERIAL_THRESHOLD};
+
+/// A `row` x `col` window onto a `Matrix`, row `i` starting `i * stride` elements
+/// after the first.
+#[derive(Clone, Copy)]
+pub struct MatrixView<'a, T> {
+    data: &'a [T],
+    row: usize,
+    col: usize,
+    stride: usize,
+}
+
+impl<T, S: Storage<T>> Matrix<T, S> {
+    /// The submatrix of rows `rows` and columns `cols`, borrowing this buffer.
+    ///
+    /// Panics if either range is out of bounds, like slicing, or if this matrix is
+    /// `ColMajor`.
+    pub fn view(&self, rows: Range<usize>, cols: Range<usize>) -> MatrixView<'_, T> {
+        self.as_view().view(rows, cols)
+    }
+
+    /// The whole matrix as a view. Panics if this matrix is `ColMajor`, views step
+    /// through whole rows, `into_row_major` first.
+    pub fn as_view(&self) -> MatrixView<'_, T> {
+        assert!(
+            self.layout == Layout::RowMajor,
+            "MatrixView of a column-major matrix"
+        );
+        MatrixView::new(&self.data, self.row, self.col)
+    }
+}
+
+impl<'a, T> MatrixView<'a, T> {
+    // `data` holding `row` rows of `col` elements back to back
+    pub(super) fn new(data: &'a [T], row: usize, col: usize) -> Self {
+        MatrixView {
+            data,
+            row,
+            col,
+            stride: col,
+        }
+    }
+
+    /// The part of this view in rows `rows` and columns `cols`.
+    ///
+    /// Panics if either range is out of bounds, like slicing.
+    pub fn view(&self, rows: Range<usize>, cols: Range<usize>) -> MatrixView<'a, T> {
+        assert!(
+            rows.start <= rows.end && rows.end <= self.row,
+            "MatrixView rows {rows:?} out of bounds for {} rows",
+            self.row
+        );
+        assert!(
+            cols.start <= cols.end && cols.end <= self.col,
+            "MatrixView cols {cols:?} out of bounds for {} cols",
+            self.col
+        );
+        let (row, col) = (rows.len(), cols.len());
+        // an empty view borrows nothing, so its rows must not step into the parent
+        if row == 0 || col == 0 {
+            return MatrixView::new(&[], row, col);
+        }
+        let start = rows.start * self.stride + cols.start;
+        MatrixView {
+            data: &self.data[start..start + (row - 1) * self.stride + col],
+            row,
+            col,
+            stride: self.stride,
+        }
+    }
+
+    pub fn row(&self) -> usize {
+        self.row
+    }
+
+    pub fn col(&self) -> usize {
+        self.col
+    }
+
+    /// The element in row `i` and column `j`, or `None` out of bounds.
+    pub fn get(&self, i: usize, j: usize) -> Option<&'a T> {
+        (i < self.row && j < self.col).then(|| &self.data[i * self.stride + j])
+    }
+
+    /// The rows, each a slice of `col` elements of the parent buffer.
+    pub fn rows(&self) -> impl ExactSizeIterator<Item = &'a [T]> + '_ {
+        (0..self.row).map(move |i| &self.data[i * self.stride..][..self.col])
+    }
+
+    /// Copies the window into a matrix of its own.
+    pub fn to_matrix(&self) -> Matrix<T>
+    where
+        T: Clone,
+    {
+        Matrix::new(
+            self.rows().flatten().cloned().collect::<Vec<_>>(),
+            self.row,
+            self.col,
+        )
+    }
+
+    /// `self * other`, with the rows of the output split into one block per thread
+    /// once the product is large enough, or `DimensionMismatch` unless
+    /// `self.col == other.row`.
+    pub fn multiply(&self, other: &MatrixView<'_, T>) -> Result<Matrix<T>, MatrixError>
+    where
+        T: Mul<Output = T> + AddAssign + Default + Clone + Send + Sync,
+    {
+        if self.col != other.row {
+            return Err(MatrixError::DimensionMismatch {
+                left: (self.row, self.col),
+                right: (other.row, other.col),
+            });
+        }
+        let (k, n) = (self.col, other.col);
+        let mut data = vec![T::default(); self.row * n];
+        if data.is_empty() {
+            return Ok(Matrix::new(data, self.row, n));
+        }
+
+        // `other` column by column, as `columns` does for whole matrices
+        let mut columns = Vec::with_capacity(k * n);
+        for j in 0..n {
+            columns.extend(other.rows().map(|r| r[j].clone()));
+        }
+        let work = self.row.saturating_mul(k).saturating_mul(n);
+        par_row_blocks(&mut data, n, work, SERIAL_THRESHOLD, |i, out| {
+            let row = &self.data[i * self.stride..][..k];
+            for (j, cell) in out.iter_mut().enumerate() {
+                *cell = dot(row, &columns[j * k..(j + 1) * k]);
+            }
+        });
+        Ok(Matrix::new(data, self.row, n))
+    }
+}
+
+/// Panics out of bounds, `get` returns `None` instead.
+impl<T> Index<(usize, usize)> for MatrixView<'_, T> {
+    type Output = T;
+    fn index(&self, (i, j): (usize, usize)) -> &Self::Output {
+        let (row, col) = (self.row, self.col);
+        self.get(i, j)
+            .unwrap_or_else(|| panic!("MatrixView index ({i}, {j}) out of bounds for {row}x{col}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::multiply;
+
+    #[test]
+    fn test_matrix_view() {
+        let a = Matrix::from_fn(4, 5, |i, j| 10 * i + j);
+        let v = a.view(1..3, 2..5);
+        assert_eq!((v.row(), v.col()), (2, 3));
+        assert_eq!(v[(0, 0)], 12);
+        assert_eq!(v.get(1, 2), Some(&24));
+        assert_eq!(v.get(2, 0), None);
+        assert_eq!(v.rows().collect::<Vec<_>>(), [[12, 13, 14], [22, 23, 24]]);
+        assert_eq!(v.to_matrix(), Matrix::new([12, 13, 14, 22, 23, 24], 2, 3));
+
+        // a view of a view keeps the parent's stride
+        let w = v.view(1..2, 1..3);
+        assert_eq!(w.to_matrix(), Matrix::new([23, 24], 1, 2));
+        assert_eq!(a.view(2..2, 0..5).rows().len(), 0);
+        assert_eq!(a.view(0..4, 3..3).rows().next(), Some(&[][..]));
+    }
+
+    #[test]
+    fn test_matrix_view_multiply() {
+        let a = Matrix::from_fn(6, 6, |i, j| (i * 6 + j) as i64 % 7);
+        let (x, y) = (a.view(0..5, 1..4), a.view(2..5, 0..6));
+        let expected = multiply(&x.to_matrix(), &y.to_matrix()).unwrap();
+        assert_eq!(x.multiply(&y).unwrap(), expected);
+        assert_eq!(
+            a.as_view().multiply(&a.as_view()).unwrap(),
+            multiply(&a, &a).unwrap()
+        );
+
+        assert_eq!(
+            x.multiply(&x).unwrap_err(),
+            MatrixError::DimensionMismatch {
+                left: (5, 3),
+                right: (5, 3)
+            }
+        );
+
+        // big enough for the worker threads
+        let b = Matrix::from_fn(50, 50, |i, j| (i + 2 * j) as i64 % 9);
+        let (x, y) = (b.view(1..41, 5..45), b.view(10..50, 0..40));
+        let expected = multiply(&x.to_matrix(), &y.to_matrix()).unwrap();
+        assert_eq!(x.multiply(&y).unwrap(), expected);
+
+        // empty views keep no stride into the parent
+        let a = Matrix::from_fn(4, 5, |i, j| (10 * i + j) as i64);
+        let x = a.view(0..4, 3..3);
+        assert_eq!(x.to_matrix(), Matrix::new(Vec::new(), 4, 0));
+        assert_eq!(x.rows().count(), 4);
+        let c = x.multiply(&a.view(0..0, 0..5)).unwrap();
+        assert_eq!(c, Matrix::new(vec![0; 20], 4, 5));
+        let (x, y) = (a.view(0..4, 1..3), a.view(1..3, 2..2));
+        assert_eq!(x.multiply(&y).unwrap(), Matrix::new(Vec::new(), 4, 0));
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn test_matrix_view_out_of_bounds() {
+        Matrix::new([1, 2, 3, 4], 2, 2).view(0..1, 1..3);
+    }
+}
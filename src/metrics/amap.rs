@@ -1,16 +1,13 @@
-use anyhow::Result;
-use std::{
-    collections::HashMap,
-    fmt::Display,
-    sync::{
-        atomic::{AtomicI64, Ordering},
-        Arc,
-    },
-};
+use std::{collections::HashMap, fmt::Display};
+
+use super::{MetricsBackend, MetricsError, Transaction};
+use crate::sync::{Arc, AtomicI64, Ordering, RwLock};
 
 #[derive(Debug)]
 pub struct AmapMetrics {
     data: Arc<HashMap<&'static str, AtomicI64>>,
+    // held shared while a transaction applies, exclusively while a snapshot reads
+    commit: Arc<RwLock<()>>,
 }
 
 impl AmapMetrics {
@@ -21,16 +18,73 @@ impl AmapMetrics {
             .collect();
         Self {
             data: Arc::new(data),
+            commit: Arc::new(RwLock::new(())),
         }
     }
 
-    pub fn inc(&self, key: impl AsRef<str>) -> Result<()> {
-        let key = key.as_ref();
-        let counter = self
-            .data
+    pub fn inc(&self, key: impl AsRef<str>) -> Result<(), MetricsError> {
+        self.inc_by(key, 1)
+    }
+
+    pub fn inc_by(&self, key: impl AsRef<str>, n: i64) -> Result<(), MetricsError> {
+        self.counter(key.as_ref())?.fetch_add(n, Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub fn dec(&self, key: impl AsRef<str>) -> Result<(), MetricsError> {
+        self.inc_by(key, -1)
+    }
+
+    /// Overwrite the counter with `value`, a gauge rather than a running total.
+    pub fn set(&self, key: impl AsRef<str>, value: i64) -> Result<(), MetricsError> {
+        self.counter(key.as_ref())?.store(value, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn counter(&self, key: &str) -> Result<&AtomicI64, MetricsError> {
+        self.data
             .get(key)
-            .ok_or_else(|| anyhow::anyhow!("key {} not found", key))?;
-        counter.fetch_add(1, Ordering::Relaxed);
+            .ok_or_else(|| MetricsError::KeyNotFound(key.to_string()))
+    }
+}
+
+impl MetricsBackend for AmapMetrics {
+    fn inc(&self, key: &str) -> Result<(), MetricsError> {
+        AmapMetrics::inc(self, key)
+    }
+
+    fn inc_by(&self, key: &str, n: i64) -> Result<(), MetricsError> {
+        AmapMetrics::inc_by(self, key, n)
+    }
+
+    fn set(&self, key: &str, value: i64) -> Result<(), MetricsError> {
+        AmapMetrics::set(self, key, value)
+    }
+
+    fn snapshot(&self) -> Result<HashMap<String, i64>, MetricsError> {
+        let _commit = self.commit.write().unwrap_or_else(|e| e.into_inner());
+        Ok(self
+            .data
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.load(Ordering::Relaxed)))
+            .collect())
+    }
+
+    fn transaction<F>(&self, f: F) -> Result<(), MetricsError>
+    where
+        F: FnOnce(&mut Transaction),
+    {
+        let txn = Transaction::record(f);
+        // look every key up first so an unknown one leaves the counters untouched
+        let updates = txn
+            .updates()
+            .map(|(key, n)| Ok((self.counter(key)?, n)))
+            .collect::<Result<Vec<_>, MetricsError>>()?;
+
+        let _commit = self.commit.read().unwrap_or_else(|e| e.into_inner());
+        for (counter, n) in updates {
+            counter.fetch_add(n, Ordering::Relaxed);
+        }
         Ok(())
     }
 }
@@ -39,6 +93,7 @@ impl Clone for AmapMetrics {
     fn clone(&self) -> Self {
         AmapMetrics {
             data: Arc::clone(&self.data),
+            commit: Arc::clone(&self.commit),
         }
     }
 }
@@ -52,3 +107,49 @@ impl Display for AmapMetrics {
         Ok(())
     }
 }
+
+#[cfg(all(test, concurrency_loom))]
+mod loom_tests {
+    use super::*;
+    use loom::thread;
+
+    #[test]
+    fn loom_concurrent_inc_is_not_lost() {
+        loom::model(|| {
+            let metrics = AmapMetrics::new(&["req"]);
+
+            let handles = (0..2)
+                .map(|_| {
+                    let metrics = metrics.clone();
+                    thread::spawn(move || metrics.inc("req").unwrap())
+                })
+                .collect::<Vec<_>>();
+            for handle in handles {
+                handle.join().unwrap();
+            }
+
+            assert_eq!(metrics.data["req"].load(Ordering::Relaxed), 2);
+        });
+    }
+
+    #[test]
+    fn loom_snapshot_never_sees_half_a_transaction() {
+        loom::model(|| {
+            let metrics = AmapMetrics::new(&["req", "err"]);
+
+            let writer = {
+                let metrics = metrics.clone();
+                thread::spawn(move || {
+                    metrics
+                        .transaction(|txn| {
+                            txn.inc("req").inc("err");
+                        })
+                        .unwrap()
+                })
+            };
+            let snapshot = metrics.snapshot().unwrap();
+            assert_eq!(snapshot["req"], snapshot["err"]);
+            writer.join().unwrap();
+        });
+    }
+}
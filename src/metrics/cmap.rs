@@ -1,10 +1,17 @@
-use anyhow::Result;
 use dashmap::DashMap;
-use std::{fmt::Display, sync::Arc};
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    sync::{Arc, RwLock},
+};
+
+use super::{MetricsBackend, MetricsError, Transaction};
 
 #[derive(Debug, Clone)]
 pub struct CmapMetrics {
     data: Arc<DashMap<String, i64>>,
+    // held shared while a transaction applies, exclusively while a snapshot reads
+    commit: Arc<RwLock<()>>,
 }
 
 impl Default for CmapMetrics {
@@ -17,17 +24,71 @@ impl CmapMetrics {
     pub fn new() -> Self {
         Self {
             data: Arc::new(DashMap::new()),
+            commit: Arc::new(RwLock::new(())),
         }
     }
 
-    pub fn inc(&self, key: impl Into<String>) -> Result<()> {
+    pub fn inc(&self, key: impl Into<String>) -> Result<(), MetricsError> {
+        self.inc_by(key, 1)
+    }
+
+    pub fn inc_by(&self, key: impl Into<String>, n: i64) -> Result<(), MetricsError> {
         let mut count = self.data.entry(key.into()).or_insert(0);
-        *count += 1;
+        *count += n;
         Ok(())
     }
 
-    pub fn snapshot(&self) -> Result<DashMap<String, i64>> {
-        Ok((*self.data).clone())
+    pub fn dec(&self, key: impl Into<String>) -> Result<(), MetricsError> {
+        self.inc_by(key, -1)
+    }
+
+    /// Overwrite the counter with `value`, a gauge rather than a running total.
+    pub fn set(&self, key: impl Into<String>, value: i64) -> Result<(), MetricsError> {
+        self.data.insert(key.into(), value);
+        Ok(())
+    }
+
+    pub fn snapshot(&self) -> Result<DashMap<String, i64>, MetricsError> {
+        let _commit = self.commit.write().unwrap_or_else(|e| e.into_inner());
+        let snapshot = (*self.data).clone();
+        #[cfg(feature = "tracing")]
+        tracing::debug!(keys = snapshot.len(), "metrics snapshot");
+        Ok(snapshot)
+    }
+}
+
+impl MetricsBackend for CmapMetrics {
+    fn inc(&self, key: &str) -> Result<(), MetricsError> {
+        CmapMetrics::inc(self, key)
+    }
+
+    fn inc_by(&self, key: &str, n: i64) -> Result<(), MetricsError> {
+        CmapMetrics::inc_by(self, key, n)
+    }
+
+    fn set(&self, key: &str, value: i64) -> Result<(), MetricsError> {
+        CmapMetrics::set(self, key, value)
+    }
+
+    fn snapshot(&self) -> Result<HashMap<String, i64>, MetricsError> {
+        let _commit = self.commit.write().unwrap_or_else(|e| e.into_inner());
+        Ok(self
+            .data
+            .iter()
+            .map(|entry| (entry.key().clone(), *entry.value()))
+            .collect())
+    }
+
+    fn transaction<F>(&self, f: F) -> Result<(), MetricsError>
+    where
+        F: FnOnce(&mut Transaction),
+    {
+        let txn = Transaction::record(f);
+        let _commit = self.commit.read().unwrap_or_else(|e| e.into_inner());
+        for (key, n) in txn.updates() {
+            *self.data.entry(key.to_string()).or_insert(0) += n;
+        }
+        Ok(())
     }
 }
 
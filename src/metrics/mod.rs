@@ -1,5 +1,177 @@
 mod amap;
+#[cfg(feature = "metrics-dashmap")]
 mod cmap;
+pub mod stress;
+
+use std::collections::HashMap;
+use thiserror::Error;
 
 pub use amap::*;
+#[cfg(feature = "metrics-dashmap")]
 pub use cmap::*;
+
+/// A set of named counters shared between threads.
+///
+/// Cloning a backend gives another handle on the same counters.
+pub trait MetricsBackend: Clone + Send + Sync + 'static {
+    fn inc(&self, key: &str) -> Result<(), MetricsError>;
+
+    /// Add `n`, which may be negative, to a counter. By default a one-update
+    /// transaction.
+    fn inc_by(&self, key: &str, n: i64) -> Result<(), MetricsError> {
+        self.transaction(|txn| {
+            txn.add(key, n);
+        })
+    }
+
+    fn dec(&self, key: &str) -> Result<(), MetricsError> {
+        self.inc_by(key, -1)
+    }
+
+    /// Overwrite a counter with `value`, using it as a gauge, e.g. the current
+    /// queue length rather than a running total.
+    fn set(&self, key: &str, value: i64) -> Result<(), MetricsError>;
+
+    /// Every counter and its current value, read key by key, so the values of two
+    /// keys may come from slightly different moments. A transaction is never seen
+    /// half applied though.
+    fn snapshot(&self) -> Result<HashMap<String, i64>, MetricsError>;
+
+    /// Apply every update `f` records in one go, e.g.
+    /// `metrics.transaction(|txn| { txn.inc("req"); txn.add("err", n); })`, so
+    /// ratios between the keys are never read torn.
+    ///
+    /// Nothing is applied when one of the keys is unknown.
+    fn transaction<F>(&self, f: F) -> Result<(), MetricsError>
+    where
+        F: FnOnce(&mut Transaction);
+}
+
+/// The updates of one `MetricsBackend::transaction`, in the order they were recorded.
+#[derive(Debug, Default)]
+pub struct Transaction {
+    updates: Vec<(String, i64)>,
+}
+
+impl Transaction {
+    pub fn inc(&mut self, key: impl Into<String>) -> &mut Self {
+        self.add(key, 1)
+    }
+
+    pub fn dec(&mut self, key: impl Into<String>) -> &mut Self {
+        self.add(key, -1)
+    }
+
+    pub fn add(&mut self, key: impl Into<String>, n: i64) -> &mut Self {
+        self.updates.push((key.into(), n));
+        self
+    }
+
+    pub(crate) fn record<F: FnOnce(&mut Transaction)>(f: F) -> Self {
+        let mut txn = Self::default();
+        f(&mut txn);
+        txn
+    }
+
+    pub(crate) fn updates(&self) -> impl Iterator<Item = (&str, i64)> {
+        self.updates.iter().map(|(key, n)| (key.as_str(), *n))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum MetricsError {
+    /// `AmapMetrics` only counts the names it was created with
+    #[error("Metrics error: key {0} not found")]
+    KeyNotFound(String),
+}
+
+#[cfg(all(test, not(concurrency_loom)))]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    // every transaction adds one request and, for every other one, an error
+    fn check_never_torn<B: MetricsBackend>(metrics: B) {
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for i in 0..2_000 {
+                        metrics
+                            .transaction(|txn| {
+                                txn.inc("req").add("err", i % 2).add("err", 1 - i % 2);
+                            })
+                            .unwrap();
+                    }
+                });
+            }
+            s.spawn(|| {
+                for _ in 0..500 {
+                    let snapshot = metrics.snapshot().unwrap();
+                    let count = |key| snapshot.get(key).copied().unwrap_or(0);
+                    assert_eq!(count("req"), count("err"));
+                }
+            });
+        });
+        assert_eq!(metrics.snapshot().unwrap()["req"], 8_000);
+    }
+
+    fn check_gauges<B: MetricsBackend>(metrics: B) {
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..1_000 {
+                        metrics.inc_by("req", 3).unwrap();
+                        metrics.dec("req").unwrap();
+                    }
+                });
+            }
+        });
+        assert_eq!(metrics.snapshot().unwrap()["req"], 8_000);
+
+        metrics.set("err", 42).unwrap();
+        metrics.inc("err").unwrap();
+        metrics
+            .transaction(|txn| {
+                txn.dec("err").dec("err");
+            })
+            .unwrap();
+        assert_eq!(metrics.snapshot().unwrap()["err"], 41);
+        metrics.set("err", -5).unwrap();
+        assert_eq!(metrics.snapshot().unwrap()["err"], -5);
+    }
+
+    #[test]
+    fn test_amap_gauges() {
+        let metrics = AmapMetrics::new(&["req", "err"]);
+        check_gauges(metrics.clone());
+        assert_eq!(
+            metrics.set("missing", 1),
+            Err(MetricsError::KeyNotFound("missing".to_string()))
+        );
+        assert!(metrics.inc_by("missing", 2).is_err());
+    }
+
+    #[cfg(feature = "metrics-dashmap")]
+    #[test]
+    fn test_cmap_gauges() {
+        check_gauges(CmapMetrics::new());
+    }
+
+    #[test]
+    fn test_amap_transaction() {
+        let metrics = AmapMetrics::new(&["req", "err"]);
+        check_never_torn(metrics.clone());
+
+        let rst = metrics.transaction(|txn| {
+            txn.inc("req").inc("missing");
+        });
+        assert_eq!(rst, Err(MetricsError::KeyNotFound("missing".to_string())));
+        assert_eq!(MetricsBackend::snapshot(&metrics).unwrap()["req"], 8_000);
+    }
+
+    #[cfg(feature = "metrics-dashmap")]
+    #[test]
+    fn test_cmap_transaction() {
+        check_never_torn(CmapMetrics::new());
+    }
+}
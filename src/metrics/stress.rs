@@ -0,0 +1,239 @@
+//! Hammer a `MetricsBackend` from many threads and check that no increment is lost.
+//!
+//! Meant for tests of new backends, e.g.
+//! `stress::run(&MyMetrics::new(), &["a", "b"], &StressConfig::default())`.
+
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+};
+use thiserror::Error;
+
+use super::{MetricsBackend, MetricsError};
+
+#[derive(Debug, Clone)]
+pub struct StressConfig {
+    /// threads calling `inc` at the same time
+    pub threads: usize,
+    /// `inc` calls per thread, spread round-robin over the keys
+    pub increments: usize,
+    /// take snapshots on another thread while the increments run
+    pub concurrent_snapshots: bool,
+}
+
+impl Default for StressConfig {
+    fn default() -> Self {
+        Self {
+            threads: 8,
+            increments: 10_000,
+            concurrent_snapshots: true,
+        }
+    }
+}
+
+/// What a successful run did.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StressReport {
+    pub increments: usize,
+    pub snapshots: usize,
+}
+
+#[derive(Debug, Error)]
+pub enum StressError {
+    #[error("Stress error: no keys to increment")]
+    NoKeys,
+    #[error("Stress error: {0}")]
+    Metrics(#[from] MetricsError),
+    #[error("Stress error: {key} is {actual}, expected {expected}")]
+    CountMismatch {
+        key: String,
+        expected: i64,
+        actual: i64,
+    },
+    #[error("Stress error: a snapshot saw {key} go back from {before} to {after}")]
+    WentBackwards {
+        key: String,
+        before: i64,
+        after: i64,
+    },
+    #[error("Stress error: a snapshot saw {key} at {value}, past its final {expected}")]
+    Overshoot {
+        key: String,
+        value: i64,
+        expected: i64,
+    },
+    #[error("Stress error: a thread panicked")]
+    Panicked,
+}
+
+/// Run `config.threads` threads, each calling `inc` `config.increments` times over `keys`,
+/// then check every key grew by exactly the number of calls it got.
+///
+/// Counts already in `backend` are taken as the baseline. Concurrent snapshots must
+/// never see a key go backwards or past its final value.
+pub fn run<B: MetricsBackend>(
+    backend: &B,
+    keys: &[&str],
+    config: &StressConfig,
+) -> Result<StressReport, StressError> {
+    if keys.is_empty() {
+        return Err(StressError::NoKeys);
+    }
+
+    let baseline = backend.snapshot()?;
+    let count = |key: &str, snapshot: &HashMap<String, i64>| *snapshot.get(key).unwrap_or(&0);
+    let mut expected = keys
+        .iter()
+        .map(|&key| (key, count(key, &baseline)))
+        .collect::<HashMap<_, _>>();
+    for t in 0..config.threads {
+        for i in 0..config.increments {
+            *expected.entry(keys[(t + i) % keys.len()]).or_default() += 1;
+        }
+    }
+
+    let done = AtomicBool::new(false);
+    let snapshots = thread::scope(|s| {
+        let checker = config.concurrent_snapshots.then(|| {
+            s.spawn(|| {
+                let mut last = baseline.clone();
+                let mut taken = 0;
+                while !done.load(Ordering::Acquire) {
+                    let snapshot = backend.snapshot()?;
+                    for (&key, &final_value) in expected.iter() {
+                        let (before, value) = (count(key, &last), count(key, &snapshot));
+                        if value < before {
+                            let key = key.to_string();
+                            let after = value;
+                            return Err(StressError::WentBackwards { key, before, after });
+                        }
+                        if value > final_value {
+                            let key = key.to_string();
+                            let expected = final_value;
+                            return Err(StressError::Overshoot {
+                                key,
+                                value,
+                                expected,
+                            });
+                        }
+                    }
+                    last = snapshot;
+                    taken += 1;
+                }
+                Ok(taken)
+            })
+        });
+
+        let workers = (0..config.threads)
+            .map(|t| {
+                s.spawn(move || {
+                    for i in 0..config.increments {
+                        backend.inc(keys[(t + i) % keys.len()])?;
+                    }
+                    Ok::<_, StressError>(())
+                })
+            })
+            .collect::<Vec<_>>();
+        let rst = workers
+            .into_iter()
+            .try_for_each(|h| h.join().map_err(|_| StressError::Panicked)?);
+        done.store(true, Ordering::Release);
+
+        let taken = match checker {
+            Some(h) => h.join().map_err(|_| StressError::Panicked)??,
+            None => 0,
+        };
+        rst.map(|_| taken)
+    })?;
+
+    let last = backend.snapshot()?;
+    for (&key, &expected) in expected.iter() {
+        let actual = count(key, &last);
+        if actual != expected {
+            let key = key.to_string();
+            return Err(StressError::CountMismatch {
+                key,
+                expected,
+                actual,
+            });
+        }
+    }
+
+    Ok(StressReport {
+        increments: config.threads * config.increments,
+        snapshots,
+    })
+}
+
+#[cfg(all(test, not(concurrency_loom)))]
+mod tests {
+    use super::*;
+    use crate::{AmapMetrics, Transaction};
+    use std::sync::{atomic::AtomicUsize, Arc};
+
+    const KEYS: [&str; 3] = ["req.page.1", "req.page.2", "req.page.3"];
+
+    #[test]
+    fn test_stress_amap() -> Result<(), StressError> {
+        let metrics = AmapMetrics::new(&KEYS);
+        metrics.inc("req.page.1")?;
+        let report = run(&metrics, &KEYS, &StressConfig::default())?;
+        assert_eq!(report.increments, 80_000);
+        Ok(())
+    }
+
+    #[cfg(feature = "metrics-dashmap")]
+    #[test]
+    fn test_stress_cmap() -> Result<(), StressError> {
+        run(&crate::CmapMetrics::new(), &KEYS, &StressConfig::default())?;
+        Ok(())
+    }
+
+    // drops every 100th increment
+    #[derive(Clone)]
+    struct LossyMetrics {
+        inner: AmapMetrics,
+        calls: Arc<AtomicUsize>,
+    }
+
+    impl MetricsBackend for LossyMetrics {
+        fn inc(&self, key: &str) -> Result<(), MetricsError> {
+            if self.calls.fetch_add(1, Ordering::Relaxed) % 100 == 99 {
+                return Ok(());
+            }
+            self.inner.inc(key)
+        }
+
+        fn set(&self, key: &str, value: i64) -> Result<(), MetricsError> {
+            self.inner.set(key, value)
+        }
+
+        fn snapshot(&self) -> Result<HashMap<String, i64>, MetricsError> {
+            MetricsBackend::snapshot(&self.inner)
+        }
+
+        fn transaction<F>(&self, f: F) -> Result<(), MetricsError>
+        where
+            F: FnOnce(&mut Transaction),
+        {
+            self.inner.transaction(f)
+        }
+    }
+
+    #[test]
+    fn test_stress_catches_lost_increments() {
+        let metrics = LossyMetrics {
+            inner: AmapMetrics::new(&KEYS),
+            calls: Arc::new(AtomicUsize::new(0)),
+        };
+        assert!(matches!(
+            run(&metrics, &KEYS, &StressConfig::default()),
+            Err(StressError::CountMismatch { .. })
+        ));
+        assert!(matches!(
+            run(&metrics, &[], &StressConfig::default()),
+            Err(StressError::NoKeys)
+        ));
+    }
+}
@@ -0,0 +1,180 @@
+use std::{
+    io,
+    mem::size_of,
+    ops::{Add, AddAssign, Mul},
+    panic::{self, AssertUnwindSafe},
+    ptr, slice,
+};
+
+use crate::matrix::row_major_operands;
+use crate::{ChannelError, Error, Matrix, MatrixError};
+
+/// Multiply `a` by `b` in `processes` forked worker processes.
+///
+/// The product lives in an anonymous shared mapping, every child computes a block
+/// of rows straight into it and reports back through its exit status, so the
+/// workers never contend on a lock or a channel.
+///
+/// A child only runs `T`'s `Mul` and `AddAssign`, and allocates nothing as long as
+/// they don't panic. A panic in a child, e.g. an integer overflow in a debug build,
+/// allocates its payload and runs the panic hook. If another thread of the parent
+/// held the allocator or a hook lock at the fork, that child deadlocks. So call this
+/// from a single-threaded process, or only with element types whose arithmetic
+/// cannot panic, such as floats.
+pub fn multiply_multiprocess<T>(
+    a: &Matrix<T>,
+    b: &Matrix<T>,
+    processes: usize,
+) -> Result<Matrix<T>, Error>
+where
+    T: Mul<Output = T> + Add<Output = T> + AddAssign + Default + Copy,
+{
+    let (a, b) = &row_major_operands(a, b)?;
+    if processes == 0 {
+        return Err(MatrixError::NoWorkers.into());
+    }
+
+    let (m, k, n) = (a.row(), a.col(), b.col());
+    let len = m * n;
+    if len == 0 || size_of::<T>() == 0 {
+        return Ok(Matrix::new(vec![T::default(); len], m, n));
+    }
+
+    let out = SharedBuf::<T>::new(len)?;
+    let (a_data, b_data) = (a.as_slice(), b.as_slice());
+    let processes = processes.min(m);
+    let block_rows = m.div_ceil(processes);
+
+    // map phase: one child per block of rows
+    let mut children = Vec::with_capacity(processes);
+    let mut fork_err = None;
+    for start in (0..m).step_by(block_rows) {
+        let end = (start + block_rows).min(m);
+        // SAFETY: the child only does arithmetic and writes into the shared mapping
+        // before _exit, it never returns into the parent's code. See the doc comment
+        // for the panic a child may still run into
+        let pid = unsafe { libc::fork() };
+        match pid {
+            -1 => {
+                fork_err = Some(io::Error::last_os_error());
+                break;
+            }
+            0 => {
+                // a panic must not unwind into the parent's code running in the child
+                let rst = panic::catch_unwind(AssertUnwindSafe(|| {
+                    for i in start..end {
+                        for j in 0..n {
+                            let mut sum = T::default();
+                            for p in 0..k {
+                                sum += a_data[i * k + p] * b_data[p * n + j];
+                            }
+                            // SAFETY: i * n + j < m * n and no other process writes row i
+                            unsafe { out.ptr.add(i * n + j).write(sum) };
+                        }
+                    }
+                }));
+                // SAFETY: leave without running destructors or atexit handlers of the parent
+                unsafe { libc::_exit(if rst.is_ok() { 0 } else { 1 }) };
+            }
+            pid => children.push(pid),
+        }
+    }
+
+    // reduce phase: wait for every child, even after a failure, so none is left behind
+    let mut failed = false;
+    for pid in children {
+        let mut status = 0;
+        // SAFETY: pid is a child we forked and have not waited for yet
+        let rst = unsafe { libc::waitpid(pid, &mut status, 0) };
+        if rst != pid || !libc::WIFEXITED(status) || libc::WEXITSTATUS(status) != 0 {
+            failed = true;
+        }
+    }
+    if let Some(e) = fork_err {
+        return Err(ChannelError::Io(e).into());
+    }
+    if failed {
+        return Err(MatrixError::WorkerFailed.into());
+    }
+
+    Ok(Matrix::new(out.as_slice().to_vec(), m, n))
+}
+
+// an anonymous MAP_SHARED mapping holding `len` values of T
+struct SharedBuf<T> {
+    ptr: *mut T,
+    len: usize,
+}
+
+impl<T: Copy + Default> SharedBuf<T> {
+    fn new(len: usize) -> Result<Self, ChannelError> {
+        let bytes = len * size_of::<T>();
+        // SAFETY: a fresh anonymous mapping, not aliased by anything else
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                bytes,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        let ptr = ptr as *mut T;
+        for i in 0..len {
+            // SAFETY: page aligned and `bytes` long, so every slot is in bounds and aligned
+            unsafe { ptr.add(i).write(T::default()) };
+        }
+        Ok(Self { ptr, len })
+    }
+
+    fn as_slice(&self) -> &[T] {
+        // SAFETY: initialized in new, children are gone by the time we read
+        unsafe { slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl<T> Drop for SharedBuf<T> {
+    fn drop(&mut self) {
+        // SAFETY: unmapping exactly what new mapped
+        unsafe { libc::munmap(self.ptr as *mut libc::c_void, self.len * size_of::<T>()) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::multiply;
+
+    #[test]
+    fn test_multiply_multiprocess() -> Result<(), Error> {
+        let a = Matrix::new((1..=35).collect::<Vec<i64>>(), 7, 5);
+        let b = Matrix::new((1..=20).collect::<Vec<i64>>(), 5, 4);
+        let expected = multiply(&a, &b)?;
+        for processes in [1, 3, 16] {
+            let c = multiply_multiprocess(&a, &b, processes)?;
+            assert_eq!((c.row(), c.col()), (7, 4));
+            assert_eq!(c.as_slice(), expected.as_slice());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_multiply_multiprocess_errors() {
+        let a = Matrix::new([1, 2, 3, 4, 5, 6], 2, 3);
+        assert!(matches!(
+            multiply_multiprocess(&a, &a, 2),
+            Err(Error::Matrix(MatrixError::DimensionMismatch { .. }))
+        ));
+
+        let b = Matrix::new([1, 2, 3], 3, 1);
+        assert!(matches!(
+            multiply_multiprocess(&a, &b, 0),
+            Err(Error::Matrix(MatrixError::NoWorkers))
+        ));
+    }
+}
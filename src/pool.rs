@@ -0,0 +1,636 @@
+use std::{
+    cmp::Ordering as CmpOrdering,
+    collections::{BinaryHeap, HashMap},
+    fmt::{self, Display},
+    mem,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, AtomicI64, Ordering},
+        Arc, Condvar, Mutex, MutexGuard, OnceLock, RwLock,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{MetricsBackend, MetricsError, Transaction};
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+static GLOBAL: OnceLock<ThreadPool> = OnceLock::new();
+
+/// Which queued job a free worker picks first, jobs of the same priority run in order.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// batch work that may wait
+    Low,
+    #[default]
+    Normal,
+    /// interactive work that should not queue up behind a batch
+    High,
+}
+
+/// A fixed set of long-lived worker threads running closures from a shared queue.
+///
+/// Dropping the pool lets the workers finish the queued jobs, then joins them, like
+/// `drain`. `shutdown_now` throws the queued jobs away instead.
+pub struct ThreadPool {
+    shared: Arc<Shared>,
+    workers: Vec<JoinHandle<()>>,
+    reporter: Option<JoinHandle<()>>,
+}
+
+/// Knobs for `ThreadPool::with_config`, `ThreadPool::new` uses the default log interval.
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    pub threads: usize,
+    /// print the pool metrics on one line every interval, never when None
+    pub log_interval: Option<Duration>,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            threads: thread::available_parallelism().map_or(4, |n| n.get()),
+            log_interval: None,
+        }
+    }
+}
+
+/// Live counters of a `ThreadPool`, cloning gives another handle on the same counters.
+///
+/// A pool is queue-bound when `pool.queue.depth` stays high and `pool.tasks.wait_us`
+/// grows faster than `pool.tasks.run_us`, CPU-bound when every worker is busy and the
+/// time goes into running.
+#[derive(Debug, Clone, Default)]
+pub struct PoolMetrics {
+    counters: Arc<PoolCounters>,
+}
+
+/// A flag long jobs poll to stop early, set by `cancel`. The pool's own token, from
+/// `ThreadPool::cancel_token`, is set by `ThreadPool::shutdown_now`.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+/// What happened to the jobs of a pool that was shut down.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ShutdownStats {
+    /// jobs that returned, over the whole life of the pool
+    pub completed: u64,
+    /// jobs that panicked, over the whole life of the pool
+    pub panicked: u64,
+    /// queued jobs thrown away without running
+    pub dropped: u64,
+    /// workers still in a job when the timeout ran out, left running detached
+    pub detached: usize,
+}
+
+#[derive(Debug, Default)]
+struct PoolCounters {
+    queue_depth: AtomicI64,
+    busy_workers: AtomicI64,
+    completed: AtomicI64,
+    panicked: AtomicI64,
+    // total microseconds jobs spent queued and running
+    wait_us: AtomicI64,
+    run_us: AtomicI64,
+    // held shared while a transaction applies, exclusively while a snapshot reads
+    commit: RwLock<()>,
+}
+
+struct Shared {
+    state: Mutex<PoolState>,
+    // signalled when a job is queued or the pool closes
+    available: Condvar,
+    // signalled when the pool closes, wakes the reporter, and when a worker exits
+    closing: Condvar,
+    metrics: PoolMetrics,
+    cancel: CancelToken,
+}
+
+struct PoolState {
+    jobs: BinaryHeap<QueuedJob>,
+    // tie breaker keeping jobs of the same priority in order
+    next_seq: u64,
+    closed: bool,
+    // workers that have not exited yet
+    live: usize,
+}
+
+struct QueuedJob {
+    priority: Priority,
+    seq: u64,
+    queued_at: Instant,
+    job: Job,
+}
+
+impl ThreadPool {
+    pub fn new(threads: usize) -> Self {
+        Self::with_config(PoolConfig {
+            threads,
+            ..Default::default()
+        })
+    }
+
+    /// Panics when `config.threads` is 0, like a pool that could never run a job.
+    pub fn with_config(config: PoolConfig) -> Self {
+        assert!(
+            config.threads > 0,
+            "a thread pool needs at least one thread"
+        );
+
+        let shared = Arc::new(Shared {
+            state: Mutex::new(PoolState {
+                jobs: BinaryHeap::new(),
+                next_seq: 0,
+                closed: false,
+                live: config.threads,
+            }),
+            available: Condvar::new(),
+            closing: Condvar::new(),
+            metrics: PoolMetrics::default(),
+            cancel: CancelToken::default(),
+        });
+        let workers = (0..config.threads)
+            .map(|_| {
+                let shared = Arc::clone(&shared);
+                thread::spawn(move || shared.work())
+            })
+            .collect();
+        let reporter = config.log_interval.map(|interval| {
+            let shared = Arc::clone(&shared);
+            thread::spawn(move || shared.report(interval))
+        });
+
+        Self {
+            shared,
+            workers,
+            reporter,
+        }
+    }
+
+    /// The pool shared by the whole process, with the default config. It is never dropped.
+    pub fn global() -> &'static ThreadPool {
+        GLOBAL.get_or_init(|| ThreadPool::with_config(PoolConfig::default()))
+    }
+
+    /// Queue `f` to run on one of the workers.
+    pub fn execute<F>(&self, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.execute_with_priority(Priority::Normal, f);
+    }
+
+    /// Queue `f` ahead of every job with a lower priority.
+    pub fn execute_with_priority<F>(&self, priority: Priority, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let mut state = self.shared.lock();
+        let seq = state.next_seq;
+        state.next_seq += 1;
+        state.jobs.push(QueuedJob {
+            priority,
+            seq,
+            queued_at: Instant::now(),
+            job: Box::new(f),
+        });
+        add(&self.shared.metrics.counters.queue_depth, 1);
+        self.shared.available.notify_one();
+    }
+
+    pub fn threads(&self) -> usize {
+        self.workers.len()
+    }
+
+    pub fn metrics(&self) -> PoolMetrics {
+        self.shared.metrics.clone()
+    }
+
+    /// The token `shutdown_now` cancels, for jobs to check between chunks of work.
+    pub fn cancel_token(&self) -> CancelToken {
+        self.shared.cancel.clone()
+    }
+
+    /// Stop taking jobs, run everything already queued, then join the workers.
+    pub fn drain(mut self) -> ShutdownStats {
+        self.close();
+        self.join();
+        self.stats(0, 0)
+    }
+
+    /// Stop taking jobs, drop everything still queued and cancel the `cancel_token`,
+    /// then wait up to `timeout` for the running jobs to return.
+    ///
+    /// Workers stuck in a job past the timeout are detached, they exit once it returns.
+    pub fn shutdown_now(mut self, timeout: Duration) -> ShutdownStats {
+        let dropped = mem::take(&mut self.shared.lock().jobs);
+        add(
+            &self.shared.metrics.counters.queue_depth,
+            -(dropped.len() as i64),
+        );
+        self.shared.cancel.cancel();
+        self.close();
+
+        let state = self.shared.lock();
+        let detached = self
+            .shared
+            .closing
+            .wait_timeout_while(state, timeout, |state| state.live > 0)
+            .unwrap_or_else(|e| e.into_inner())
+            .0
+            .live;
+        if detached > 0 {
+            // dropping a handle detaches its thread
+            self.workers.retain(|worker| worker.is_finished());
+        }
+        self.join();
+        self.stats(dropped.len() as u64, detached)
+    }
+
+    fn close(&self) {
+        self.shared.lock().closed = true;
+        self.shared.available.notify_all();
+        self.shared.closing.notify_all();
+    }
+
+    fn join(&mut self) {
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+        if let Some(reporter) = self.reporter.take() {
+            let _ = reporter.join();
+        }
+    }
+
+    fn stats(&self, dropped: u64, detached: usize) -> ShutdownStats {
+        let counters = &self.shared.metrics.counters;
+        let count = |counter: &AtomicI64| counter.load(Ordering::Relaxed).max(0) as u64;
+        ShutdownStats {
+            completed: count(&counters.completed),
+            panicked: count(&counters.panicked),
+            dropped,
+            detached,
+        }
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        self.close();
+        self.join();
+    }
+}
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Release);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Acquire)
+    }
+}
+
+impl Shared {
+    // a job never runs while the lock is held, so a poisoned lock still has a sane state
+    fn lock(&self) -> MutexGuard<'_, PoolState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn work(&self) {
+        let counters = &self.metrics.counters;
+        loop {
+            let QueuedJob { job, queued_at, .. } = {
+                let mut state = self.lock();
+                loop {
+                    if let Some(job) = state.jobs.pop() {
+                        break job;
+                    }
+                    if state.closed {
+                        state.live -= 1;
+                        self.closing.notify_all();
+                        return;
+                    }
+                    state = self
+                        .available
+                        .wait(state)
+                        .unwrap_or_else(|e| e.into_inner());
+                }
+            };
+
+            let started = Instant::now();
+            add(&counters.queue_depth, -1);
+            add(&counters.busy_workers, 1);
+            add(&counters.wait_us, micros(started - queued_at));
+
+            // a panicking job must not take the worker down with it
+            let rst = panic::catch_unwind(AssertUnwindSafe(job));
+
+            add(&counters.run_us, micros(started.elapsed()));
+            add(&counters.busy_workers, -1);
+            match rst {
+                Ok(()) => add(&counters.completed, 1),
+                Err(_) => add(&counters.panicked, 1),
+            }
+        }
+    }
+
+    fn report(&self, interval: Duration) {
+        let mut state = self.lock();
+        while !state.closed {
+            state = self
+                .closing
+                .wait_timeout(state, interval)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+            #[cfg(feature = "tracing")]
+            tracing::info!(metrics = %self.metrics, "thread pool");
+            #[cfg(not(feature = "tracing"))]
+            eprintln!("thread pool: {}", self.metrics);
+        }
+    }
+}
+
+// max-heap order: higher priority first, then lower seq first
+impl Ord for QueuedJob {
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+impl PartialOrd for QueuedJob {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for QueuedJob {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == CmpOrdering::Equal
+    }
+}
+
+impl Eq for QueuedJob {}
+
+impl PoolMetrics {
+    const KEYS: [&'static str; 6] = [
+        "pool.queue.depth",
+        "pool.workers.busy",
+        "pool.tasks.completed",
+        "pool.tasks.panicked",
+        "pool.tasks.wait_us",
+        "pool.tasks.run_us",
+    ];
+
+    fn counter(&self, key: &str) -> Option<&AtomicI64> {
+        let c = &self.counters;
+        let counter = match key {
+            "pool.queue.depth" => &c.queue_depth,
+            "pool.workers.busy" => &c.busy_workers,
+            "pool.tasks.completed" => &c.completed,
+            "pool.tasks.panicked" => &c.panicked,
+            "pool.tasks.wait_us" => &c.wait_us,
+            "pool.tasks.run_us" => &c.run_us,
+            _ => return None,
+        };
+        Some(counter)
+    }
+
+    /// Current value of one of the `pool.*` counters.
+    pub fn get(&self, key: &str) -> Option<i64> {
+        self.counter(key).map(|c| c.load(Ordering::Relaxed))
+    }
+}
+
+impl MetricsBackend for PoolMetrics {
+    fn inc(&self, key: &str) -> Result<(), MetricsError> {
+        let counter = self
+            .counter(key)
+            .ok_or_else(|| MetricsError::KeyNotFound(key.to_string()))?;
+        add(counter, 1);
+        Ok(())
+    }
+
+    fn set(&self, key: &str, value: i64) -> Result<(), MetricsError> {
+        let counter = self
+            .counter(key)
+            .ok_or_else(|| MetricsError::KeyNotFound(key.to_string()))?;
+        counter.store(value, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn snapshot(&self) -> Result<HashMap<String, i64>, MetricsError> {
+        let _commit = self
+            .counters
+            .commit
+            .write()
+            .unwrap_or_else(|e| e.into_inner());
+        Ok(Self::KEYS
+            .iter()
+            .map(|&key| (key.to_string(), self.get(key).unwrap_or_default()))
+            .collect())
+    }
+
+    fn transaction<F>(&self, f: F) -> Result<(), MetricsError>
+    where
+        F: FnOnce(&mut Transaction),
+    {
+        let txn = Transaction::record(f);
+        let updates = txn
+            .updates()
+            .map(|(key, n)| {
+                let counter = self
+                    .counter(key)
+                    .ok_or_else(|| MetricsError::KeyNotFound(key.to_string()))?;
+                Ok((counter, n))
+            })
+            .collect::<Result<Vec<_>, MetricsError>>()?;
+
+        let _commit = self
+            .counters
+            .commit
+            .read()
+            .unwrap_or_else(|e| e.into_inner());
+        for (counter, n) in updates {
+            add(counter, n);
+        }
+        Ok(())
+    }
+}
+
+// one line, e.g. `pool.queue.depth=0 pool.workers.busy=2 ...`
+impl Display for PoolMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, key) in Self::KEYS.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}={}", key, self.get(key).unwrap_or_default())?;
+        }
+        Ok(())
+    }
+}
+
+fn add(counter: &AtomicI64, n: i64) {
+    counter.fetch_add(n, Ordering::Relaxed);
+}
+
+fn micros(d: Duration) -> i64 {
+    i64::try_from(d.as_micros()).unwrap_or(i64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_pool_runs_jobs() {
+        let pool = ThreadPool::new(3);
+        let (tx, rx) = mpsc::channel();
+        for i in 0..10 {
+            let tx = tx.clone();
+            pool.execute(move || tx.send(i * i).unwrap());
+        }
+        drop(tx);
+
+        let mut squares = rx.iter().collect::<Vec<_>>();
+        squares.sort();
+        assert_eq!(squares, (0..10).map(|i| i * i).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_pool_metrics() -> Result<(), MetricsError> {
+        let pool = ThreadPool::new(2);
+        let metrics = pool.metrics();
+
+        // hold both workers so the rest has to queue up
+        let (release, wait) = mpsc::channel::<()>();
+        let wait = Arc::new(Mutex::new(wait));
+        let (started, running) = mpsc::channel();
+        for _ in 0..2 {
+            let (wait, started) = (Arc::clone(&wait), started.clone());
+            pool.execute(move || {
+                started.send(()).unwrap();
+                let _ = wait.lock().unwrap().recv();
+            });
+        }
+        running.recv().unwrap();
+        running.recv().unwrap();
+        pool.execute(|| {});
+        pool.execute(|| panic!("boom"));
+
+        assert_eq!(metrics.get("pool.workers.busy"), Some(2));
+        assert_eq!(metrics.get("pool.queue.depth"), Some(2));
+
+        drop(release);
+        drop(pool);
+        let snapshot = metrics.snapshot()?;
+        assert_eq!(snapshot["pool.queue.depth"], 0);
+        assert_eq!(snapshot["pool.workers.busy"], 0);
+        assert_eq!(snapshot["pool.tasks.completed"], 3);
+        assert_eq!(snapshot["pool.tasks.panicked"], 1);
+        assert!(metrics.to_string().starts_with("pool.queue.depth=0 "));
+        Ok(())
+    }
+
+    #[test]
+    fn test_pool_priority() {
+        let pool = ThreadPool::new(1);
+
+        // keep the only worker busy while the jobs queue up
+        let (release, wait) = mpsc::channel::<()>();
+        let (started, running) = mpsc::channel();
+        pool.execute(move || {
+            started.send(()).unwrap();
+            let _ = wait.recv();
+        });
+        running.recv().unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        for (priority, name) in [
+            (Priority::Low, "low"),
+            (Priority::Normal, "normal 1"),
+            (Priority::High, "high"),
+            (Priority::Normal, "normal 2"),
+        ] {
+            let tx = tx.clone();
+            pool.execute_with_priority(priority, move || tx.send(name).unwrap());
+        }
+        drop(tx);
+        drop(release);
+
+        let order = rx.iter().collect::<Vec<_>>();
+        assert_eq!(order, ["high", "normal 1", "normal 2", "low"]);
+    }
+
+    // queue `jobs` behind a job holding the only worker until `release` is dropped
+    fn blocked_pool(jobs: usize) -> (ThreadPool, mpsc::Sender<()>) {
+        let pool = ThreadPool::new(1);
+        let (release, wait) = mpsc::channel::<()>();
+        let (started, running) = mpsc::channel();
+        pool.execute(move || {
+            started.send(()).unwrap();
+            let _ = wait.recv();
+        });
+        running.recv().unwrap();
+        for _ in 0..jobs {
+            pool.execute(|| {});
+        }
+        (pool, release)
+    }
+
+    #[test]
+    fn test_pool_drain() {
+        let (pool, release) = blocked_pool(5);
+        pool.execute(|| panic!("boom"));
+        drop(release);
+
+        let stats = pool.drain();
+        assert_eq!(
+            stats,
+            ShutdownStats {
+                completed: 6,
+                panicked: 1,
+                dropped: 0,
+                detached: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_pool_shutdown_now() {
+        let (pool, release) = blocked_pool(0);
+        let token = pool.cancel_token();
+        let (started, running) = mpsc::channel();
+        pool.execute(move || {
+            started.send(()).unwrap();
+            while !token.is_cancelled() {
+                thread::sleep(Duration::from_millis(1));
+            }
+        });
+        for _ in 0..3 {
+            pool.execute(|| {});
+        }
+        let metrics = pool.metrics();
+        drop(release);
+        running.recv().unwrap();
+
+        let stats = pool.shutdown_now(Duration::from_secs(5));
+        assert_eq!((stats.completed, stats.dropped, stats.detached), (2, 3, 0));
+        assert_eq!(metrics.get("pool.queue.depth"), Some(0));
+    }
+
+    #[test]
+    fn test_pool_shutdown_now_detaches_stuck_workers() {
+        let (pool, release) = blocked_pool(1);
+        let stats = pool.shutdown_now(Duration::from_millis(20));
+        assert_eq!((stats.completed, stats.dropped, stats.detached), (0, 1, 1));
+        drop(release);
+    }
+}
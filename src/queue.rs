@@ -0,0 +1,407 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::sync::{Arc, Condvar, Mutex};
+use crate::ChannelError;
+
+/// A work queue backed by an append-only write-ahead log.
+///
+/// Every `push` and `ack` is appended to the log before it takes effect in memory,
+/// so when the process dies the jobs that were pushed but never acked are replayed
+/// by the next `open` on the same path.
+#[derive(Debug)]
+pub struct PersistentQueue<T> {
+    inner: Arc<(Mutex<QueueState<T>>, Condvar)>,
+}
+
+#[derive(Debug)]
+pub struct Job<T> {
+    pub id: u64,
+    pub data: T,
+}
+
+#[derive(Debug)]
+struct QueueState<T> {
+    path: PathBuf,
+    log: File,
+    next_id: u64,
+    // jobs waiting to be popped
+    ready: VecDeque<u64>,
+    // every job not acked yet, popped or not
+    pending: BTreeMap<u64, T>,
+}
+
+// one line of the log
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum Record<T> {
+    Push { id: u64, job: T },
+    Ack { id: u64 },
+}
+
+impl<T> PersistentQueue<T>
+where
+    T: Serialize + DeserializeOwned + Clone,
+{
+    /// Open the queue stored at `path`, creating it if needed and replaying unacked jobs.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, ChannelError> {
+        let path = path.as_ref().to_path_buf();
+        let mut pending = BTreeMap::new();
+        let mut next_id = 0;
+
+        // byte length of the log up to the last record that parsed
+        let mut good_len = 0;
+        if path.exists() {
+            let mut reader = BufReader::new(File::open(&path)?);
+            let mut line = Vec::new();
+            loop {
+                line.clear();
+                let n = reader.read_until(b'\n', &mut line)?;
+                if n == 0 {
+                    break;
+                }
+                let record = match serde_json::from_slice::<Record<T>>(&line) {
+                    Ok(record) => record,
+                    // a torn last line means we died in the middle of a write, the
+                    // record was never confirmed to the caller so it is safe to drop
+                    Err(_) if reader.fill_buf()?.is_empty() => break,
+                    Err(_) => return Err(ChannelError::Corrupted(good_len)),
+                };
+                good_len += n as u64;
+                match record {
+                    Record::Push { id, job } => {
+                        next_id = next_id.max(id + 1);
+                        pending.insert(id, job);
+                    }
+                    Record::Ack { id } => {
+                        pending.remove(&id);
+                    }
+                }
+            }
+        }
+
+        let log = OpenOptions::new().create(true).append(true).open(&path)?;
+        // cut the torn tail off, otherwise the next record is glued onto it
+        log.set_len(good_len)?;
+        let ready = pending.keys().copied().collect();
+        let state = QueueState {
+            path,
+            log,
+            next_id,
+            ready,
+            pending,
+        };
+
+        Ok(Self {
+            inner: Arc::new((Mutex::new(state), Condvar::new())),
+        })
+    }
+
+    /// Append a job to the log and make it available to consumers.
+    pub fn push(&self, job: T) -> Result<u64, ChannelError> {
+        let (lock, cvar) = &*self.inner;
+        let mut state = lock.lock().map_err(|_| ChannelError::Poisoned)?;
+        let id = state.next_id;
+        state.append(&Record::Push {
+            id,
+            job: job.clone(),
+        })?;
+        state.next_id += 1;
+        state.pending.insert(id, job);
+        state.ready.push_back(id);
+        #[cfg(feature = "tracing")]
+        tracing::trace!(id, "job pushed");
+        cvar.notify_one();
+        Ok(id)
+    }
+
+    /// Take the next job without blocking. The job stays in the log until it is acked.
+    pub fn try_pop(&self) -> Result<Option<Job<T>>, ChannelError> {
+        let (lock, _) = &*self.inner;
+        let mut state = lock.lock().map_err(|_| ChannelError::Poisoned)?;
+        Ok(state.take())
+    }
+
+    /// Take the next job, blocking until one is pushed.
+    pub fn pop(&self) -> Result<Job<T>, ChannelError> {
+        let (lock, cvar) = &*self.inner;
+        let mut state = lock.lock().map_err(|_| ChannelError::Poisoned)?;
+        loop {
+            if let Some(job) = state.take() {
+                return Ok(job);
+            }
+            state = cvar.wait(state).map_err(|_| ChannelError::Poisoned)?;
+        }
+    }
+
+    /// Mark a job as done so it is not replayed on restart.
+    pub fn ack(&self, id: u64) -> Result<(), ChannelError> {
+        let (lock, _) = &*self.inner;
+        let mut state = lock.lock().map_err(|_| ChannelError::Poisoned)?;
+        if !state.pending.contains_key(&id) {
+            return Err(ChannelError::JobNotFound(id));
+        }
+        state.append(&Record::<T>::Ack { id })?;
+        state.pending.remove(&id);
+        #[cfg(feature = "tracing")]
+        tracing::trace!(id, "job acked");
+        Ok(())
+    }
+
+    /// Number of jobs not acked yet, including the ones consumers are working on.
+    pub fn len(&self) -> usize {
+        let (lock, _) = &*self.inner;
+        // a panicking holder never leaves `pending` half updated, the count is still right
+        let state = lock.lock().unwrap_or_else(|e| e.into_inner());
+        state.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Rewrite the log so it only holds unacked jobs.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    pub fn compact(&self) -> Result<(), ChannelError> {
+        let (lock, _) = &*self.inner;
+        let mut state = lock.lock().map_err(|_| ChannelError::Poisoned)?;
+
+        let tmp_path = state.path.with_extension("compact");
+        let mut tmp = File::create(&tmp_path)?;
+        for (&id, job) in state.pending.iter() {
+            let record = Record::Push {
+                id,
+                job: job.clone(),
+            };
+            writeln!(tmp, "{}", serde_json::to_string(&record)?)?;
+        }
+        tmp.sync_all()?;
+        fs::rename(&tmp_path, &state.path)?;
+
+        state.log = OpenOptions::new().append(true).open(&state.path)?;
+        #[cfg(feature = "tracing")]
+        tracing::debug!(pending = state.pending.len(), "log compacted");
+        Ok(())
+    }
+}
+
+impl<T> QueueState<T>
+where
+    T: Serialize + Clone,
+{
+    fn append(&mut self, record: &Record<T>) -> Result<(), ChannelError> {
+        // one write per record so a crash can only tear the last line
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        self.log.write_all(&line)?;
+        self.log.flush()?;
+        #[cfg(feature = "tracing")]
+        tracing::trace!(bytes = line.len(), "log flushed");
+        Ok(())
+    }
+
+    fn take(&mut self) -> Option<Job<T>> {
+        while let Some(id) = self.ready.pop_front() {
+            if let Some(data) = self.pending.get(&id) {
+                return Some(Job {
+                    id,
+                    data: data.clone(),
+                });
+            }
+        }
+        None
+    }
+}
+
+impl<T> Clone for PersistentQueue<T> {
+    fn clone(&self) -> Self {
+        PersistentQueue {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+#[cfg(all(test, not(concurrency_loom)))]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_queue_replays_unacked_jobs() -> Result<(), ChannelError> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("jobs.log");
+
+        let queue = PersistentQueue::open(&path)?;
+        queue.push("a".to_string())?;
+        queue.push("b".to_string())?;
+        queue.push("c".to_string())?;
+
+        let job = queue.pop()?;
+        assert_eq!(job.data, "a");
+        queue.ack(job.id)?;
+        // popped but not acked, must come back after restart
+        let job = queue.pop()?;
+        assert_eq!(job.data, "b");
+        drop(queue);
+
+        let queue = PersistentQueue::<String>::open(&path)?;
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.pop()?.data, "b");
+        assert_eq!(queue.pop()?.data, "c");
+        assert!(queue.try_pop()?.is_none());
+
+        // ids keep growing after a replay
+        assert_eq!(queue.push("d".to_string())?, 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_queue_ignores_torn_last_line() -> Result<(), ChannelError> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("jobs.log");
+
+        let queue = PersistentQueue::open(&path)?;
+        queue.push(1)?;
+        drop(queue);
+        OpenOptions::new()
+            .append(true)
+            .open(&path)?
+            .write_all(b"{\"op\":\"push\",\"id\":1,\"jo")?;
+
+        let queue = PersistentQueue::<i32>::open(&path)?;
+        assert_eq!(queue.len(), 1);
+        queue.push(2)?;
+        drop(queue);
+
+        // the torn tail was cut, so the record pushed after it replays too
+        let queue = PersistentQueue::<i32>::open(&path)?;
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.pop()?.data, 1);
+        assert_eq!(queue.pop()?.data, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_queue_rejects_corrupted_middle() -> Result<(), ChannelError> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("jobs.log");
+        fs::write(
+            &path,
+            "{\"op\":\"push\",\"id\":0,\"job\":1}\ngarbage\n{\"op\":\"push\",\"id\":1,\"job\":2}\n",
+        )?;
+
+        let err = PersistentQueue::<i32>::open(&path).unwrap_err();
+        assert!(matches!(err, ChannelError::Corrupted(29)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_queue_compact() -> Result<(), ChannelError> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("jobs.log");
+
+        let queue = PersistentQueue::open(&path)?;
+        for i in 0..10 {
+            let id = queue.push(i)?;
+            if i % 2 == 0 {
+                queue.ack(id)?;
+            }
+        }
+        queue.compact()?;
+        assert_eq!(fs::read_to_string(&path)?.lines().count(), 5);
+        drop(queue);
+
+        let queue = PersistentQueue::<i32>::open(&path)?;
+        let jobs = (0..5)
+            .map(|_| queue.pop().unwrap().data)
+            .collect::<Vec<_>>();
+        assert_eq!(jobs, [1, 3, 5, 7, 9]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_queue_concurrent_consumers() -> Result<(), ChannelError> {
+        let dir = tempfile::tempdir()?;
+        let queue = PersistentQueue::open(dir.path().join("jobs.log"))?;
+
+        let consumers = (0..4)
+            .map(|_| {
+                let queue = queue.clone();
+                thread::spawn(move || {
+                    let mut sum = 0;
+                    while let Some(job) = queue.try_pop()? {
+                        sum += job.data;
+                        queue.ack(job.id)?;
+                    }
+                    Ok::<_, ChannelError>(sum)
+                })
+            })
+            .collect::<Vec<_>>();
+        // consumers may finish early, drain whatever is left below
+        for i in 1..=100 {
+            queue.push(i)?;
+        }
+
+        let mut total = 0;
+        for handle in consumers {
+            total += handle.join().unwrap()?;
+        }
+        while let Some(job) = queue.try_pop()? {
+            total += job.data;
+            queue.ack(job.id)?;
+        }
+        assert_eq!(total, 5050);
+        assert!(queue.is_empty());
+        Ok(())
+    }
+}
+
+#[cfg(all(test, concurrency_loom))]
+mod loom_tests {
+    use super::*;
+    use loom::thread;
+
+    #[test]
+    fn loom_pop_waits_for_push() {
+        loom::model(|| {
+            let dir = tempfile::tempdir().unwrap();
+            let queue = PersistentQueue::open(dir.path().join("jobs.log")).unwrap();
+
+            let consumer = {
+                let queue = queue.clone();
+                thread::spawn(move || queue.pop().unwrap().data)
+            };
+            queue.push(42).unwrap();
+
+            assert_eq!(consumer.join().unwrap(), 42);
+        });
+    }
+
+    #[test]
+    fn loom_job_is_taken_once() {
+        loom::model(|| {
+            let dir = tempfile::tempdir().unwrap();
+            let queue = PersistentQueue::open(dir.path().join("jobs.log")).unwrap();
+            queue.push(1).unwrap();
+
+            let handles = (0..2)
+                .map(|_| {
+                    let queue = queue.clone();
+                    thread::spawn(move || queue.try_pop().unwrap().is_some())
+                })
+                .collect::<Vec<_>>();
+            let taken = handles
+                .into_iter()
+                .map(|h| h.join().unwrap())
+                .filter(|&taken| taken)
+                .count();
+
+            assert_eq!(taken, 1);
+        });
+    }
+}
@@ -0,0 +1,107 @@
+//! Random inputs for benchmarks and demos, e.g.
+//! `Matrix::random_with(64, 64, Normal::new(0.0, 1.0)?)` with `Normal` from the
+//! re-exported `rand_distr`.
+
+use alloc::vec::Vec;
+use rand::Rng;
+use rand_distr::{Bernoulli, BernoulliError, Distribution, Standard};
+
+use crate::{Matrix, Vector};
+
+/// Samples `inner` with probability `density`, `T::default()` otherwise, for
+/// mostly zero inputs.
+#[derive(Debug, Clone, Copy)]
+pub struct Sparse<D> {
+    fill: Bernoulli,
+    inner: D,
+}
+
+impl<D> Sparse<D> {
+    /// Fails unless `0.0 <= density <= 1.0`.
+    pub fn new(density: f64, inner: D) -> Result<Self, BernoulliError> {
+        Ok(Self {
+            fill: Bernoulli::new(density)?,
+            inner,
+        })
+    }
+}
+
+impl<T, D> Distribution<T> for Sparse<D>
+where
+    T: Default,
+    D: Distribution<T>,
+{
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> T {
+        if self.fill.sample(rng) {
+            self.inner.sample(rng)
+        } else {
+            T::default()
+        }
+    }
+}
+
+impl<T> Matrix<T> {
+    /// A `row` x `col` matrix sampled from `distribution` with the thread-local rng.
+    pub fn random_with(row: usize, col: usize, distribution: impl Distribution<T>) -> Self {
+        Matrix::new(sample(row * col, distribution), row, col)
+    }
+
+    /// A `row` x `col` matrix from rand's `Standard` distribution, e.g. uniform in
+    /// `[0, 1)` for floats and over the whole range for integers.
+    pub fn random(row: usize, col: usize) -> Self
+    where
+        Standard: Distribution<T>,
+    {
+        Matrix::random_with(row, col, Standard)
+    }
+}
+
+impl<T> Vector<T> {
+    /// `len` elements sampled from `distribution` with the thread-local rng.
+    pub fn random_with(len: usize, distribution: impl Distribution<T>) -> Self {
+        Vector::new(sample(len, distribution))
+    }
+}
+
+fn sample<T>(len: usize, distribution: impl Distribution<T>) -> Vec<T> {
+    distribution
+        .sample_iter(rand::thread_rng())
+        .take(len)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand_distr::{Normal, Uniform};
+
+    #[test]
+    fn test_random_with() {
+        let m = Matrix::random_with(30, 20, Uniform::new_inclusive(-5, 5));
+        assert_eq!((m.row(), m.col(), m.as_slice().len()), (30, 20, 600));
+        assert!(m.as_slice().iter().all(|v| (-5..=5).contains(v)));
+
+        let v = Vector::random_with(10_000, Normal::new(10.0, 1.0).unwrap());
+        let mean = v.iter().sum::<f64>() / v.len() as f64;
+        assert!((mean - 10.0).abs() < 0.1, "mean {mean}");
+    }
+
+    #[test]
+    fn test_random() {
+        let m = Matrix::<f64>::random(8, 4);
+        assert_eq!((m.row(), m.col()), (8, 4));
+        assert!(m.as_slice().iter().all(|v| (0.0..1.0).contains(v)));
+    }
+
+    #[test]
+    fn test_sparse_fill() {
+        let sparse = Sparse::new(0.1, Uniform::new(1, 10)).unwrap();
+        let v = Vector::random_with(10_000, sparse);
+        let filled = v.iter().filter(|&&x| x != 0).count();
+        assert!((500..1500).contains(&filled), "{filled} filled");
+
+        let empty = Vector::random_with(100, Sparse::new(0.0, Uniform::new(1, 10)).unwrap());
+        assert!(empty.iter().all(|&x| x == 0));
+        assert!(Sparse::new(1.5, Uniform::new(1, 10)).is_err());
+    }
+}
@@ -0,0 +1,468 @@
+use bytes::{Bytes, BytesMut};
+use std::ops::Range;
+use thiserror::Error;
+
+/// One RESP2 value, as sent by redis clients.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RespFrame {
+    Simple(String),
+    Error(String),
+    Integer(i64),
+    /// `$-1` is a null bulk string
+    Bulk(Option<Bytes>),
+    /// `*-1` is a null array
+    Array(Option<Vec<RespFrame>>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ParseMode {
+    /// reject anything the spec does not allow
+    #[default]
+    Strict,
+    /// accept what sloppy clients send: bare `\n` line endings, inline commands like
+    /// `PING\r\n` typed into telnet, and negative lengths other than -1 as null
+    Lenient,
+}
+
+/// Limits are enforced in both modes, they protect the server from malformed input.
+#[derive(Debug, Clone)]
+pub struct ParseConfig {
+    pub mode: ParseMode,
+    /// longest bulk string accepted, in bytes
+    pub max_bulk_len: usize,
+    /// most elements accepted in one array
+    pub max_array_len: usize,
+    /// deepest nesting of arrays accepted, a flat command has depth 1
+    pub max_depth: usize,
+    /// longest simple string, integer, length or inline command line, in bytes
+    pub max_line_len: usize,
+}
+
+impl Default for ParseConfig {
+    fn default() -> Self {
+        // same limits as redis itself
+        Self {
+            mode: ParseMode::default(),
+            max_bulk_len: 512 * 1024 * 1024,
+            max_array_len: 1024 * 1024,
+            max_depth: 32,
+            max_line_len: 64 * 1024,
+        }
+    }
+}
+
+/// A malformed frame, `offset` is the index in the parsed buffer where it goes wrong.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("Protocol error at byte {offset}: {kind}")]
+pub struct ProtocolError {
+    pub offset: usize,
+    pub kind: ProtocolErrorKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ProtocolErrorKind {
+    #[error("unknown frame type {:?}", char::from(*.0))]
+    UnknownType(u8),
+    #[error("invalid length {0:?}")]
+    InvalidLength(String),
+    #[error("invalid integer {0:?}")]
+    InvalidInteger(String),
+    #[error("simple string is not valid UTF-8")]
+    InvalidUtf8,
+    #[error("expected CRLF")]
+    MissingCrlf,
+    #[error("line longer than {max} bytes")]
+    LineTooLong { max: usize },
+    #[error("bulk string of {len} bytes exceeds the limit of {max}")]
+    BulkTooLarge { len: usize, max: usize },
+    #[error("array of {len} elements exceeds the limit of {max}")]
+    ArrayTooLarge { len: usize, max: usize },
+    #[error("arrays nested deeper than {max}")]
+    TooDeep { max: usize },
+}
+
+impl ProtocolError {
+    /// Where a lenient reader can pick up again: right after the line the error is on.
+    /// None when that line is not complete yet.
+    pub fn resume_offset(&self, buf: &[u8]) -> Option<usize> {
+        let rest = buf.get(self.offset..)?;
+        rest.iter()
+            .position(|&b| b == b'\n')
+            .map(|nl| self.offset + nl + 1)
+    }
+}
+
+/// Parse the first frame in `buf`.
+///
+/// Returns the frame and the number of bytes it used, or None when `buf` does not
+/// hold a whole frame yet and the caller should read more and try again.
+///
+/// Bulk strings are copied out of `buf`, use `split_frame` to avoid the copies.
+pub fn parse_frame(
+    buf: &[u8],
+    config: &ParseConfig,
+) -> Result<Option<(RespFrame, usize)>, ProtocolError> {
+    let mut cursor = Cursor::new(buf, config);
+    let Some(mut frame) = cursor.frame(1)? else {
+        return Ok(None);
+    };
+    let mut bulks = cursor
+        .bulks
+        .into_iter()
+        .map(|r| Bytes::copy_from_slice(&buf[r]));
+    fill_bulks(&mut frame, &mut bulks);
+    Ok(Some((frame, cursor.pos)))
+}
+
+/// Split the first frame off the front of `buf`, None when it is not complete yet.
+///
+/// The bulk strings of the frame share the memory of `buf` instead of being copied.
+pub fn split_frame(
+    buf: &mut BytesMut,
+    config: &ParseConfig,
+) -> Result<Option<RespFrame>, ProtocolError> {
+    let mut cursor = Cursor::new(buf, config);
+    let Some(mut frame) = cursor.frame(1)? else {
+        return Ok(None);
+    };
+    let (used, ranges) = (cursor.pos, cursor.bulks);
+    let raw = buf.split_to(used).freeze();
+    fill_bulks(&mut frame, &mut ranges.into_iter().map(|r| raw.slice(r)));
+    Ok(Some(frame))
+}
+
+// swap the placeholders left by the cursor for the payloads, in the order they were read
+fn fill_bulks(frame: &mut RespFrame, bulks: &mut impl Iterator<Item = Bytes>) {
+    match frame {
+        RespFrame::Bulk(Some(data)) => *data = bulks.next().unwrap_or_default(),
+        RespFrame::Array(Some(items)) => {
+            for item in items {
+                fill_bulks(item, bulks);
+            }
+        }
+        _ => {}
+    }
+}
+
+struct Cursor<'a> {
+    buf: &'a [u8],
+    pos: usize,
+    config: &'a ParseConfig,
+    // where the payload of every non-null bulk string is, frames hold empty placeholders
+    bulks: Vec<Range<usize>>,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(buf: &'a [u8], config: &'a ParseConfig) -> Self {
+        Self {
+            buf,
+            pos: 0,
+            config,
+            bulks: Vec::new(),
+        }
+    }
+
+    // Ok(None) everywhere below means the input ends before the frame does
+    fn frame(&mut self, depth: usize) -> Result<Option<RespFrame>, ProtocolError> {
+        let start = self.pos;
+        let Some(&ty) = self.buf.get(start) else {
+            return Ok(None);
+        };
+        if depth > self.config.max_depth {
+            return Err(self.error(
+                start,
+                ProtocolErrorKind::TooDeep {
+                    max: self.config.max_depth,
+                },
+            ));
+        }
+
+        if !matches!(ty, b'+' | b'-' | b':' | b'$' | b'*') {
+            if self.config.mode == ParseMode::Lenient && depth == 1 {
+                return self.inline();
+            }
+            return Err(self.error(start, ProtocolErrorKind::UnknownType(ty)));
+        }
+
+        self.pos += 1;
+        let Some(line) = self.line()? else {
+            return Ok(None);
+        };
+        let frame = match ty {
+            b'+' | b'-' => {
+                let s = String::from_utf8(line.to_vec())
+                    .map_err(|_| self.error(start + 1, ProtocolErrorKind::InvalidUtf8))?;
+                if ty == b'+' {
+                    RespFrame::Simple(s)
+                } else {
+                    RespFrame::Error(s)
+                }
+            }
+            b':' => RespFrame::Integer(self.integer(line, start + 1)?),
+            b'$' => match self.length(line, start + 1)? {
+                None => RespFrame::Bulk(None),
+                Some(len) if len > self.config.max_bulk_len => {
+                    let max = self.config.max_bulk_len;
+                    return Err(self.error(start, ProtocolErrorKind::BulkTooLarge { len, max }));
+                }
+                Some(len) => match self.bulk(len)? {
+                    Some(range) => {
+                        self.bulks.push(range);
+                        RespFrame::Bulk(Some(Bytes::new()))
+                    }
+                    None => return Ok(None),
+                },
+            },
+            _ => match self.length(line, start + 1)? {
+                None => RespFrame::Array(None),
+                Some(len) if len > self.config.max_array_len => {
+                    let max = self.config.max_array_len;
+                    return Err(self.error(start, ProtocolErrorKind::ArrayTooLarge { len, max }));
+                }
+                Some(len) => {
+                    let mut items = Vec::with_capacity(len.min(64));
+                    for _ in 0..len {
+                        match self.frame(depth + 1)? {
+                            Some(item) => items.push(item),
+                            None => return Ok(None),
+                        }
+                    }
+                    RespFrame::Array(Some(items))
+                }
+            },
+        };
+        Ok(Some(frame))
+    }
+
+    // the rest of the current line, without its line ending
+    fn line(&mut self) -> Result<Option<&'a [u8]>, ProtocolError> {
+        let rest = &self.buf[self.pos..];
+        let Some(nl) = rest.iter().position(|&b| b == b'\n') else {
+            if rest.len() > self.config.max_line_len {
+                let max = self.config.max_line_len;
+                return Err(self.error(self.pos, ProtocolErrorKind::LineTooLong { max }));
+            }
+            return Ok(None);
+        };
+
+        let line = match rest[..nl].strip_suffix(b"\r") {
+            Some(line) => line,
+            None if self.config.mode == ParseMode::Lenient => &rest[..nl],
+            None => return Err(self.error(self.pos + nl, ProtocolErrorKind::MissingCrlf)),
+        };
+        if line.len() > self.config.max_line_len {
+            let max = self.config.max_line_len;
+            return Err(self.error(self.pos, ProtocolErrorKind::LineTooLong { max }));
+        }
+        self.pos += nl + 1;
+        Ok(Some(line))
+    }
+
+    // `len` bytes of payload followed by a line ending, returns where the payload is
+    fn bulk(&mut self, len: usize) -> Result<Option<Range<usize>>, ProtocolError> {
+        let rest = &self.buf[self.pos..];
+        if rest.len() < len + 1 {
+            return Ok(None);
+        }
+        let ending = match &rest[len..] {
+            [b'\r', b'\n', ..] => 2,
+            [b'\n', ..] if self.config.mode == ParseMode::Lenient => 1,
+            [b'\r'] => return Ok(None),
+            _ => return Err(self.error(self.pos + len, ProtocolErrorKind::MissingCrlf)),
+        };
+        let data = self.pos..self.pos + len;
+        self.pos += len + ending;
+        Ok(Some(data))
+    }
+
+    // a command typed without RESP framing, words separated by whitespace
+    fn inline(&mut self) -> Result<Option<RespFrame>, ProtocolError> {
+        let Some(line) = self.line()? else {
+            return Ok(None);
+        };
+        let mut words = Vec::new();
+        for word in line.split(|b| b.is_ascii_whitespace()) {
+            if !word.is_empty() {
+                // `word` borrows from `self.buf`, so this is its offset in there
+                let start = word.as_ptr() as usize - self.buf.as_ptr() as usize;
+                self.bulks.push(start..start + word.len());
+                words.push(RespFrame::Bulk(Some(Bytes::new())));
+            }
+        }
+        Ok(Some(RespFrame::Array(Some(words))))
+    }
+
+    fn integer(&self, line: &[u8], offset: usize) -> Result<i64, ProtocolError> {
+        core::str::from_utf8(line)
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| {
+                let s = String::from_utf8_lossy(line).into_owned();
+                self.error(offset, ProtocolErrorKind::InvalidInteger(s))
+            })
+    }
+
+    // None for a null value
+    fn length(&self, line: &[u8], offset: usize) -> Result<Option<usize>, ProtocolError> {
+        let invalid = || {
+            let s = String::from_utf8_lossy(line).into_owned();
+            self.error(offset, ProtocolErrorKind::InvalidLength(s))
+        };
+        let len = self.integer(line, offset).map_err(|_| invalid())?;
+        match len {
+            -1 => Ok(None),
+            len if len < 0 && self.config.mode == ParseMode::Lenient => Ok(None),
+            len => usize::try_from(len).map(Some).map_err(|_| invalid()),
+        }
+    }
+
+    fn error(&self, offset: usize, kind: ProtocolErrorKind) -> ProtocolError {
+        ProtocolError { offset, kind }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lenient() -> ParseConfig {
+        ParseConfig {
+            mode: ParseMode::Lenient,
+            ..Default::default()
+        }
+    }
+
+    fn bulk(s: &str) -> RespFrame {
+        RespFrame::Bulk(Some(Bytes::copy_from_slice(s.as_bytes())))
+    }
+
+    #[test]
+    fn test_parse_frames() -> Result<(), ProtocolError> {
+        let config = ParseConfig::default();
+        let input = b"*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$-1\r\n+OK\r\n";
+        let (frame, used) = parse_frame(input, &config)?.unwrap();
+        assert_eq!(
+            frame,
+            RespFrame::Array(Some(vec![bulk("SET"), bulk("k"), RespFrame::Bulk(None)]))
+        );
+        assert_eq!(&input[used..], b"+OK\r\n");
+
+        let cases = [
+            (&b"+OK\r\n"[..], RespFrame::Simple("OK".to_string())),
+            (b"-ERR no\r\n", RespFrame::Error("ERR no".to_string())),
+            (b":-42\r\n", RespFrame::Integer(-42)),
+            (b"$0\r\n\r\n", bulk("")),
+            (b"*-1\r\n", RespFrame::Array(None)),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(parse_frame(input, &config)?, Some((expected, input.len())));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_incomplete() -> Result<(), ProtocolError> {
+        let input = b"*2\r\n$3\r\nGET\r\n$1\r\nk\r\n";
+        for end in 0..input.len() {
+            assert_eq!(parse_frame(&input[..end], &ParseConfig::default())?, None);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_strict_and_lenient_modes() -> Result<(), ProtocolError> {
+        let strict = ParseConfig::default();
+
+        let err = parse_frame(b"PING\r\n", &strict).unwrap_err();
+        assert_eq!(err.kind, ProtocolErrorKind::UnknownType(b'P'));
+        let frame = parse_frame(b"PING  hello\r\n", &lenient())?.unwrap().0;
+        assert_eq!(
+            frame,
+            RespFrame::Array(Some(vec![bulk("PING"), bulk("hello")]))
+        );
+
+        let err = parse_frame(b"*1\n$4\r\nPING\r\n", &strict).unwrap_err();
+        assert_eq!((err.offset, err.kind), (2, ProtocolErrorKind::MissingCrlf));
+        let frame = parse_frame(b"*1\n$4\nPING\n", &lenient())?.unwrap().0;
+        assert_eq!(frame, RespFrame::Array(Some(vec![bulk("PING")])));
+
+        let err = parse_frame(b"$-2\r\n", &strict).unwrap_err();
+        assert_eq!(err.kind, ProtocolErrorKind::InvalidLength("-2".to_string()));
+        assert_eq!(
+            parse_frame(b"$-2\r\n", &lenient())?.unwrap().0,
+            RespFrame::Bulk(None)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_limits() {
+        let config = ParseConfig {
+            max_bulk_len: 4,
+            max_array_len: 2,
+            max_depth: 2,
+            max_line_len: 8,
+            ..Default::default()
+        };
+
+        let err = parse_frame(b"*1\r\n$5\r\nhello\r\n", &config).unwrap_err();
+        assert_eq!(err.offset, 4);
+        assert_eq!(err.kind, ProtocolErrorKind::BulkTooLarge { len: 5, max: 4 });
+
+        let err = parse_frame(b"*3\r\n", &config).unwrap_err();
+        assert_eq!(
+            err.kind,
+            ProtocolErrorKind::ArrayTooLarge { len: 3, max: 2 }
+        );
+
+        let err = parse_frame(b"*1\r\n*1\r\n*1\r\n:1\r\n", &config).unwrap_err();
+        assert_eq!(
+            (err.offset, err.kind),
+            (8, ProtocolErrorKind::TooDeep { max: 2 })
+        );
+
+        // no line ending in sight, don't wait for more
+        let err = parse_frame(b"+123456789", &config).unwrap_err();
+        assert_eq!(err.kind, ProtocolErrorKind::LineTooLong { max: 8 });
+    }
+
+    #[test]
+    fn test_resume_after_error() -> Result<(), ProtocolError> {
+        let input = b"$x\r\n+OK\r\n";
+        let err = parse_frame(input, &lenient()).unwrap_err();
+        assert_eq!(err.offset, 1);
+        assert_eq!(
+            err.to_string(),
+            "Protocol error at byte 1: invalid length \"x\""
+        );
+
+        let resume = err.resume_offset(input).unwrap();
+        let frame = parse_frame(&input[resume..], &lenient())?.unwrap().0;
+        assert_eq!(frame, RespFrame::Simple("OK".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_frame_shares_the_buffer() -> Result<(), ProtocolError> {
+        let mut buf = BytesMut::from(&b"*2\r\n$3\r\nGET\r\n$5\r\nhello\r\n+OK"[..]);
+        let start = buf.as_ptr() as usize;
+
+        let frame = split_frame(&mut buf, &ParseConfig::default())?.unwrap();
+        assert_eq!(
+            frame,
+            RespFrame::Array(Some(vec![bulk("GET"), bulk("hello")]))
+        );
+        let RespFrame::Array(Some(items)) = frame else {
+            unreachable!()
+        };
+        let RespFrame::Bulk(Some(hello)) = &items[1] else {
+            unreachable!()
+        };
+        assert_eq!(hello.as_ptr() as usize, start + 17);
+
+        // the incomplete rest stays in the buffer
+        assert_eq!(split_frame(&mut buf, &ParseConfig::default())?, None);
+        assert_eq!(&buf[..], b"+OK");
+        Ok(())
+    }
+}
@@ -0,0 +1,429 @@
+//! Compressed sparse row matrices and sparse vectors, for large inputs that are
+//! mostly zeros.
+
+use alloc::{vec, vec::Vec};
+use core::cmp::Ordering;
+use core::ops::{AddAssign, Mul};
+
+use crate::matrix::{par_row_blocks, PARALLEL_MIN};
+use crate::{Matrix, MatrixError, Vector, VectorError};
+
+/// A `row` x `col` matrix in compressed sparse row (CSR) layout: the non-zero
+/// values row by row, the column of each, and where each row starts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SparseMatrix<T> {
+    // `values[row_starts[i]..row_starts[i + 1]]` are the non-zeros of row `i`
+    values: Vec<T>,
+    cols: Vec<usize>,
+    row_starts: Vec<usize>,
+    row: usize,
+    col: usize,
+}
+
+impl<T> SparseMatrix<T> {
+    /// Builds a matrix from `(row, col, value)` triplets in any order, summing the
+    /// values given for the same cell. Fails with `ShapeMismatch` if a triplet lies
+    /// outside `row` x `col`.
+    pub fn from_triplets(
+        row: usize,
+        col: usize,
+        mut triplets: Vec<(usize, usize, T)>,
+    ) -> Result<Self, MatrixError>
+    where
+        T: AddAssign,
+    {
+        if let Some(&(i, j, _)) = triplets.iter().find(|(i, j, _)| *i >= row || *j >= col) {
+            return Err(MatrixError::ShapeMismatch {
+                left: (row, col),
+                right: (i + 1, j + 1),
+            });
+        }
+        triplets.sort_by_key(|&(i, j, _)| (i, j));
+
+        let mut values: Vec<T> = Vec::with_capacity(triplets.len());
+        let mut cols: Vec<usize> = Vec::with_capacity(triplets.len());
+        let mut row_starts = vec![0; row + 1];
+        let mut last = None;
+        for (i, j, value) in triplets {
+            if last == Some((i, j)) {
+                // sorted, so the duplicate is the last value pushed
+                if let Some(prev) = values.last_mut() {
+                    *prev += value;
+                }
+                continue;
+            }
+            last = Some((i, j));
+            values.push(value);
+            cols.push(j);
+            row_starts[i + 1] += 1;
+        }
+        for i in 0..row {
+            row_starts[i + 1] += row_starts[i];
+        }
+        Ok(Self {
+            values,
+            cols,
+            row_starts,
+            row,
+            col,
+        })
+    }
+
+    pub fn row(&self) -> usize {
+        self.row
+    }
+
+    pub fn col(&self) -> usize {
+        self.col
+    }
+
+    /// Number of stored (non-zero) elements.
+    pub fn nnz(&self) -> usize {
+        self.values.len()
+    }
+
+    /// The stored elements of row `i` as `(col, value)`, in column order.
+    pub fn row_entries(&self, i: usize) -> impl Iterator<Item = (usize, &T)> + '_ {
+        let range = self.row_starts[i]..self.row_starts[i + 1];
+        self.cols[range.clone()]
+            .iter()
+            .copied()
+            .zip(&self.values[range])
+    }
+
+    /// The dense matrix, `T::default()` where nothing is stored.
+    pub fn to_dense(&self) -> Matrix<T>
+    where
+        T: Default + Clone,
+    {
+        let mut data = vec![T::default(); self.row * self.col];
+        for i in 0..self.row {
+            for (j, value) in self.row_entries(i) {
+                data[i * self.col + j] = value.clone();
+            }
+        }
+        Matrix::new(data, self.row, self.col)
+    }
+
+    /// `self * b` for a dense `b`, the rows of the output split into one block per
+    /// thread once there is enough work. Only the stored elements are multiplied.
+    pub fn mul_dense(&self, b: &Matrix<T>) -> Result<Matrix<T>, MatrixError>
+    where
+        T: Mul<Output = T> + AddAssign + Default + Clone + Send + Sync,
+    {
+        if self.col != b.row() {
+            return Err(MatrixError::DimensionMismatch {
+                left: (self.row, self.col),
+                right: (b.row(), b.col()),
+            });
+        }
+        let b = b.row_major();
+        let n = b.col();
+        let mut data = vec![T::default(); self.row * n];
+        let work = self.nnz().saturating_mul(n);
+        par_row_blocks(&mut data, n, work, PARALLEL_MIN, |i, out| {
+            for (p, x) in self.row_entries(i) {
+                let b_row = &b.as_slice()[p * n..(p + 1) * n];
+                for (cell, y) in out.iter_mut().zip(b_row) {
+                    *cell += x.clone() * y.clone();
+                }
+            }
+        });
+        Ok(Matrix::new(data, self.row, n))
+    }
+
+    /// `self * v`, the rows split into one block per thread once there is enough work.
+    pub fn mul_vec(&self, v: &Vector<T>) -> Result<Vector<T>, MatrixError>
+    where
+        T: Mul<Output = T> + AddAssign + Default + Clone + Send + Sync,
+    {
+        if self.col != v.len() {
+            return Err(VectorError::LengthMismatch {
+                left: self.col,
+                right: v.len(),
+            }
+            .into());
+        }
+        let mut data = vec![T::default(); self.row];
+        par_row_blocks(&mut data, 1, self.nnz(), PARALLEL_MIN, |i, out| {
+            for (p, x) in self.row_entries(i) {
+                out[0] += x.clone() * v[p].clone();
+            }
+        });
+        Ok(Vector::new(data))
+    }
+}
+
+impl<T> From<&Matrix<T>> for SparseMatrix<T>
+where
+    T: Default + PartialEq + Clone,
+{
+    /// Keeps the elements that are not `T::default()`.
+    fn from(m: &Matrix<T>) -> Self {
+        let zero = T::default();
+        let mut values = Vec::new();
+        let mut cols = Vec::new();
+        let mut row_starts = Vec::with_capacity(m.row() + 1);
+        row_starts.push(0);
+        for row in m.rows() {
+            for (j, value) in row.enumerate() {
+                if *value != zero {
+                    values.push(value.clone());
+                    cols.push(j);
+                }
+            }
+            row_starts.push(values.len());
+        }
+        Self {
+            values,
+            cols,
+            row_starts,
+            row: m.row(),
+            col: m.col(),
+        }
+    }
+}
+
+/// A vector of `len` elements storing only the non-zero ones, as their indices in
+/// increasing order and their values, e.g. the features of a text document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SparseVector<T> {
+    indices: Vec<usize>,
+    values: Vec<T>,
+    len: usize,
+}
+
+impl<T> SparseVector<T> {
+    /// Builds a vector from `(index, value)` pairs in any order, summing the values
+    /// given for the same index. Fails with `LengthMismatch` if an index lies at or
+    /// past `len`, `right` then the length that index needs.
+    pub fn from_pairs(len: usize, mut pairs: Vec<(usize, T)>) -> Result<Self, VectorError>
+    where
+        T: AddAssign,
+    {
+        if let Some(&(i, _)) = pairs.iter().find(|(i, _)| *i >= len) {
+            return Err(VectorError::LengthMismatch {
+                left: len,
+                right: i + 1,
+            });
+        }
+        pairs.sort_by_key(|&(i, _)| i);
+
+        let mut indices: Vec<usize> = Vec::with_capacity(pairs.len());
+        let mut values: Vec<T> = Vec::with_capacity(pairs.len());
+        for (i, value) in pairs {
+            if indices.last() == Some(&i) {
+                // sorted, so the duplicate is the last value pushed
+                if let Some(prev) = values.last_mut() {
+                    *prev += value;
+                }
+                continue;
+            }
+            indices.push(i);
+            values.push(value);
+        }
+        Ok(Self {
+            indices,
+            values,
+            len,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of stored (non-zero) elements.
+    pub fn nnz(&self) -> usize {
+        self.values.len()
+    }
+
+    /// The stored elements as `(index, value)`, in index order.
+    pub fn entries(&self) -> impl Iterator<Item = (usize, &T)> + '_ {
+        self.indices.iter().copied().zip(&self.values)
+    }
+
+    /// The dense vector, `T::default()` where nothing is stored.
+    pub fn to_dense(&self) -> Vector<T>
+    where
+        T: Default + Clone,
+    {
+        let mut data = vec![T::default(); self.len];
+        for (i, value) in self.entries() {
+            data[i] = value.clone();
+        }
+        Vector::new(data)
+    }
+
+    /// The dot product with a dense `b`, multiplying only the stored elements, or
+    /// `LengthMismatch`.
+    pub fn dot_dense(&self, b: &[T]) -> Result<T, VectorError>
+    where
+        T: Mul<Output = T> + AddAssign + Default + Clone,
+    {
+        if self.len != b.len() {
+            return Err(VectorError::LengthMismatch {
+                left: self.len,
+                right: b.len(),
+            });
+        }
+        let mut sum = T::default();
+        for (i, x) in self.entries() {
+            sum += x.clone() * b[i].clone();
+        }
+        Ok(sum)
+    }
+}
+
+impl<T> From<&Vector<T>> for SparseVector<T>
+where
+    T: Default + PartialEq + Clone,
+{
+    /// Keeps the elements that are not `T::default()`.
+    fn from(v: &Vector<T>) -> Self {
+        let zero = T::default();
+        let (indices, values) = v
+            .iter()
+            .enumerate()
+            .filter(|(_, value)| **value != zero)
+            .map(|(i, value)| (i, value.clone()))
+            .unzip();
+        Self {
+            indices,
+            values,
+            len: v.len(),
+        }
+    }
+}
+
+/// The dot product of two sparse vectors, walking both index lists once and
+/// multiplying only where both store an element, or `LengthMismatch`. Use
+/// `SparseVector::dot_dense` for a dense operand.
+pub fn dot_product_sparse<T>(a: &SparseVector<T>, b: &SparseVector<T>) -> Result<T, VectorError>
+where
+    T: Mul<Output = T> + AddAssign + Default + Clone,
+{
+    if a.len != b.len {
+        return Err(VectorError::LengthMismatch {
+            left: a.len,
+            right: b.len,
+        });
+    }
+    let mut sum = T::default();
+    let (mut p, mut q) = (0, 0);
+    while p < a.nnz() && q < b.nnz() {
+        match a.indices[p].cmp(&b.indices[q]) {
+            Ordering::Less => p += 1,
+            Ordering::Greater => q += 1,
+            Ordering::Equal => {
+                sum += a.values[p].clone() * b.values[q].clone();
+                p += 1;
+                q += 1;
+            }
+        }
+    }
+    Ok(sum)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sparse_round_trip() {
+        let dense = Matrix::new([0, 2, 0, 0, 0, 0, 3, 0, 4], 3, 3);
+        let sparse = SparseMatrix::from(&dense);
+        assert_eq!(sparse.nnz(), 3);
+        assert_eq!(
+            sparse.row_entries(2).collect::<Vec<_>>(),
+            [(0, &3), (2, &4)]
+        );
+        assert_eq!(sparse.row_entries(1).count(), 0);
+        assert_eq!(sparse.to_dense(), dense);
+
+        // out of order, with a duplicate summed
+        let built =
+            SparseMatrix::from_triplets(3, 3, vec![(2, 2, 4), (2, 0, 1), (0, 1, 2), (2, 0, 2)])
+                .unwrap();
+        assert_eq!(built, sparse);
+        assert_eq!(
+            SparseMatrix::from_triplets(2, 2, vec![(0, 2, 1)]).unwrap_err(),
+            MatrixError::ShapeMismatch {
+                left: (2, 2),
+                right: (1, 3)
+            }
+        );
+    }
+
+    #[test]
+    fn test_sparse_mul() {
+        let dense = Matrix::from_fn(
+            9,
+            7,
+            |i, j| if (i + j) % 3 == 0 { (i * j) as i64 } else { 0 },
+        );
+        let sparse = SparseMatrix::from(&dense);
+        let b = Matrix::from_fn(7, 5, |i, j| (i + 2 * j) as i64);
+        assert_eq!(
+            sparse.mul_dense(&b).unwrap(),
+            crate::multiply(&dense, &b).unwrap()
+        );
+
+        let v = Vector::new([1, 0, -1, 2, 0, 1, 3]);
+        assert_eq!(sparse.mul_vec(&v).unwrap(), dense.mul_vec(&v).unwrap());
+
+        // big enough for the worker threads
+        let dense = Matrix::from_fn(
+            300,
+            200,
+            |i, j| if (i + j) % 3 == 0 { (i * j) as i64 } else { 0 },
+        );
+        let big = SparseMatrix::from(&dense);
+        let v = Vector::new((0..200).collect::<Vec<i64>>());
+        assert_eq!(big.mul_vec(&v).unwrap(), dense.mul_vec(&v).unwrap());
+
+        assert!(matches!(
+            sparse.mul_dense(&dense),
+            Err(MatrixError::DimensionMismatch { .. })
+        ));
+        assert_eq!(
+            sparse.mul_vec(&Vector::new([1])).unwrap_err(),
+            MatrixError::Vector(VectorError::LengthMismatch { left: 7, right: 1 })
+        );
+    }
+
+    #[test]
+    fn test_sparse_vector() {
+        let dense = Vector::new([0, 3, 0, 0, -2, 0, 1]);
+        let a = SparseVector::from(&dense);
+        assert_eq!((a.len(), a.nnz()), (7, 3));
+        assert_eq!(
+            a.entries().collect::<Vec<_>>(),
+            [(1, &3), (4, &-2), (6, &1)]
+        );
+        assert_eq!(a.to_dense(), dense);
+
+        // out of order, with a duplicate summed
+        let b = SparseVector::from_pairs(7, vec![(6, 5), (4, 1), (0, 9), (4, 2)]).unwrap();
+        assert_eq!(b.nnz(), 3);
+        let expected = crate::dot_product_slice(&dense, &b.to_dense()).unwrap();
+        assert_eq!(expected, -1);
+        assert_eq!(dot_product_sparse(&a, &b).unwrap(), expected);
+        assert_eq!(a.dot_dense(&b.to_dense()).unwrap(), expected);
+
+        assert_eq!(
+            SparseVector::from_pairs(3, vec![(3, 1)]).unwrap_err(),
+            VectorError::LengthMismatch { left: 3, right: 4 }
+        );
+        let short = SparseVector::<i32>::from_pairs(2, vec![]).unwrap();
+        assert_eq!(
+            dot_product_sparse(&a, &short).unwrap_err(),
+            VectorError::LengthMismatch { left: 7, right: 2 }
+        );
+        assert!(a.dot_dense(&[1, 2]).is_err());
+    }
+}
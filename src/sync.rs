@@ -0,0 +1,15 @@
+// The primitives the crate synchronizes with. Building with
+// `RUSTFLAGS="--cfg concurrency_loom"` swaps them for loom's model-checked versions
+// so the tests under `cfg(concurrency_loom)` explore every interleaving.
+
+#[cfg(concurrency_loom)]
+pub(crate) use loom::sync::{
+    atomic::{AtomicI64, Ordering},
+    Arc, Condvar, Mutex, RwLock,
+};
+
+#[cfg(not(concurrency_loom))]
+pub(crate) use std::sync::{
+    atomic::{AtomicI64, Ordering},
+    Arc, Condvar, Mutex, RwLock,
+};
@@ -1,8 +1,22 @@
 // use std::ops::Index;
 
-use anyhow::Result;
-use std::ops::{Add, AddAssign, Deref, Mul};
+use alloc::{borrow::Cow, vec::Vec};
+use core::ops::{Add, AddAssign, Bound, Deref, Mul, Range, RangeBounds, Sub};
+#[cfg(feature = "threads")]
+use std::thread;
+use thiserror::Error;
 
+use crate::matrix::par_zip_update;
+#[cfg(feature = "threads")]
+use crate::matrix::{PARALLEL_MIN, THREAD_NUM};
+
+/// With the `serde` feature it serializes as a plain sequence, e.g. `[1,2,3]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
 pub struct Vector<T> {
     data: Vec<T>,
 }
@@ -12,6 +26,109 @@ impl<T> Vector<T> {
         Self { data: data.into() }
     }
 
+    /// `self += alpha * x` in one pass over both, split into one chunk per thread
+    /// for long vectors, or `LengthMismatch` leaving `self` as it was.
+    pub fn axpy(&mut self, alpha: T, x: &Vector<T>) -> Result<(), VectorError>
+    where
+        T: Mul<Output = T> + AddAssign + Clone + Send + Sync,
+    {
+        if self.len() != x.len() {
+            return Err(VectorError::LengthMismatch {
+                left: self.len(),
+                right: x.len(),
+            });
+        }
+        par_zip_update(&mut self.data, x, |y, x| *y += alpha.clone() * x.clone());
+        Ok(())
+    }
+
+    /// `f` of every pair of elements at the same index, e.g.
+    /// `a.zip_map(&b, |x, y| x.max(*y))`, or `LengthMismatch`.
+    pub fn zip_map<U, R, F>(&self, other: &Vector<U>, mut f: F) -> Result<Vector<R>, VectorError>
+    where
+        F: FnMut(&T, &U) -> R,
+    {
+        if self.len() != other.len() {
+            return Err(VectorError::LengthMismatch {
+                left: self.len(),
+                right: other.len(),
+            });
+        }
+        Ok(self
+            .iter()
+            .zip(other.iter())
+            .map(|(x, y)| f(x, y))
+            .collect())
+    }
+
+    /// All elements combined with `op`, starting from `identity`, e.g.
+    /// `v.par_reduce(1, |x, y| x * y)`. Long vectors are split into one chunk per
+    /// thread and the partial results combined, so `op` should be associative and
+    /// `identity` neutral for it.
+    pub fn par_reduce<F>(&self, identity: T, op: F) -> T
+    where
+        T: Clone + Send + Sync,
+        F: Fn(T, T) -> T + Sync,
+    {
+        par_map_reduce(&self.data, identity, T::clone, op)
+    }
+
+    /// The sum of the elements, `T::default()` for an empty vector.
+    pub fn par_sum(&self) -> T
+    where
+        T: Add<Output = T> + Default + Clone + Send + Sync,
+    {
+        self.par_reduce(T::default(), Add::add)
+    }
+
+    /// The smallest element, `None` for an empty vector. Elements that do not
+    /// compare, like `NaN`, lose to any other.
+    pub fn par_min(&self) -> Option<T>
+    where
+        T: PartialOrd + Clone + Send + Sync,
+    {
+        par_map_reduce(
+            &self.data,
+            None,
+            |x| Some(x.clone()),
+            |a, b| pick(a, b, |x, y| x < y),
+        )
+    }
+
+    /// The largest element, `None` for an empty vector. Elements that do not
+    /// compare, like `NaN`, lose to any other.
+    pub fn par_max(&self) -> Option<T>
+    where
+        T: PartialOrd + Clone + Send + Sync,
+    {
+        par_map_reduce(
+            &self.data,
+            None,
+            |x| Some(x.clone()),
+            |a, b| pick(a, b, |x, y| x > y),
+        )
+    }
+
+    /// The cross product `self x other` of two three-element vectors, or
+    /// `NotThreeDimensional` if either has another length.
+    pub fn cross(&self, other: &Vector<T>) -> Result<Vector<T>, VectorError>
+    where
+        T: Mul<Output = T> + Sub<Output = T> + Clone,
+    {
+        let ([a1, a2, a3], [b1, b2, b3]) = (&self.data[..], &other.data[..]) else {
+            return Err(VectorError::NotThreeDimensional {
+                left: self.len(),
+                right: other.len(),
+            });
+        };
+        let product = |x: &T, y: &T, z: &T, w: &T| x.clone() * y.clone() - z.clone() * w.clone();
+        Ok(Vector::new([
+            product(a2, b3, a3, b2),
+            product(a3, b1, a1, b3),
+            product(a1, b2, a2, b1),
+        ]))
+    }
+
     // pub fn len(&self) -> usize {
     //     self.data.len()
     // }
@@ -35,19 +152,640 @@ impl<T> Deref for Vector<T> {
     }
 }
 
+/// A vector that borrows its elements where it can, e.g. a row of a `RowMajor`
+/// matrix from `Matrix::row_vector`, and owns a copy where they are not contiguous.
+///
+/// Derefs to a slice, so it can be passed to `dot_product_slice` as it is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VectorView<'a, T: Clone> {
+    data: Cow<'a, [T]>,
+}
+
+impl<T: Clone> VectorView<'_, T> {
+    /// Whether the elements are borrowed rather than copied.
+    pub fn is_borrowed(&self) -> bool {
+        matches!(self.data, Cow::Borrowed(_))
+    }
+
+    /// The elements in `range` as a view borrowing this one. Panics out of bounds,
+    /// like slicing.
+    pub fn slice(&self, range: impl RangeBounds<usize>) -> VectorView<'_, T> {
+        VectorView::from(&self.data[bounds(range)])
+    }
+
+    /// An owned `Vector`, copying the elements if they are borrowed.
+    pub fn into_vector(self) -> Vector<T> {
+        Vector::new(self.data.into_owned())
+    }
+}
+
+impl<T: Clone> Vector<T> {
+    /// This vector as a view borrowing its elements.
+    pub fn as_view(&self) -> VectorView<'_, T> {
+        VectorView::from(self.data.as_slice())
+    }
+
+    /// The elements in `range` as a view, e.g. `v.slice(..n / 2)`, borrowing them
+    /// without a copy. Panics out of bounds, like slicing.
+    pub fn slice(&self, range: impl RangeBounds<usize>) -> VectorView<'_, T> {
+        VectorView::from(&self.data[bounds(range)])
+    }
+}
+
+// `range` as something a slice can be indexed with
+fn bounds(range: impl RangeBounds<usize>) -> (Bound<usize>, Bound<usize>) {
+    (range.start_bound().cloned(), range.end_bound().cloned())
+}
+
+impl<'a, T: Clone> From<&'a [T]> for VectorView<'a, T> {
+    fn from(data: &'a [T]) -> Self {
+        Self {
+            data: Cow::Borrowed(data),
+        }
+    }
+}
+
+impl<T: Clone> FromIterator<T> for VectorView<'_, T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self {
+            data: Cow::Owned(iter.into_iter().collect()),
+        }
+    }
+}
+
+impl<T: Clone> Deref for VectorView<'_, T> {
+    type Target = [T];
+    fn deref(&self) -> &Self::Target {
+        &self.data
+    }
+}
+
+impl<T> IntoIterator for Vector<T> {
+    type Item = T;
+    type IntoIter = alloc::vec::IntoIter<T>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.data.into_iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Vector<T> {
+    type Item = &'a T;
+    type IntoIter = core::slice::Iter<'a, T>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.data.iter()
+    }
+}
+
+impl<T> FromIterator<T> for Vector<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self::new(iter.into_iter().collect::<Vec<_>>())
+    }
+}
+
+impl<T> Extend<T> for Vector<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.data.extend(iter);
+    }
+}
+
+macro_rules! impl_norms {
+    ($($t:ty),*) => {$(
+        #[cfg(feature = "std")]
+        impl Vector<$t> {
+            /// The euclidean length, the square root of the sum of squares.
+            pub fn norm_l2(&self) -> $t {
+                par_map_reduce(&self.data, 0.0, |x| x * x, Add::add).sqrt()
+            }
+
+            /// The sum of the absolute values.
+            pub fn norm_l1(&self) -> $t {
+                par_map_reduce(&self.data, 0.0, |x| x.abs(), Add::add)
+            }
+
+            /// This vector divided by its `norm_l2`, or `None` if that is zero or
+            /// not finite.
+            pub fn normalize(&self) -> Option<Self> {
+                let norm = self.norm_l2();
+                if norm == 0.0 || !norm.is_finite() {
+                    return None;
+                }
+                Some(Self::new(self.data.iter().map(|x| x / norm).collect::<Vec<_>>()))
+            }
+        }
+    )*};
+}
+
+impl_norms!(f32, f64);
+
+// `a` or `b`, whichever `better` prefers, `a` on ties. An element that does not
+// compare with itself is dropped
+fn pick<T: PartialOrd>(a: Option<T>, b: Option<T>, better: impl Fn(&T, &T) -> bool) -> Option<T> {
+    match (a, b) {
+        (Some(a), Some(b)) if a.partial_cmp(&a).is_none() => Some(b),
+        (Some(a), Some(b)) if b.partial_cmp(&b).is_none() || !better(&b, &a) => Some(a),
+        (a, b) => b.or(a),
+    }
+}
+
+// `f` of consecutive ranges covering `0..len`, one per thread once `work`, the
+// elements touched in all, is large enough, else of the whole of `0..len` on the
+// calling thread
+fn par_chunks<R, F>(len: usize, work: usize, f: F) -> Vec<R>
+where
+    R: Send,
+    F: Fn(Range<usize>) -> R + Sync,
+{
+    #[cfg(feature = "threads")]
+    if work >= PARALLEL_MIN && len > 1 {
+        let chunk = len.div_ceil(THREAD_NUM);
+        let f = &f;
+        return thread::scope(|s| {
+            let handles = (0..len)
+                .step_by(chunk)
+                .map(|start| s.spawn(move || f(start..(start + chunk).min(len))))
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|e| std::panic::resume_unwind(e))
+                })
+                .collect()
+        });
+    }
+    #[cfg(not(feature = "threads"))]
+    let _ = work;
+    alloc::vec![f(0..len)]
+}
+
+// map/reduce of `data`: `map` of every element folded with `op` from `identity`,
+// per chunk and then over the partial results
+fn par_map_reduce<T, R, M, F>(data: &[T], identity: R, map: M, op: F) -> R
+where
+    T: Sync,
+    R: Clone + Send + Sync,
+    M: Fn(&T) -> R + Sync,
+    F: Fn(R, R) -> R + Sync,
+{
+    let partials = par_chunks(data.len(), data.len(), |range| {
+        data[range]
+            .iter()
+            .fold(identity.clone(), |acc, x| op(acc, map(x)))
+    });
+    partials.into_iter().fold(identity, &op)
+}
+
+/// Why a vector operation failed, with the lengths involved so callers can report
+/// which operand was wrong without parsing the message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum VectorError {
+    /// two vectors (or slices) that must be equally long are not, `left` the
+    /// length of the first operand and `right` of the second
+    #[error("Dot product error: a.len != b.len ({left} != {right})")]
+    LengthMismatch { left: usize, right: usize },
+    /// `cross` of vectors that do not both have three elements
+    #[error("Cross product error: a.len and b.len must be 3 ({left}, {right})")]
+    NotThreeDimensional { left: usize, right: usize },
+}
+
 // pretend this is a heavy operation, CPU intensive
-pub fn dot_product<T>(a: Vector<T>, b: Vector<T>) -> Result<T>
+pub fn dot_product<T>(a: Vector<T>, b: Vector<T>) -> Result<T, VectorError>
 where
-    T: Copy + Default + Add<Output = T> + AddAssign + Mul<Output = T>,
+    T: Clone + Default + Add<Output = T> + AddAssign + Mul<Output = T>,
+{
+    dot_product_slice(&a, &b)
+}
+
+/// `dot_product` of borrowed data, e.g. a row of a matrix or a `&Vector` (which
+/// derefs to a slice), without moving or copying it.
+pub fn dot_product_slice<T>(a: &[T], b: &[T]) -> Result<T, VectorError>
+where
+    T: Clone + Default + Add<Output = T> + AddAssign + Mul<Output = T>,
 {
     if a.len() != b.len() {
-        anyhow::bail!("Dot product error: a.len != b.len");
+        return Err(VectorError::LengthMismatch {
+            left: a.len(),
+            right: b.len(),
+        });
     }
 
     let mut sum = T::default();
     for i in 0..a.len() {
-        sum += a[i] * b[i];
+        sum += a[i].clone() * b[i].clone();
     }
 
     Ok(sum)
 }
+
+/// `dot_product_slice` with Kahan's compensated summation: the low-order bits each
+/// float addition rounds off are carried over into the next one, so the error
+/// stays around one rounding however long the vectors, instead of growing with
+/// their length. Costs about four times the additions; for integers the
+/// compensation is always zero and the result is the plain sum.
+pub fn dot_product_kahan<T>(a: &[T], b: &[T]) -> Result<T, VectorError>
+where
+    T: Clone + Default + Add<Output = T> + Sub<Output = T> + Mul<Output = T>,
+{
+    if a.len() != b.len() {
+        return Err(VectorError::LengthMismatch {
+            left: a.len(),
+            right: b.len(),
+        });
+    }
+
+    let (mut sum, mut lost) = (T::default(), T::default());
+    for (x, y) in a.iter().zip(b) {
+        let term = x.clone() * y.clone() - lost;
+        let next = sum.clone() + term.clone();
+        // what of `term` did not make it into `next`
+        lost = (next.clone() - sum) - term;
+        sum = next;
+    }
+
+    Ok(sum)
+}
+
+/// `dot_product_slice` split into one chunk per thread once the vectors are long
+/// enough, the partial sums added up on the calling thread.
+///
+/// The sum is taken in a different order than `dot_product`, so float results
+/// may differ in the last bits.
+pub fn par_dot_product<T>(a: &[T], b: &[T]) -> Result<T, VectorError>
+where
+    T: Clone + Default + Add<Output = T> + AddAssign + Mul<Output = T> + Send + Sync,
+{
+    if a.len() != b.len() {
+        return Err(VectorError::LengthMismatch {
+            left: a.len(),
+            right: b.len(),
+        });
+    }
+    // map/reduce: a partial sum per chunk, added up on the calling thread
+    let mut sum = T::default();
+    for partial in par_chunks(a.len(), a.len(), |range| {
+        dot_product_slice(&a[range.clone()], &b[range])
+    }) {
+        sum += partial?;
+    }
+    Ok(sum)
+}
+
+/// `dot_product_slice` of every pair, e.g. one query embedding against many, in
+/// one call. The pairs are split into one batch per thread once there is enough
+/// work in all. Fails with the `LengthMismatch` of the first pair whose slices
+/// differ in length, before anything is computed.
+pub fn dot_products<T>(pairs: &[(&[T], &[T])]) -> Result<Vec<T>, VectorError>
+where
+    T: Clone + Default + Add<Output = T> + AddAssign + Mul<Output = T> + Send + Sync,
+{
+    if let Some((a, b)) = pairs.iter().find(|(a, b)| a.len() != b.len()) {
+        return Err(VectorError::LengthMismatch {
+            left: a.len(),
+            right: b.len(),
+        });
+    }
+    let work = pairs.iter().map(|(a, _)| a.len()).sum();
+    // map/reduce: each batch of pairs maps to its products, concatenated in order
+    let batches = par_chunks(pairs.len(), work, |range| {
+        pairs[range]
+            .iter()
+            .map(|(a, b)| dot_product_slice(a, b))
+            .collect::<Result<Vec<_>, _>>()
+    });
+    let mut products = Vec::with_capacity(pairs.len());
+    for batch in batches {
+        products.extend(batch?);
+    }
+    Ok(products)
+}
+
+/// `dot_product` with the sum starting from `T::zero()` instead of `T::default()`.
+#[cfg(feature = "num-traits")]
+pub fn dot_product_num<T>(a: Vector<T>, b: Vector<T>) -> Result<T, VectorError>
+where
+    T: num_traits::Zero + Mul<Output = T> + Clone,
+{
+    if a.len() != b.len() {
+        return Err(VectorError::LengthMismatch {
+            left: a.len(),
+            right: b.len(),
+        });
+    }
+
+    Ok(a.iter()
+        .zip(b.iter())
+        .fold(T::zero(), |sum, (x, y)| sum + x.clone() * y.clone()))
+}
+
+/// An accumulator for products of `T`, e.g. `i64` for `i8`/`i16`/`i32` inputs, so
+/// integer dot products of realistic lengths do not overflow.
+///
+/// Implemented for every `Acc` that converts losslessly from `T`, including `T`
+/// itself.
+pub trait Accumulate<T>: Clone + Default {
+    /// `self + a * b`, with `a` and `b` widened before they are multiplied
+    fn mul_add(self, a: T, b: T) -> Self;
+}
+
+impl<T, Acc> Accumulate<T> for Acc
+where
+    Acc: From<T> + Clone + Default + Add<Output = Acc> + Mul<Output = Acc>,
+{
+    fn mul_add(self, a: T, b: T) -> Self {
+        self + Acc::from(a) * Acc::from(b)
+    }
+}
+
+/// Like `dot_product`, but summed into the wider `Acc`.
+pub fn dot_product_widening<T, Acc>(a: Vector<T>, b: Vector<T>) -> Result<Acc, VectorError>
+where
+    T: Clone,
+    Acc: Accumulate<T>,
+{
+    if a.len() != b.len() {
+        return Err(VectorError::LengthMismatch {
+            left: a.len(),
+            right: b.len(),
+        });
+    }
+
+    Ok(a.iter().zip(b.iter()).fold(Acc::default(), |acc, (x, y)| {
+        acc.mul_add(x.clone(), y.clone())
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dot_product_widening() {
+        let a = Vector::new([i8::MAX; 100]);
+        let b = Vector::new([i8::MIN; 100]);
+        let sum: i64 = dot_product_widening(a, b).unwrap();
+        assert_eq!(sum, 100 * 127 * -128);
+
+        let sum: i32 = dot_product_widening(Vector::new([3i16, 4]), Vector::new([5, 6])).unwrap();
+        assert_eq!(sum, 39);
+
+        let rst = dot_product_widening::<i32, i64>(Vector::new([1]), Vector::new([1, 2]));
+        assert_eq!(
+            rst.unwrap_err(),
+            VectorError::LengthMismatch { left: 1, right: 2 }
+        );
+    }
+
+    #[test]
+    fn test_dot_product_slice() {
+        let a = Vector::new([1, 2, 3]);
+        let b = [4, 5, 6];
+        assert_eq!(dot_product_slice(&a, &b).unwrap(), 32);
+        assert_eq!(dot_product_slice(&a[1..], &b[..2]).unwrap(), 23);
+        // `a` is still ours
+        assert_eq!(dot_product(a, Vector::new(b)).unwrap(), 32);
+        assert_eq!(
+            dot_product_slice(&[1.0], &[]).unwrap_err(),
+            VectorError::LengthMismatch { left: 1, right: 0 }
+        );
+    }
+
+    #[test]
+    fn test_par_dot_product() {
+        assert_eq!(par_dot_product(&[1, 2, 3], &[4, 5, 6]).unwrap(), 32);
+
+        let n = (1 << 14) * 3 + 7;
+        let a = (0..n as i64).collect::<Vec<_>>();
+        let b = alloc::vec![2; n];
+        assert_eq!(
+            par_dot_product(&a, &b).unwrap(),
+            dot_product_slice(&a, &b).unwrap()
+        );
+        assert_eq!(
+            par_dot_product(&a, &b[1..]).unwrap_err(),
+            VectorError::LengthMismatch {
+                left: n,
+                right: n - 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_dot_product_kahan() {
+        // every 1e-16 is below half an ulp of 1.0, the plain sum drops them all
+        let n = 10_000;
+        let a = [1.0]
+            .into_iter()
+            .chain(core::iter::repeat_n(1e-16, n))
+            .collect::<Vec<f64>>();
+        let b = alloc::vec![1.0; n + 1];
+        assert_eq!(dot_product_slice(&a, &b).unwrap(), 1.0);
+        let sum = dot_product_kahan(&a, &b).unwrap();
+        assert!((sum - (1.0 + 1e-12)).abs() < 1e-15, "{sum}");
+
+        assert_eq!(dot_product_kahan(&[1, 2, 3], &[4, 5, 6]).unwrap(), 32);
+        assert_eq!(
+            dot_product_kahan(&[1.0f32], &[]).unwrap_err(),
+            VectorError::LengthMismatch { left: 1, right: 0 }
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_vector_norms() {
+        let v = Vector::new([3.0f64, -4.0]);
+        assert_eq!(v.norm_l2(), 5.0);
+        assert_eq!(v.norm_l1(), 7.0);
+        assert_eq!(v.normalize().unwrap(), Vector::new([0.6, -0.8]));
+        assert_eq!(Vector::<f32>::new([0.0, 0.0]).normalize(), None);
+        assert_eq!(Vector::<f64>::new([]).norm_l2(), 0.0);
+
+        // long enough to be summed in chunks
+        let n = (1 << 14) * 2 + 1;
+        let v = Vector::new(alloc::vec![-0.5f32; n]);
+        assert_eq!(v.norm_l1(), n as f32 * 0.5);
+        assert!((v.normalize().unwrap().norm_l2() - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_vector_axpy() {
+        let mut y = Vector::new([1, 2, 3]);
+        y.axpy(2, &Vector::new([10, 20, 30])).unwrap();
+        assert_eq!(y, Vector::new([21, 42, 63]));
+        assert_eq!(
+            y.axpy(1, &Vector::new([1])).unwrap_err(),
+            VectorError::LengthMismatch { left: 3, right: 1 }
+        );
+        assert_eq!(y, Vector::new([21, 42, 63]));
+
+        let n = (1 << 14) * 2 + 3;
+        let mut y = Vector::new(alloc::vec![1.0; n]);
+        y.axpy(-0.5, &Vector::new(alloc::vec![4.0; n])).unwrap();
+        assert!(y.iter().all(|&v| v == -1.0));
+    }
+
+    #[test]
+    fn test_vector_iterators() {
+        let mut v = (1..=3).collect::<Vector<i32>>();
+        v.extend([4, 5]);
+        assert_eq!(v, Vector::new([1, 2, 3, 4, 5]));
+
+        let mut sum = 0;
+        for x in &v {
+            sum += x;
+        }
+        assert_eq!(sum, 15);
+        let doubled = v.into_iter().map(|x| x * 2).collect::<Vector<_>>();
+        assert_eq!(doubled, Vector::new([2, 4, 6, 8, 10]));
+    }
+
+    #[test]
+    fn test_vector_slice() {
+        let v = (0..10).collect::<Vector<i32>>();
+        let head = v.slice(..4);
+        assert!(head.is_borrowed());
+        assert_eq!(*head, [0, 1, 2, 3]);
+        assert_eq!(*v.slice(7..), [7, 8, 9]);
+        assert_eq!(*head.slice(1..=2), [1, 2]);
+        assert!(v.slice(3..3).is_empty());
+        // halves multiplied without copying either
+        assert_eq!(dot_product_slice(&v.slice(..5), &v.slice(5..)).unwrap(), 80);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_vector_slice_out_of_bounds() {
+        Vector::new([1, 2]).slice(1..3);
+    }
+
+    #[test]
+    fn test_vector_view() {
+        let v = Vector::new([1, 2, 3]);
+        let view = v.as_view();
+        assert!(view.is_borrowed());
+        assert_eq!(dot_product_slice(&view, &v).unwrap(), 14);
+        assert_eq!(view.into_vector(), v);
+
+        let owned = v.iter().map(|x| x * 2).collect::<VectorView<_>>();
+        assert!(!owned.is_borrowed());
+        assert_eq!(*owned, [2, 4, 6]);
+    }
+
+    #[test]
+    fn test_vector_error_messages() {
+        let err = dot_product(Vector::new([1, 2]), Vector::new([1, 2, 3])).unwrap_err();
+        assert_eq!(err, VectorError::LengthMismatch { left: 2, right: 3 });
+        assert_eq!(
+            err.to_string(),
+            "Dot product error: a.len != b.len (2 != 3)"
+        );
+        let err = Vector::new([1]).cross(&Vector::new([1, 2, 3])).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Cross product error: a.len and b.len must be 3 (1, 3)"
+        );
+    }
+
+    #[test]
+    fn test_vector_zip_map() {
+        let a = Vector::new([1, 5, 3]);
+        let b = Vector::new([4.0, 2.0, 6.0]);
+        let max = a.zip_map(&b, |&x, &y| (x as f64).max(y)).unwrap();
+        assert_eq!(max, Vector::new([4.0, 5.0, 6.0]));
+        assert_eq!(
+            a.zip_map(&Vector::new([1]), |x, y| x + y).unwrap_err(),
+            VectorError::LengthMismatch { left: 3, right: 1 }
+        );
+    }
+
+    #[test]
+    fn test_dot_products() {
+        let query = [1.0, 0.5];
+        let docs = [[2.0, 2.0], [0.0, 4.0], [-1.0, 0.0]];
+        let pairs = docs
+            .iter()
+            .map(|d| (&query[..], &d[..]))
+            .collect::<Vec<_>>();
+        assert_eq!(dot_products(&pairs).unwrap(), [3.0, 2.0, -1.0]);
+        assert!(dot_products::<i32>(&[]).unwrap().is_empty());
+
+        // enough work for the threads, in order
+        let rows = (0..64)
+            .map(|i| (0..512).map(|j| i * j).collect::<Vec<i64>>())
+            .collect::<Vec<_>>();
+        let ones = alloc::vec![1; 512];
+        let pairs = rows.iter().map(|r| (&r[..], &ones[..])).collect::<Vec<_>>();
+        let sums = dot_products(&pairs).unwrap();
+        assert!(sums
+            .iter()
+            .enumerate()
+            .all(|(i, &s)| s == i as i64 * 511 * 256));
+
+        let short = [1i64];
+        let mut pairs = pairs;
+        pairs[5] = (&short[..], &ones[..]);
+        assert_eq!(
+            dot_products(&pairs).unwrap_err(),
+            VectorError::LengthMismatch {
+                left: 1,
+                right: 512
+            }
+        );
+    }
+
+    #[test]
+    fn test_vector_par_reduce() {
+        let v = Vector::new([3, -1, 4, 1, -5]);
+        assert_eq!(v.par_sum(), 2);
+        assert_eq!(v.par_reduce(1, |x, y| x * y), 60);
+        assert_eq!((v.par_min(), v.par_max()), (Some(-5), Some(4)));
+        assert_eq!(Vector::<i32>::new([]).par_max(), None);
+
+        let f = Vector::new([f64::NAN, 2.0, -1.0, f64::NAN]);
+        assert_eq!((f.par_min(), f.par_max()), (Some(-1.0), Some(2.0)));
+
+        // long enough to be split into chunks
+        let n = (1 << 14) * 3 + 1;
+        let v = (0..n as i64).collect::<Vector<_>>();
+        assert_eq!(v.par_sum(), (n as i64 - 1) * n as i64 / 2);
+        assert_eq!((v.par_min(), v.par_max()), (Some(0), Some(n as i64 - 1)));
+    }
+
+    #[test]
+    fn test_vector_cross() {
+        let x = Vector::new([1, 0, 0]);
+        let y = Vector::new([0, 1, 0]);
+        assert_eq!(x.cross(&y).unwrap(), Vector::new([0, 0, 1]));
+        assert_eq!(y.cross(&x).unwrap(), Vector::new([0, 0, -1]));
+        let a = Vector::new([2.0, 3.0, 4.0]);
+        assert_eq!(
+            a.cross(&Vector::new([5.0, 6.0, 7.0])).unwrap(),
+            Vector::new([-3.0, 6.0, -3.0])
+        );
+        assert_eq!(
+            a.cross(&Vector::new([1.0, 2.0])).unwrap_err(),
+            VectorError::NotThreeDimensional { left: 3, right: 2 }
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_vector_serde_round_trip() {
+        let v = Vector::new([1.5, -2.0, 3.0]);
+        let json = serde_json::to_string(&v).unwrap();
+        assert_eq!(json, "[1.5,-2.0,3.0]");
+        assert_eq!(serde_json::from_str::<Vector<f64>>(&json).unwrap(), v);
+        assert!(serde_json::from_str::<Vector<i32>>(r#"{"data":[1]}"#).is_err());
+    }
+
+    #[cfg(feature = "num-traits")]
+    #[test]
+    fn test_dot_product_num() {
+        let sum = dot_product_num(Vector::new([1.5, 2.0]), Vector::new([2.0, -1.0])).unwrap();
+        assert_eq!(sum, 1.0);
+        assert_eq!(
+            dot_product_num::<i32>(Vector::new([]), Vector::new([1])).unwrap_err(),
+            VectorError::LengthMismatch { left: 0, right: 1 }
+        );
+    }
+}